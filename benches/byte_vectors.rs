@@ -0,0 +1,108 @@
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+use serde_derive::{Deserialize, Serialize};
+
+mod common;
+use common::Syscalls;
+
+// Both encode to the same bytes, since `[]u8` and `data` share a wire format, but serde hands a
+// plain Vec<u8> over one element at a time.
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct PlainBlob {
+    payload: Vec<u8>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct DataBlob {
+    #[serde(with = "serde_bytes")]
+    payload: Vec<u8>,
+}
+
+fn payload() -> Vec<u8> {
+    (0..4096u32).map(|i| i as u8).collect()
+}
+
+fn serialize_blob(c: &mut Criterion) {
+    let plain = PlainBlob { payload: payload() };
+    let data = DataBlob { payload: payload() };
+    let ser = serde_bare::to_vec(&data).unwrap();
+    assert_eq!(serde_bare::to_vec(&plain).unwrap(), ser);
+
+    let mut group = c.benchmark_group("serialization");
+    group.throughput(Throughput::Bytes(ser.len() as u64));
+    group.bench_function("serialize blob Vec<u8>", |b| {
+        b.iter(|| serde_bare::to_vec(&plain).unwrap())
+    });
+    group.bench_function("serialize blob serde_bytes", |b| {
+        b.iter(|| serde_bare::to_vec(&data).unwrap())
+    });
+    group.finish();
+}
+
+fn deserialize_blob(c: &mut Criterion) {
+    let ser = serde_bare::to_vec(&DataBlob { payload: payload() }).unwrap();
+
+    let mut group = c.benchmark_group("deserialization");
+    group.throughput(Throughput::Bytes(ser.len() as u64));
+    group.bench_function("deserialize blob Vec<u8>", |b| {
+        b.iter(|| serde_bare::from_slice::<PlainBlob>(&ser).unwrap())
+    });
+    group.bench_function("deserialize blob serde_bytes", |b| {
+        b.iter(|| serde_bare::from_slice::<DataBlob>(&ser).unwrap())
+    });
+    group.finish();
+}
+
+fn deserialize_large_blob(c: &mut Criterion) {
+    let payload = (0..1 << 20).map(|i: u32| i as u8).collect();
+    let ser = serde_bare::to_vec(&DataBlob { payload }).unwrap();
+
+    let mut group = c.benchmark_group("deserialization");
+    group.throughput(Throughput::Bytes(ser.len() as u64));
+    group.bench_function("deserialize 1MB blob serde_bytes", |b| {
+        b.iter(|| serde_bare::from_slice::<DataBlob>(&ser).unwrap())
+    });
+    group.finish();
+}
+
+fn serialize_unbuffered_seq(c: &mut Criterion) {
+    let payload: Vec<u8> = (0..64 * 1024u32).map(|i| i as u8).collect();
+
+    let mut group = c.benchmark_group("serialization");
+    group.throughput(Throughput::Bytes(payload.len() as u64));
+    group.bench_function("serialize 64KiB Vec<u8> unbuffered", |b| {
+        b.iter(|| serde_bare::to_writer(Syscalls(0), &payload).unwrap())
+    });
+    group.finish();
+}
+
+fn deserialize_hash_list(c: &mut Criterion) {
+    let hashes: Vec<serde_bare::Data<32>> = (0..10_000u32)
+        .map(|i| serde_bare::Data([i as u8; 32]))
+        .collect();
+    let ser = serde_bare::to_vec(&hashes).unwrap();
+
+    // serde reads a Vec<Data<32>> a byte at a time; deserialize_data_vec reads it in chunks.
+    let mut group = c.benchmark_group("deserialization");
+    group.throughput(Throughput::Bytes(ser.len() as u64));
+    group.bench_function("deserialize 10K Data<32> serde", |b| {
+        b.iter(|| serde_bare::from_slice::<Vec<serde_bare::Data<32>>>(&ser).unwrap())
+    });
+    group.bench_function("deserialize 10K Data<32> deserialize_data_vec", |b| {
+        b.iter(|| {
+            let mut deserializer = serde_bare::Deserializer::new(&ser[..]);
+            serde_bare::deserialize_data_vec::<_, 32>(&mut deserializer).unwrap()
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(
+    blob,
+    serialize_blob,
+    serialize_unbuffered_seq,
+    deserialize_blob,
+    deserialize_large_blob,
+    deserialize_hash_list
+);
+criterion_main!(blob);
@@ -0,0 +1,117 @@
+//! Helpers shared by the benchmarks. Each benchmark uses only some of them.
+#![allow(dead_code)]
+
+use criterion::measurement::{Measurement, ValueFormatter};
+use criterion::{Criterion, Throughput};
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// A writer where every write costs something, like an unbuffered file or socket.
+pub struct Syscalls(pub u64);
+
+impl std::io::Write for Syscalls {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0 = criterion::black_box(self.0 + 1);
+        std::thread::yield_now();
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Counts allocations, for benchmarks that compare how often decoding allocates. A benchmark
+/// using it declares `#[global_allocator] static ALLOCATOR: CountingAllocator = CountingAllocator;`
+/// and measures with `Allocations`.
+pub struct CountingAllocator;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+/// A criterion measurement of the allocations made through `CountingAllocator`, rather than of
+/// time.
+pub struct Allocations;
+
+impl Measurement for Allocations {
+    type Intermediate = usize;
+    type Value = usize;
+
+    fn start(&self) -> usize {
+        ALLOCATIONS.load(Ordering::Relaxed)
+    }
+
+    fn end(&self, start: usize) -> usize {
+        ALLOCATIONS.load(Ordering::Relaxed) - start
+    }
+
+    fn add(&self, v1: &usize, v2: &usize) -> usize {
+        v1 + v2
+    }
+
+    fn zero(&self) -> usize {
+        0
+    }
+
+    /// Criterion rejects samples of zero, which is what a routine that doesn't allocate gives,
+    /// so those are nudged up by an amount too small to show.
+    fn to_f64(&self, value: &usize) -> f64 {
+        if *value == 0 {
+            1e-9
+        } else {
+            *value as f64
+        }
+    }
+
+    fn formatter(&self) -> &dyn ValueFormatter {
+        &AllocationsFormatter
+    }
+}
+
+/// A `Criterion` measuring `Allocations`, configured from the command line like
+/// `criterion_group!` does. The counts don't vary between runs, which criterion can't plot, so
+/// plots are turned off.
+pub fn allocations_criterion() -> Criterion<Allocations> {
+    Criterion::default()
+        .with_measurement(Allocations)
+        .configure_from_args()
+        .without_plots()
+}
+
+struct AllocationsFormatter;
+
+impl ValueFormatter for AllocationsFormatter {
+    fn scale_values(&self, _typical_value: f64, _values: &mut [f64]) -> &'static str {
+        "allocs"
+    }
+
+    fn scale_throughputs(
+        &self,
+        _typical_value: f64,
+        throughput: &Throughput,
+        values: &mut [f64],
+    ) -> &'static str {
+        let (count, unit) = match *throughput {
+            Throughput::Bytes(bytes) => (bytes, "allocs/B"),
+            Throughput::Elements(elements) => (elements, "allocs/elem"),
+        };
+        for value in values {
+            *value /= count as f64;
+        }
+        unit
+    }
+
+    fn scale_for_machines(&self, _values: &mut [f64]) -> &'static str {
+        "allocs"
+    }
+}
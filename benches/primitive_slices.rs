@@ -0,0 +1,49 @@
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+use serde::Serialize;
+use serde_bare::Serializer;
+
+mod common;
+use common::Syscalls;
+
+// serde hands a slice over one element at a time, which is one write per element. The
+// `serialize_*_slice` helpers write it a chunk at a time instead.
+
+fn values() -> Vec<u32> {
+    (0..16 * 1024u32)
+        .map(|i| i.wrapping_mul(0x9e37_79b9))
+        .collect()
+}
+
+fn serialize_u32_slice(c: &mut Criterion) {
+    let values = values();
+    let len = serde_bare::to_vec(&values).unwrap().len();
+
+    let mut group = c.benchmark_group("serialization");
+    group.throughput(Throughput::Bytes(len as u64));
+    group.bench_function("serialize 16K u32 serde", |b| {
+        b.iter(|| {
+            let mut serializer = Serializer::new(Vec::with_capacity(len));
+            values.serialize(&mut serializer).unwrap();
+            serializer.into_inner()
+        })
+    });
+    group.bench_function("serialize 16K u32 serialize_u32_slice", |b| {
+        b.iter(|| {
+            let mut serializer = Serializer::new(Vec::with_capacity(len));
+            serde_bare::serialize_u32_slice(&mut serializer, &values).unwrap();
+            serializer.into_inner()
+        })
+    });
+    group.bench_function("serialize 16K u32 serde unbuffered", |b| {
+        b.iter(|| values.serialize(&mut Serializer::new(Syscalls(0))).unwrap())
+    });
+    group.bench_function("serialize 16K u32 serialize_u32_slice unbuffered", |b| {
+        b.iter(|| {
+            serde_bare::serialize_u32_slice(&mut Serializer::new(Syscalls(0)), &values).unwrap()
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(slices, serialize_u32_slice);
+criterion_main!(slices);
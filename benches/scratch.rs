@@ -0,0 +1,114 @@
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+use serde::de::{Deserialize, Deserializer, Visitor};
+use serde_derive::{Deserialize, Serialize};
+use std::fmt;
+
+mod common;
+use common::{Allocations, CountingAllocator};
+
+// Decodes a thousand session tokens into a type that only borrows the bytes while it's decoded,
+// once with a new deserializer per message and once with one deserializer reset between them.
+// Both are timed, and counted in allocations.
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+/// The first 8 bytes of a token.
+struct TokenPrefix([u8; 8]);
+
+impl<'de> Deserialize<'de> for TokenPrefix {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct PrefixVisitor;
+        impl Visitor<'_> for PrefixVisitor {
+            type Value = TokenPrefix;
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("a token")
+            }
+            fn visit_bytes<E>(self, v: &[u8]) -> Result<TokenPrefix, E> {
+                let mut prefix = [0; 8];
+                let len = v.len().min(8);
+                prefix[..len].copy_from_slice(&v[..len]);
+                Ok(TokenPrefix(prefix))
+            }
+        }
+        deserializer.deserialize_bytes(PrefixVisitor)
+    }
+}
+
+#[derive(Serialize)]
+struct Session {
+    #[serde(with = "serde_bytes")]
+    token: Vec<u8>,
+    expires: u64,
+}
+
+#[derive(Deserialize)]
+struct SessionRef {
+    token: TokenPrefix,
+    expires: u64,
+}
+
+fn messages() -> Vec<Vec<u8>> {
+    (0..1000u64)
+        .map(|i| {
+            let session = Session {
+                token: format!("{:064x}", i).into_bytes(),
+                expires: i,
+            };
+            serde_bare::to_vec(&session).unwrap()
+        })
+        .collect()
+}
+
+fn decode_fresh(messages: &[Vec<u8>]) -> u64 {
+    let mut total = 0;
+    for message in messages {
+        let session: SessionRef = serde_bare::from_slice(message).unwrap();
+        total += session.expires + u64::from(session.token.0[0]);
+    }
+    total
+}
+
+fn decode_reset(messages: &[Vec<u8>]) -> u64 {
+    let mut total = 0;
+    let mut deserializer = serde_bare::Deserializer::new(&[][..]);
+    for message in messages {
+        deserializer.reset(message);
+        let session = SessionRef::deserialize(&mut deserializer).unwrap();
+        total += session.expires + u64::from(session.token.0[0]);
+    }
+    total
+}
+
+fn deserialize_sessions(c: &mut Criterion) {
+    let messages = messages();
+    let mut group = c.benchmark_group("deserialization");
+    group.throughput(Throughput::Elements(messages.len() as u64));
+    group.bench_function("deserialize 1000 sessions", |b| {
+        b.iter(|| decode_fresh(&messages))
+    });
+    group.bench_function("deserialize 1000 sessions with reset", |b| {
+        b.iter(|| decode_reset(&messages))
+    });
+    group.finish();
+}
+
+fn count_session_allocations(c: &mut Criterion<Allocations>) {
+    let messages = messages();
+    let mut group = c.benchmark_group("allocations");
+    group.throughput(Throughput::Elements(messages.len() as u64));
+    group.bench_function("deserialize 1000 sessions", |b| {
+        b.iter(|| decode_fresh(&messages))
+    });
+    group.bench_function("deserialize 1000 sessions with reset", |b| {
+        b.iter(|| decode_reset(&messages))
+    });
+    group.finish();
+}
+
+criterion_group!(benches, deserialize_sessions);
+
+fn allocations() {
+    count_session_allocations(&mut common::allocations_criterion());
+}
+criterion_main!(benches, allocations);
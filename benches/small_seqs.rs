@@ -0,0 +1,69 @@
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+use smallvec::SmallVec;
+
+mod common;
+use common::{Allocations, CountingAllocator};
+
+// Decodes a thousand three-element lists, into a Vec, which allocates for each one, and into a
+// SmallVec with room for four, which keeps them inline. Both are timed, and counted in
+// allocations.
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+fn messages() -> Vec<Vec<u8>> {
+    (0..1000u32)
+        .map(|i| serde_bare::to_vec(&[i, i + 1, i + 2][..]).unwrap())
+        .collect()
+}
+
+fn decode_vec(messages: &[Vec<u8>]) -> u32 {
+    let mut total = 0;
+    for message in messages {
+        let list: Vec<u32> = serde_bare::from_slice_borrowed(message).unwrap();
+        total += list[2];
+    }
+    total
+}
+
+fn decode_smallvec(messages: &[Vec<u8>]) -> u32 {
+    let mut total = 0;
+    for message in messages {
+        let list: SmallVec<[u32; 4]> = serde_bare::from_slice_borrowed(message).unwrap();
+        total += list[2];
+    }
+    total
+}
+
+fn deserialize_lists(c: &mut Criterion) {
+    let messages = messages();
+    let mut group = c.benchmark_group("deserialization");
+    group.throughput(Throughput::Elements(messages.len() as u64));
+    group.bench_function("deserialize 1000 lists Vec<u32>", |b| {
+        b.iter(|| decode_vec(&messages))
+    });
+    group.bench_function("deserialize 1000 lists SmallVec<[u32; 4]>", |b| {
+        b.iter(|| decode_smallvec(&messages))
+    });
+    group.finish();
+}
+
+fn count_list_allocations(c: &mut Criterion<Allocations>) {
+    let messages = messages();
+    let mut group = c.benchmark_group("allocations");
+    group.throughput(Throughput::Elements(messages.len() as u64));
+    group.bench_function("deserialize 1000 lists Vec<u32>", |b| {
+        b.iter(|| decode_vec(&messages))
+    });
+    group.bench_function("deserialize 1000 lists SmallVec<[u32; 4]>", |b| {
+        b.iter(|| decode_smallvec(&messages))
+    });
+    group.finish();
+}
+
+criterion_group!(benches, deserialize_lists);
+
+fn allocations() {
+    count_list_allocations(&mut common::allocations_criterion());
+}
+criterion_main!(benches, allocations);
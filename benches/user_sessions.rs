@@ -1,21 +1,22 @@
 use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+use serde_bare_derive::BareSize;
 use serde_derive::{Deserialize, Serialize};
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, BareSize)]
 enum UserRole {
     Admin,
     User,
     Guest,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, BareSize)]
 struct Session {
     #[serde(with = "serde_bytes")]
     token: Vec<u8>,
     expires: u64,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, BareSize)]
 struct User {
     id: u32,
     name: String,
@@ -60,6 +61,12 @@ fn serialize_admin(c: &mut Criterion) {
     group.bench_function("serialize admin", |b| {
         b.iter(|| serde_bare::to_writer(&mut buffer[..], &sample).unwrap())
     });
+    group.bench_function("serialize admin to_vec", |b| {
+        b.iter(|| serde_bare::to_vec(&sample).unwrap())
+    });
+    group.bench_function("serialize admin to_vec_presized", |b| {
+        b.iter(|| serde_bare::to_vec_presized(&sample).unwrap())
+    });
     group.finish();
 }
 
@@ -97,6 +104,32 @@ fn deserialize_guest(c: &mut Criterion) {
     group.finish();
 }
 
-criterion_group!(admin, serialize_admin, deserialize_admin);
+fn serialize_many(c: &mut Criterion) {
+    let (sample, ser) = admin_sample();
+    let messages = vec![sample; 1000];
+    let mut group = c.benchmark_group("serialization");
+    group.throughput(Throughput::Bytes(ser.len() as u64 * messages.len() as u64));
+
+    group.bench_function("serialize 1000 admins to_vec", |b| {
+        b.iter(|| {
+            for message in &messages {
+                criterion::black_box(serde_bare::to_vec(message).unwrap());
+            }
+        })
+    });
+    let mut buf = Vec::new();
+    group.bench_function("serialize 1000 admins to_vec_in reused buffer", |b| {
+        b.iter(|| {
+            for message in &messages {
+                buf.clear();
+                serde_bare::to_vec_in(&mut buf, message).unwrap();
+                criterion::black_box(&buf);
+            }
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(admin, serialize_admin, deserialize_admin, serialize_many);
 criterion_group!(guest, serialize_guest, deserialize_guest);
 criterion_main!(admin, guest);
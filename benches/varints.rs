@@ -0,0 +1,30 @@
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+use serde_bare::Uint;
+
+// Length prefixes are read by the deserializer directly, and so are `Uint` values, which ask for
+// themselves by name through their serde impl. Both inputs are 1000 three-byte varints: zero-length strings with
+// non-minimal prefixes, and the same bytes as Uints, so neither allocates per element.
+
+fn varints() -> Vec<u8> {
+    let mut bytes = vec![0xe8, 0x07];
+    for _ in 0..1000 {
+        bytes.extend_from_slice(&[0x80, 0x80, 0x00]);
+    }
+    bytes
+}
+
+fn deserialize_varints(c: &mut Criterion) {
+    let bytes = varints();
+    let mut group = c.benchmark_group("deserialization");
+    group.throughput(Throughput::Elements(1000));
+    group.bench_function("deserialize 1000 length prefixes", |b| {
+        b.iter(|| serde_bare::from_slice::<Vec<String>>(&bytes).unwrap())
+    });
+    group.bench_function("deserialize 1000 Uints", |b| {
+        b.iter(|| serde_bare::from_slice::<Vec<Uint>>(&bytes).unwrap())
+    });
+    group.finish();
+}
+
+criterion_group!(benches, deserialize_varints);
+criterion_main!(benches);
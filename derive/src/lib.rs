@@ -0,0 +1,372 @@
+//! Derive macros for `serde_bare::schema::BareSchema`, `serde_bare::size::BareSize` and
+//! `serde_bare::tags::BareUnion`.
+//! Use it through `serde_bare`'s `derive` feature rather than depending on this crate directly.
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::{
+    parenthesized, parse_macro_input, parse_quote, Data, DataEnum, DeriveInput, Field, Fields,
+    LitInt, LitStr, Variant,
+};
+
+#[proc_macro_derive(BareSchema, attributes(bare))]
+pub fn derive_bare_schema(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    match expand(input) {
+        Ok(tokens) => tokens.into(),
+        Err(e) => e.to_compile_error().into(),
+    }
+}
+
+fn expand(mut input: DeriveInput) -> syn::Result<TokenStream2> {
+    let name = &input.ident;
+    let name_str = name.to_string();
+    let body = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Unit => quote!(schema.declare(#name_str, |_| {
+                ::serde_bare::schema::String::from("void")
+            })),
+            // serde serializes newtype structs as the type they contain
+            Fields::Unnamed(fields) if fields.unnamed.len() == 1 => {
+                let ty = field_type(&fields.unnamed[0])?;
+                quote!(schema.declare(#name_str, #ty))
+            }
+            fields => {
+                let fields = struct_fields(fields)?;
+                quote!(schema.declare(#name_str, |schema| schema.struct_type(&[#(#fields),*])))
+            }
+        },
+        Data::Enum(data) => {
+            // Tags given with `#[bare(tag = N)]` are the ones `BareUnion` writes.
+            let mut explicit = false;
+            for variant in &data.variants {
+                explicit |= variant_tag(variant)?.is_some();
+            }
+            let tags = if explicit {
+                Some(variant_tags(data)?)
+            } else {
+                None
+            };
+            if data
+                .variants
+                .iter()
+                .all(|v| matches!(v.fields, Fields::Unit))
+            {
+                let values = data
+                    .variants
+                    .iter()
+                    .map(|v| screaming_snake_case(&v.ident.to_string()));
+                match tags {
+                    Some(tags) => quote!(
+                        schema.declare_tagged_enum(#name_str, &[#(#tags),*], &[#(#values),*])
+                    ),
+                    None => quote!(schema.declare_enum(#name_str, &[#(#values),*])),
+                }
+            } else {
+                let mut members = Vec::new();
+                for variant in &data.variants {
+                    let member = format!("{}{}", name_str, variant.ident);
+                    let ty = match &variant.fields {
+                        Fields::Unit => quote!(|_: &mut ::serde_bare::schema::SchemaBuilder| {
+                            ::serde_bare::schema::String::from("void")
+                        }),
+                        Fields::Unnamed(fields) if fields.unnamed.len() == 1 => {
+                            field_type(&fields.unnamed[0])?
+                        }
+                        fields => {
+                            let fields = struct_fields(fields)?;
+                            quote!(|schema: &mut ::serde_bare::schema::SchemaBuilder| {
+                                schema.struct_type(&[#(#fields),*])
+                            })
+                        }
+                    };
+                    members.push(quote!((#member, #ty)));
+                }
+                match tags {
+                    Some(tags) => quote!(
+                        schema.declare_tagged_union(#name_str, &[#(#tags),*], &[#(#members),*])
+                    ),
+                    None => quote!(schema.declare_union(#name_str, &[#(#members),*])),
+                }
+            }
+        }
+        Data::Union(data) => {
+            return Err(syn::Error::new_spanned(
+                data.union_token,
+                "BareSchema can't be derived for unions",
+            ))
+        }
+    };
+
+    for param in input.generics.type_params_mut() {
+        param
+            .bounds
+            .push(parse_quote!(::serde_bare::schema::BareSchema));
+    }
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    Ok(quote! {
+        impl #impl_generics ::serde_bare::schema::BareSchema for #name #ty_generics #where_clause {
+            fn bare_type(
+                schema: &mut ::serde_bare::schema::SchemaBuilder,
+            ) -> ::serde_bare::schema::String {
+                #body
+            }
+        }
+    })
+}
+
+#[proc_macro_derive(BareSize, attributes(bare))]
+pub fn derive_bare_size(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    match expand_size(input) {
+        Ok(tokens) => tokens.into(),
+        Err(e) => e.to_compile_error().into(),
+    }
+}
+
+fn expand_size(mut input: DeriveInput) -> syn::Result<TokenStream2> {
+    let name = &input.ident;
+    let body = match &input.data {
+        Data::Struct(data) => {
+            let (pattern, sizes) = destructure(quote!(#name), &data.fields)?;
+            quote!({
+                let #pattern = self;
+                0 #(+ #sizes)*
+            })
+        }
+        Data::Enum(data) => {
+            let mut arms = Vec::new();
+            for (i, variant) in data.variants.iter().enumerate() {
+                let ident = &variant.ident;
+                let (pattern, sizes) = destructure(quote!(#name::#ident), &variant.fields)?;
+                let tag = i as u64;
+                arms.push(quote! {
+                    #pattern => ::serde_bare::Uint(#tag).encoded_len() #(+ #sizes)*
+                });
+            }
+            quote!(match self { #(#arms,)* })
+        }
+        Data::Union(data) => {
+            return Err(syn::Error::new_spanned(
+                data.union_token,
+                "BareSize can't be derived for unions",
+            ))
+        }
+    };
+
+    for param in input.generics.type_params_mut() {
+        param
+            .bounds
+            .push(parse_quote!(::serde_bare::size::BareSize));
+    }
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    Ok(quote! {
+        impl #impl_generics ::serde_bare::size::BareSize for #name #ty_generics #where_clause {
+            #[allow(unused_variables)]
+            fn approx_bare_size(&self) -> usize {
+                #body
+            }
+        }
+    })
+}
+
+#[proc_macro_derive(BareUnion, attributes(bare))]
+pub fn derive_bare_union(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    match expand_union(input) {
+        Ok(tokens) => tokens.into(),
+        Err(e) => e.to_compile_error().into(),
+    }
+}
+
+fn expand_union(input: DeriveInput) -> syn::Result<TokenStream2> {
+    let name = &input.ident;
+    let data = match &input.data {
+        Data::Enum(data) => data,
+        _ => {
+            return Err(syn::Error::new_spanned(
+                name,
+                "BareUnion can only be derived for enums",
+            ))
+        }
+    };
+
+    let tags = variant_tags(data)?;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    Ok(quote! {
+        impl #impl_generics ::serde_bare::tags::BareUnion for #name #ty_generics #where_clause {
+            const TAGS: &'static [u32] = &[#(#tags),*];
+        }
+    })
+}
+
+/// The union tag of each variant, from their `#[bare(tag = N)]` attributes.
+fn variant_tags(data: &DataEnum) -> syn::Result<Vec<u32>> {
+    // Like enum discriminants, a variant without a tag takes the one after the previous tag.
+    let mut tags: Vec<u32> = Vec::new();
+    for variant in &data.variants {
+        let tag = match variant_tag(variant)? {
+            Some(tag) => tag,
+            None => match tags.last() {
+                Some(previous) => previous.checked_add(1).ok_or_else(|| {
+                    syn::Error::new_spanned(&variant.ident, "union tag overflows u32")
+                })?,
+                None => 0,
+            },
+        };
+        if tags.contains(&tag) {
+            return Err(syn::Error::new_spanned(
+                &variant.ident,
+                format!("union tag {} is used more than once", tag),
+            ));
+        }
+        tags.push(tag);
+    }
+    Ok(tags)
+}
+
+fn variant_tag(variant: &Variant) -> syn::Result<Option<u32>> {
+    let mut tag = None;
+    for attr in &variant.attrs {
+        if attr.path().is_ident("bare") {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("tag") {
+                    let value: LitInt = meta.value()?.parse()?;
+                    tag = Some(value.base10_parse()?);
+                    Ok(())
+                } else {
+                    Err(meta.error("unknown bare attribute"))
+                }
+            })?;
+        }
+    }
+    Ok(tag)
+}
+
+/// A pattern binding the fields of `path`, and the size of each field that isn't skipped.
+fn destructure(
+    path: TokenStream2,
+    fields: &Fields,
+) -> syn::Result<(TokenStream2, Vec<TokenStream2>)> {
+    let mut bindings = Vec::new();
+    let mut sizes = Vec::new();
+    for (i, field) in fields.iter().enumerate() {
+        let binding = quote::format_ident!("field_{}", i);
+        if !field_attrs(field)?.skip {
+            sizes.push(quote!(::serde_bare::size::BareSize::approx_bare_size(#binding)));
+        }
+        bindings.push(match &field.ident {
+            Some(ident) => quote!(#ident: #binding),
+            None => quote!(#binding),
+        });
+    }
+    let pattern = match fields {
+        Fields::Named(_) => quote!(#path { #(#bindings),* }),
+        Fields::Unnamed(_) => quote!(#path(#(#bindings),*)),
+        Fields::Unit => quote!(#path),
+    };
+    Ok((pattern, sizes))
+}
+
+/// `(name, type)` pairs for `SchemaBuilder::struct_type`, leaving out skipped fields.
+fn struct_fields(fields: &Fields) -> syn::Result<Vec<TokenStream2>> {
+    let mut pairs = Vec::new();
+    for (i, field) in fields.iter().enumerate() {
+        if field_attrs(field)?.skip {
+            continue;
+        }
+        let name = match &field.ident {
+            Some(ident) => camel_case(ident.to_string().trim_start_matches("r#")),
+            None => format!("_{}", i),
+        };
+        let ty = field_type(field)?;
+        pairs.push(quote!((#name, #ty)));
+    }
+    Ok(pairs)
+}
+
+/// A function returning the BARE type of `field`.
+fn field_type(field: &Field) -> syn::Result<TokenStream2> {
+    let attrs = field_attrs(field)?;
+    let bytes = attrs.bytes;
+    let fixed = attrs.schema.or_else(|| bytes.then(|| "data".to_string()));
+    Ok(match fixed {
+        Some(schema) => quote!((|_: &mut ::serde_bare::schema::SchemaBuilder| {
+            ::serde_bare::schema::String::from(#schema)
+        })),
+        None => {
+            let ty = &field.ty;
+            quote!(<#ty as ::serde_bare::schema::BareSchema>::bare_type)
+        }
+    })
+}
+
+#[derive(Default)]
+struct FieldAttrs {
+    skip: bool,
+    bytes: bool,
+    schema: Option<String>,
+}
+
+fn field_attrs(field: &Field) -> syn::Result<FieldAttrs> {
+    let mut attrs = FieldAttrs::default();
+    for attr in &field.attrs {
+        if attr.path().is_ident("bare") {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("schema") {
+                    let schema: LitStr = meta.value()?.parse()?;
+                    attrs.schema = Some(schema.value());
+                    Ok(())
+                } else {
+                    Err(meta.error("unknown bare attribute"))
+                }
+            })?;
+        } else if attr.path().is_ident("serde") {
+            // serde reports problems with its own attributes, so they're ignored here.
+            let _ = attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("skip") || meta.path.is_ident("skip_serializing") {
+                    attrs.skip = true;
+                } else if meta.path.is_ident("with") {
+                    let with: LitStr = meta.value()?.parse()?;
+                    attrs.bytes = with.value() == "serde_bytes";
+                } else if meta.input.peek(syn::Token![=]) {
+                    meta.value()?.parse::<syn::Lit>()?;
+                } else if meta.input.peek(syn::token::Paren) {
+                    let _content;
+                    parenthesized!(_content in meta.input);
+                }
+                Ok(())
+            });
+        }
+    }
+    Ok(attrs)
+}
+
+/// BARE field names are conventionally `camelCase`.
+fn camel_case(ident: &str) -> String {
+    let mut name = String::new();
+    let mut upper = false;
+    for c in ident.chars() {
+        if c == '_' && !name.is_empty() {
+            upper = true;
+        } else if upper {
+            name.push(c.to_ascii_uppercase());
+            upper = false;
+        } else {
+            name.push(c);
+        }
+    }
+    name
+}
+
+/// BARE enum values are conventionally `SCREAMING_SNAKE_CASE`.
+fn screaming_snake_case(ident: &str) -> String {
+    let mut name = String::new();
+    for (i, c) in ident.chars().enumerate() {
+        if c.is_ascii_uppercase() && i > 0 {
+            name.push('_');
+        }
+        name.push(c.to_ascii_uppercase());
+    }
+    name
+}
@@ -0,0 +1,187 @@
+//! Serialization to and from tokio's async readers and writers.
+//!
+//! serde's serializer and deserializer are synchronous, so values are serialized into a buffer
+//! which is then written out with a single `write_all`, and read into a buffer in full before
+//! being deserialized.
+//!
+//! BARE messages don't carry their own length, so a reader can't tell where one ends without
+//! knowing its type. The `_framed` functions use the same framing as `to_writer_framed`: a BARE
+//! `uint` holding the length of the message in bytes, followed by the message itself.
+use crate::{
+    de::{frame_prefix, from_frame_body, from_slice, read_error, UNTRUSTED_CAPACITY},
+    error::Error,
+    ser::to_vec,
+    MAX_VARINT_LEN,
+};
+use alloc::vec::Vec;
+use serde::{de::DeserializeOwned, Serialize};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+/// Serialize `value` and write it to `writer`.
+/// The whole value is buffered in memory before anything is written.
+pub async fn to_async_writer<W, T>(mut writer: W, value: &T) -> Result<(), Error>
+where
+    W: AsyncWrite + Unpin,
+    T: ?Sized + Serialize,
+{
+    let buf = to_vec(value)?;
+    writer.write_all(&buf).await.map_err(Error::Io)
+}
+
+/// Read `reader` to the end and deserialize its contents as a single value.
+pub async fn from_async_reader<R, T>(mut reader: R) -> Result<T, Error>
+where
+    R: AsyncRead + Unpin,
+    T: DeserializeOwned,
+{
+    let mut buf = Vec::new();
+    reader.read_to_end(&mut buf).await.map_err(Error::Io)?;
+    from_slice(&buf)
+}
+
+/// Read one framed message from `reader` and deserialize it.
+/// Only the frame is consumed; anything after it is left in `reader`.
+/// Error::TrailingData if the value doesn't use every byte of the frame, as with
+/// `from_reader_framed`.
+///
+/// The frame is buffered before decoding, however long its prefix says it is; use
+/// `from_async_reader_framed_limited` for frames from an untrusted peer.
+pub async fn from_async_reader_framed<R, T>(reader: R) -> Result<T, Error>
+where
+    R: AsyncRead + Unpin,
+    T: DeserializeOwned,
+{
+    from_async_reader_framed_limited(reader, usize::MAX).await
+}
+
+/// Like `from_async_reader_framed`, but a frame longer than `max_length` bytes is
+/// Error::LimitExceeded as soon as its prefix has been read, before any of it is buffered.
+pub async fn from_async_reader_framed_limited<R, T>(
+    mut reader: R,
+    max_length: usize,
+) -> Result<T, Error>
+where
+    R: AsyncRead + Unpin,
+    T: DeserializeOwned,
+{
+    let mut prefix = [0u8; MAX_VARINT_LEN];
+    let mut read = 0;
+    let length = loop {
+        prefix[read] = reader.read_u8().await.map_err(read_error)?;
+        read += 1;
+        if let Some((_, length)) = frame_prefix(&prefix[..read])? {
+            break length;
+        }
+    };
+    if length > max_length {
+        return Err(Error::LimitExceeded {
+            length: length as u64,
+            limit: max_length,
+        });
+    }
+
+    // Don't trust the prefix for the initial allocation.
+    let mut buf = Vec::with_capacity(length.min(UNTRUSTED_CAPACITY));
+    let read = (&mut reader)
+        .take(length as u64)
+        .read_to_end(&mut buf)
+        .await
+        .map_err(read_error)?;
+    if read < length {
+        return Err(Error::Eof);
+    }
+    from_frame_body(&buf)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use tokio::io::AsyncReadExt;
+
+    #[tokio::test]
+    async fn test_to_async_writer() {
+        let value = ("hello".to_string(), vec![1u32, 2, 3], Some(4u8));
+        let (mut client, mut server) = tokio::io::duplex(8);
+        let write = async {
+            to_async_writer(&mut client, &value).await.unwrap();
+            drop(client);
+        };
+        let read = async {
+            let mut buf = Vec::new();
+            server.read_to_end(&mut buf).await.unwrap();
+            buf
+        };
+        let ((), buf) = tokio::join!(write, read);
+        assert_eq!(buf, crate::to_vec(&value).unwrap());
+        assert_eq!(
+            crate::from_slice::<(String, Vec<u32>, Option<u8>)>(&buf).unwrap(),
+            value
+        );
+    }
+
+    #[tokio::test]
+    async fn test_from_async_reader() {
+        let value = ("hello".to_string(), vec![1u32, 2, 3], Some(4u8));
+        let bytes = crate::to_vec(&value).unwrap();
+        let got: (String, Vec<u32>, Option<u8>) = from_async_reader(&bytes[..]).await.unwrap();
+        assert_eq!(got, value);
+    }
+
+    #[tokio::test]
+    async fn test_from_async_reader_framed() {
+        let first = vec!["x".to_string(); 100];
+        let second = vec!["y".to_string()];
+        let mut bytes = Vec::new();
+        for value in [&first, &second] {
+            crate::to_writer_framed(&mut bytes, value).unwrap();
+        }
+        // A prefix longer than one byte
+        assert_eq!(&bytes[..2], &[201, 1]);
+
+        let mut reader = &bytes[..];
+        let got: Vec<String> = from_async_reader_framed(&mut reader).await.unwrap();
+        assert_eq!(got, first);
+        let got: Vec<String> = from_async_reader_framed(&mut reader).await.unwrap();
+        assert_eq!(got, second);
+        assert!(reader.is_empty());
+
+        let truncated = &bytes[..50];
+        assert!(matches!(
+            from_async_reader_framed::<_, Vec<String>>(truncated).await,
+            Err(Error::Eof)
+        ));
+
+        // The same frames are rejected as with from_reader_framed.
+        for frame in [&[2, 7, 0][..], &[0xff; 10][..]] {
+            let sync_err = crate::from_reader_framed::<_, u8>(frame).unwrap_err();
+            let async_err = from_async_reader_framed::<_, u8>(frame).await.unwrap_err();
+            assert_eq!(sync_err.to_string(), async_err.to_string());
+        }
+        assert!(matches!(
+            from_async_reader_framed::<_, u8>(&[2, 7, 0][..]).await,
+            Err(Error::TrailingData)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_from_async_reader_framed_limited() {
+        let frame = [4u8, 3, 1, 2, 3, 0xaa];
+        let mut reader = &frame[..];
+        let got: Vec<u8> = from_async_reader_framed_limited(&mut reader, 4)
+            .await
+            .unwrap();
+        assert_eq!(got, [1, 2, 3]);
+        assert_eq!(reader, [0xaa]);
+
+        // Only the prefix is read from a frame that's too long.
+        let mut reader = &frame[..];
+        assert!(matches!(
+            from_async_reader_framed_limited::<_, Vec<u8>>(&mut reader, 3).await,
+            Err(Error::LimitExceeded {
+                length: 4,
+                limit: 3
+            })
+        ));
+        assert_eq!(reader, &frame[1..]);
+    }
+}
@@ -0,0 +1,158 @@
+//! Flag sets generated by `bitflags!`, for use with `#[serde(with = "serde_bare::bitflags")]`.
+//!
+//! Flags encode as their underlying integer, so flags over `u32` are a BARE `u32`. Decoding with
+//! this module keeps bits the type doesn't define, so that flags added by a newer peer survive a
+//! round trip. [`strict`] rejects them with `Error::InvalidFlags` instead. That check relies on
+//! this crate's deserializers, so `strict` can't be used with other formats, and only works for
+//! flags over unsigned integers of up to 64 bits.
+use ::bitflags::Flags;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+pub fn serialize<T, S>(value: &T, serializer: S) -> Result<S::Ok, S::Error>
+where
+    T: Flags,
+    T::Bits: Serialize,
+    S: Serializer,
+{
+    value.bits().serialize(serializer)
+}
+
+pub fn deserialize<'de, T, D>(deserializer: D) -> Result<T, D::Error>
+where
+    T: Flags,
+    T::Bits: Deserialize<'de>,
+    D: Deserializer<'de>,
+{
+    T::Bits::deserialize(deserializer).map(T::from_bits_retain)
+}
+
+/// Like the parent module, but decoding fails with `Error::InvalidFlags` if any bits are set
+/// that the flags type doesn't define.
+pub mod strict {
+    pub use super::serialize;
+
+    use crate::de::CHECKED_FLAGS;
+    use ::bitflags::Flags;
+    use core::{convert::TryFrom, fmt, marker::PhantomData, mem};
+    use serde::{
+        de::{Error, Unexpected, Visitor},
+        Deserializer,
+    };
+
+    pub fn deserialize<'de, T, D>(deserializer: D) -> Result<T, D::Error>
+    where
+        T: Flags,
+        T::Bits: TryFrom<u64>,
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_tuple_struct(
+            CHECKED_FLAGS,
+            mem::size_of::<T::Bits>(),
+            FlagsVisitor(PhantomData),
+        )
+    }
+
+    struct FlagsVisitor<T>(PhantomData<T>);
+
+    impl<'de, T> Visitor<'de> for FlagsVisitor<T>
+    where
+        T: Flags,
+        T::Bits: TryFrom<u64>,
+    {
+        type Value = T;
+
+        fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+            formatter.write_str("flags with only known bits set")
+        }
+
+        fn visit_u64<E: Error>(self, v: u64) -> Result<T, E> {
+            T::Bits::try_from(v)
+                .ok()
+                .and_then(T::from_bits)
+                .ok_or_else(|| E::invalid_value(Unexpected::Unsigned(v), &self))
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::Error;
+    use serde_derive::{Deserialize, Serialize};
+
+    ::bitflags::bitflags! {
+        #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+        struct Perms: u32 {
+            const READ = 1;
+            const WRITE = 2;
+            const EXEC = 4;
+        }
+
+        #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+        struct Mode: u8 {
+            const APPEND = 0x10;
+        }
+    }
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    struct Lenient {
+        #[serde(with = "super")]
+        perms: Perms,
+        #[serde(with = "super")]
+        mode: Mode,
+    }
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    struct Strict {
+        #[serde(with = "super::strict")]
+        perms: Perms,
+        #[serde(with = "super::strict")]
+        mode: Mode,
+    }
+
+    #[test]
+    fn test_known_bits() {
+        let lenient = Lenient {
+            perms: Perms::READ | Perms::EXEC,
+            mode: Mode::APPEND,
+        };
+        let bytes = crate::to_vec(&lenient).unwrap();
+        assert_eq!(bytes, [5, 0, 0, 0, 0x10]);
+        assert_eq!(crate::from_slice::<Lenient>(&bytes).unwrap(), lenient);
+
+        let strict = Strict {
+            perms: lenient.perms,
+            mode: lenient.mode,
+        };
+        assert_eq!(crate::to_vec(&strict).unwrap(), bytes);
+        assert_eq!(crate::from_slice::<Strict>(&bytes).unwrap(), strict);
+        assert_eq!(
+            crate::from_slice_borrowed::<Strict>(&bytes).unwrap(),
+            strict
+        );
+    }
+
+    #[test]
+    fn test_unknown_bits() {
+        let bytes = [0x0b, 0, 0, 0x80, 0x10];
+        let lenient = crate::from_slice::<Lenient>(&bytes).unwrap();
+        assert_eq!(lenient.perms.bits(), 0x8000_000b);
+        assert_eq!(crate::to_vec(&lenient).unwrap(), bytes);
+
+        assert!(matches!(
+            crate::from_slice::<Strict>(&bytes),
+            Err(Error::InvalidFlags(0x8000_000b))
+        ));
+        assert!(matches!(
+            crate::from_slice_borrowed::<Strict>(&bytes),
+            Err(Error::InvalidFlags(0x8000_000b))
+        ));
+        assert!(matches!(
+            crate::from_slice::<Strict>(&[1, 0, 0, 0, 0x11]),
+            Err(Error::InvalidFlags(0x11))
+        ));
+        assert!(matches!(
+            crate::from_slice::<Strict>(&[1, 0, 0]),
+            Err(Error::Eof)
+        ));
+    }
+}
@@ -0,0 +1,98 @@
+//! Serialization to and from the `bytes` crate's buffers.
+use crate::{
+    compat::io::{self, Read, Write},
+    de::Deserializer,
+    error::Error,
+    ser::to_writer,
+};
+use bytes::{Buf, BufMut};
+use serde::{de::DeserializeOwned, Serialize};
+
+/// Deserialize a value from the front of `buf`, advancing it past the value.
+pub fn from_buf<B, T>(buf: B) -> Result<T, Error>
+where
+    B: Buf,
+    T: DeserializeOwned,
+{
+    T::deserialize(&mut Deserializer::new(BufReader(buf)))
+}
+
+/// Reads from a `Buf` with `copy_to_slice`, so non-contiguous buffers work too.
+struct BufReader<B>(B);
+
+impl<B> Read for BufReader<B>
+where
+    B: Buf,
+{
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = buf.len().min(self.0.remaining());
+        self.0.copy_to_slice(&mut buf[..n]);
+        Ok(n)
+    }
+}
+
+/// Serialize `value` onto the end of `buf`.
+/// Fails if `buf` can't grow to fit the value.
+pub fn to_buf_mut<B, T>(buf: &mut B, value: &T) -> Result<(), Error>
+where
+    B: BufMut,
+    T: ?Sized + Serialize,
+{
+    to_writer(BufMutWriter(buf), value)
+}
+
+/// Writes to a `BufMut` with `put_slice`, stopping short when it has no room left.
+struct BufMutWriter<'a, B>(&'a mut B);
+
+impl<B> Write for BufMutWriter<'_, B>
+where
+    B: BufMut,
+{
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = buf.len().min(self.0.remaining_mut());
+        self.0.put_slice(&buf[..n]);
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use bytes::{Bytes, BytesMut};
+
+    #[test]
+    fn test_from_buf() {
+        let value = ("hello".to_string(), vec![1u32, 2, 3], Some(4u8));
+        let bytes = Bytes::from(crate::to_vec(&value).unwrap());
+        let got: (String, Vec<u32>, Option<u8>) = from_buf(bytes.clone()).unwrap();
+        assert_eq!(got, value);
+
+        // Split the message across two chunks
+        let (head, tail) = (bytes.slice(..4), bytes.slice(4..));
+        let got: (String, Vec<u32>, Option<u8>) = from_buf(head.chain(tail)).unwrap();
+        assert_eq!(got, value);
+
+        let mut buf = bytes.slice(..);
+        let _: String = from_buf(&mut buf).unwrap();
+        assert_eq!(buf.remaining(), bytes.len() - 6);
+
+        assert!(from_buf::<_, (String, Vec<u32>, Option<u8>)>(bytes.slice(..8)).is_err());
+    }
+
+    #[test]
+    fn test_to_buf_mut() {
+        let value = ("hello".repeat(100), vec![1u32, 2, 3], Some(4u8));
+        let mut buf = BytesMut::new();
+        buf.put_u8(0xff);
+        to_buf_mut(&mut buf, &value).unwrap();
+        assert_eq!(buf[0], 0xff);
+        assert_eq!(&buf[1..], &crate::to_vec(&value).unwrap()[..]);
+
+        let mut small = [0u8; 4];
+        assert!(to_buf_mut(&mut &mut small[..], &value).is_err());
+    }
+}
@@ -0,0 +1,133 @@
+//! Encodings for `chrono`'s date and time types, for use with `#[serde(with = "...")]`.
+//!
+//! Both encode as the BARE struct `{ secs: i64, nanos: u32 }`, counted from the Unix epoch in
+//! UTC, with `secs` rounded down as for [`time::system_time`](crate::time::system_time). chrono
+//! represents a leap second as `nanos` of 1,000,000,000 or more in the second before it, so
+//! `nanos` can be up to 1,999,999,999, but only when `secs` is the last second of a minute.
+//! Other values of `nanos` fail to deserialize.
+use ::chrono::{DateTime, Utc};
+use serde::de::Error;
+
+fn from_parts<E: Error>(secs: i64, nanos: u32) -> Result<DateTime<Utc>, E> {
+    if nanos >= 2_000_000_000 {
+        return Err(E::custom("nanos out of range"));
+    }
+    if nanos >= 1_000_000_000 && secs.rem_euclid(60) != 59 {
+        return Err(E::custom(
+            "leap second nanos outside the last second of a minute",
+        ));
+    }
+    DateTime::from_timestamp(secs, nanos).ok_or_else(|| E::custom("time out of range"))
+}
+
+/// `DateTime<Utc>` as `{ secs: i64, nanos: u32 }`.
+pub mod datetime {
+    use ::chrono::{DateTime, Utc};
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    pub fn serialize<S>(value: &DateTime<Utc>, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        (value.timestamp(), value.timestamp_subsec_nanos()).serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<DateTime<Utc>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let (secs, nanos) = <(i64, u32)>::deserialize(deserializer)?;
+        super::from_parts(secs, nanos)
+    }
+}
+
+/// `NaiveDateTime` as `{ secs: i64, nanos: u32 }`, taking it to be in UTC.
+pub mod naive_datetime {
+    use ::chrono::NaiveDateTime;
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S>(value: &NaiveDateTime, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        super::datetime::serialize(&value.and_utc(), serializer)
+    }
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<NaiveDateTime, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let (secs, nanos) = <(i64, u32)>::deserialize(deserializer)?;
+        super::from_parts(secs, nanos).map(|time| time.naive_utc())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use ::chrono::{DateTime, NaiveDate, NaiveDateTime, Utc};
+    use serde_derive::{Deserialize, Serialize};
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    struct Event {
+        #[serde(with = "super::datetime")]
+        at: DateTime<Utc>,
+        #[serde(with = "super::naive_datetime")]
+        local: NaiveDateTime,
+    }
+
+    fn encode(at: DateTime<Utc>) -> (i64, u32) {
+        let event = Event {
+            at,
+            local: at.naive_utc(),
+        };
+        let bytes = crate::to_vec(&event).unwrap();
+        assert_eq!(crate::from_slice::<Event>(&bytes).unwrap(), event);
+        let (at, local) = crate::from_slice::<((i64, u32), (i64, u32))>(&bytes).unwrap();
+        assert_eq!(at, local);
+        at
+    }
+
+    #[test]
+    fn test_round_trip() {
+        assert_eq!(encode(DateTime::UNIX_EPOCH), (0, 0));
+        let at = DateTime::from_timestamp(1_700_000_000, 5).unwrap();
+        assert_eq!(encode(at), (1_700_000_000, 5));
+        // Half a second before the epoch
+        let at = DateTime::from_timestamp(-1, 500_000_000).unwrap();
+        assert_eq!(encode(at), (-1, 500_000_000));
+        let at = NaiveDate::from_ymd_opt(1900, 1, 1)
+            .unwrap()
+            .and_hms_nano_opt(0, 0, 0, 1)
+            .unwrap()
+            .and_utc();
+        assert_eq!(encode(at), (-2_208_988_800, 1));
+        encode(DateTime::<Utc>::MIN_UTC);
+        encode(DateTime::<Utc>::MAX_UTC);
+
+        // 2016-12-31T23:59:60.5Z
+        let leap = NaiveDate::from_ymd_opt(2016, 12, 31)
+            .unwrap()
+            .and_hms_milli_opt(23, 59, 59, 1_500)
+            .unwrap()
+            .and_utc();
+        assert_eq!(encode(leap), (1_483_228_799, 1_500_000_000));
+    }
+
+    #[test]
+    fn test_invalid() {
+        let decode = |secs: i64, nanos: u32| {
+            let bytes = crate::to_vec(&((secs, nanos), (0i64, 0u32))).unwrap();
+            match crate::from_slice::<Event>(&bytes) {
+                Err(crate::Error::Message(message)) => message,
+                other => panic!("unexpected result {:?}", other),
+            }
+        };
+        assert_eq!(decode(0, 2_000_000_000), "nanos out of range");
+        assert_eq!(
+            decode(0, 1_000_000_000),
+            "leap second nanos outside the last second of a minute"
+        );
+        assert_eq!(decode(i64::MAX, 0), "time out of range");
+        assert_eq!(decode(-59, 1_000_000_000), decode(0, 1_000_000_000));
+    }
+}
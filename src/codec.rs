@@ -0,0 +1,179 @@
+//! A `tokio_util` codec for BARE messages.
+//!
+//! Each message is framed as a BARE `uint` holding the length of the message in bytes, followed
+//! by the message itself, the same framing used by `to_writer_framed` and `from_reader_framed`.
+use crate::{
+    de::{frame_prefix, from_frame_body, UNTRUSTED_CAPACITY},
+    error::Error,
+    ser::to_writer_framed,
+};
+use bytes::{Buf, BufMut, BytesMut};
+use core::marker::PhantomData;
+use serde::{de::DeserializeOwned, Serialize};
+use tokio_util::codec::{Decoder, Encoder};
+
+/// The largest frame `BareCodec::new` accepts, 8 MiB.
+const DEFAULT_MAX_FRAME_LENGTH: usize = 8 * 1024 * 1024;
+
+/// Encodes and decodes length-prefixed BARE messages of type `T`.
+pub struct BareCodec<T> {
+    max_frame_length: usize,
+    _marker: PhantomData<fn(T) -> T>,
+}
+
+impl<T> BareCodec<T> {
+    /// A codec decoding frames of up to 8 MiB.
+    pub fn new() -> Self {
+        BareCodec::with_max_frame_length(DEFAULT_MAX_FRAME_LENGTH)
+    }
+
+    /// A codec decoding frames of up to `max_frame_length` bytes, not counting the length
+    /// prefix. A longer frame is Error::LimitExceeded as soon as its prefix has been read.
+    pub fn with_max_frame_length(max_frame_length: usize) -> Self {
+        BareCodec {
+            max_frame_length,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<T> Default for BareCodec<T> {
+    fn default() -> Self {
+        BareCodec::new()
+    }
+}
+
+impl<T> Encoder<T> for BareCodec<T>
+where
+    T: Serialize,
+{
+    type Error = Error;
+
+    /// Like `to_writer_framed`, this serializes `item` twice, the first time to measure it.
+    fn encode(&mut self, item: T, dst: &mut BytesMut) -> Result<(), Error> {
+        to_writer_framed(dst.writer(), &item)
+    }
+}
+
+impl<T> Decoder for BareCodec<T>
+where
+    T: DeserializeOwned,
+{
+    type Item = T;
+    type Error = Error;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<T>, Error> {
+        let (prefix_len, length) = match frame_prefix(src)? {
+            Some(prefix) => prefix,
+            None => return Ok(None),
+        };
+        if length > self.max_frame_length {
+            return Err(Error::LimitExceeded {
+                length: length as u64,
+                limit: self.max_frame_length,
+            });
+        }
+        let frame_len = prefix_len.saturating_add(length);
+        if src.len() < frame_len {
+            // Don't trust the prefix for more than a modest allocation.
+            src.reserve((frame_len - src.len()).min(UNTRUSTED_CAPACITY));
+            return Ok(None);
+        }
+        src.advance(prefix_len);
+        let body = src.split_to(length);
+        from_frame_body(&body).map(Some)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use futures::{SinkExt, StreamExt};
+    use tokio_util::codec::{FramedRead, FramedWrite};
+
+    #[test]
+    fn test_partial_frames() {
+        let mut codec = BareCodec::<Vec<String>>::new();
+        let value = vec!["x".to_string(); 100];
+        let mut encoded = BytesMut::new();
+        codec.encode(value.clone(), &mut encoded).unwrap();
+        assert_eq!(&encoded[..2], &[201, 1]);
+
+        let mut src = BytesMut::new();
+        for (i, b) in encoded.iter().enumerate() {
+            assert!(codec.decode(&mut src).unwrap().is_none(), "byte {}", i);
+            src.put_u8(*b);
+        }
+        assert_eq!(codec.decode(&mut src).unwrap(), Some(value));
+        assert!(src.is_empty());
+
+        let mut src = BytesMut::from(&[0xffu8; 10][..]);
+        assert!(matches!(codec.decode(&mut src), Err(Error::VarintTooLong)));
+    }
+
+    #[test]
+    fn test_max_frame_length() {
+        let mut codec = BareCodec::<Vec<u8>>::with_max_frame_length(4);
+        let mut src = BytesMut::from(&[4u8, 3, 1, 2, 3][..]);
+        assert_eq!(codec.decode(&mut src).unwrap(), Some(vec![1, 2, 3]));
+
+        // Rejected on the prefix alone, without waiting for the body.
+        let mut src = BytesMut::from(&[5u8][..]);
+        assert!(matches!(
+            codec.decode(&mut src),
+            Err(Error::LimitExceeded {
+                length: 5,
+                limit: 4
+            })
+        ));
+        let mut src = BytesMut::from(&[0xff, 0xff, 0xff, 0xff, 0x0f][..]);
+        assert!(matches!(
+            BareCodec::<Vec<u8>>::new().decode(&mut src),
+            Err(Error::LimitExceeded { .. })
+        ));
+    }
+
+    #[test]
+    fn test_trailing_data() {
+        // Frames are decoded the same way as by from_reader_framed.
+        let frame = [2u8, 7, 0];
+        let mut src = BytesMut::from(&frame[..]);
+        assert!(matches!(
+            BareCodec::<u8>::new().decode(&mut src),
+            Err(Error::TrailingData)
+        ));
+        assert!(matches!(
+            crate::from_reader_framed::<_, u8>(&frame[..]),
+            Err(Error::TrailingData)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_duplex() {
+        let messages = vec![
+            ("hello".to_string(), 1u32),
+            ("".to_string(), 2),
+            ("a".repeat(300), 3),
+        ];
+        // A tiny buffer splits both length prefixes and bodies across reads.
+        let (client, server) = tokio::io::duplex(3);
+        let mut sink = FramedWrite::new(client, BareCodec::new());
+        let mut stream = FramedRead::new(server, BareCodec::<(String, u32)>::new());
+
+        let write = async {
+            for message in messages.clone() {
+                sink.send(message).await.unwrap();
+            }
+            drop(sink);
+        };
+        let read = async {
+            let mut received = Vec::new();
+            while let Some(message) = stream.next().await {
+                received.push(message.unwrap());
+            }
+            received
+        };
+        let ((), received) = tokio::join!(write, read);
+        assert_eq!(received, messages);
+    }
+}
@@ -0,0 +1,555 @@
+//! Generate Rust types from a `.bare` schema, for use from `build.rs`.
+//!
+//! ```ignore
+//! let schema = std::fs::read_to_string("messages.bare").unwrap();
+//! let code = serde_bare::codegen::generate(&schema).unwrap();
+//! let out = std::path::Path::new(&std::env::var("OUT_DIR").unwrap()).join("messages.rs");
+//! std::fs::write(out, code).unwrap();
+//! ```
+//!
+//! The generated code derives `serde::Serialize` and `serde::Deserialize`, so the crate including
+//! it needs serde's `derive` feature. Types map to Rust as described in the crate documentation:
+//! structs to structs, unions to enums with a newtype variant per member, enums to enums with
+//! unit variants, `data<N>` to [`Data`](crate::Data), `[N]type` to [`FixedSeq`](crate::FixedSeq)
+//! and maps to `BTreeMap`. Unions and enums whose tags aren't `0, 1, 2, ...` go through
+//! [`tags`](crate::tags).
+//!
+//! Both the older (`[]type`, `map[K]V`, `type T { ... }`) and newer (`list<type>`,
+//! `map<K><V>`, `type T struct { ... }`) spellings are accepted. Not supported:
+//! - anonymous structs, unions and enums nested inside other types
+//! - union members other than named types and primitives
+//! - recursive types, which would need a `Box` the generator doesn't insert
+use alloc::{
+    format,
+    string::{String, ToString},
+    vec::Vec,
+};
+use core::fmt::{self, Write};
+
+/// An error in a `.bare` schema.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SchemaError {
+    /// The line the error was found on, starting at 1.
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for SchemaError {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        write!(formatter, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl serde::de::StdError for SchemaError {}
+
+/// Parse `schema_src` and generate Rust source for the types it declares.
+pub fn generate(schema_src: &str) -> Result<String, SchemaError> {
+    let mut parser = Parser {
+        tokens: lex(schema_src)?,
+        pos: 0,
+        out: String::from("// Generated by serde_bare::codegen. Do not edit.\n"),
+    };
+    while parser.pos < parser.tokens.len() {
+        parser.declaration()?;
+    }
+    Ok(parser.out)
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Ident(String),
+    Number(u64),
+    Punct(char),
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Token::Ident(ident) => write!(formatter, "`{}`", ident),
+            Token::Number(n) => write!(formatter, "`{}`", n),
+            Token::Punct(c) => write!(formatter, "`{}`", c),
+        }
+    }
+}
+
+fn lex(src: &str) -> Result<Vec<(Token, usize)>, SchemaError> {
+    let mut tokens = Vec::new();
+    for (i, line) in src.lines().enumerate() {
+        let line_no = i + 1;
+        let line = line.split('#').next().unwrap_or("");
+        let mut chars = line.char_indices().peekable();
+        while let Some((start, c)) = chars.next() {
+            if c.is_whitespace() {
+                continue;
+            }
+            let mut end = start + c.len_utf8();
+            if c.is_ascii_alphanumeric() || c == '_' {
+                while let Some(&(i, c)) = chars.peek() {
+                    if !(c.is_ascii_alphanumeric() || c == '_') {
+                        break;
+                    }
+                    end = i + c.len_utf8();
+                    chars.next();
+                }
+                let word = &line[start..end];
+                let token = if c.is_ascii_digit() {
+                    Token::Number(word.parse().map_err(|_| SchemaError {
+                        line: line_no,
+                        message: format!("invalid number `{}`", word),
+                    })?)
+                } else {
+                    Token::Ident(word.to_string())
+                };
+                tokens.push((token, line_no));
+            } else if "{}()[]<>|=:".contains(c) {
+                tokens.push((Token::Punct(c), line_no));
+            } else {
+                return Err(SchemaError {
+                    line: line_no,
+                    message: format!("unexpected character `{}`", c),
+                });
+            }
+        }
+    }
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<(Token, usize)>,
+    pos: usize,
+    out: String,
+}
+
+impl Parser {
+    fn error<T>(&self, message: String) -> Result<T, SchemaError> {
+        let line = match self.tokens.get(self.pos) {
+            Some((_, line)) => *line,
+            None => self.tokens.last().map_or(1, |(_, line)| *line),
+        };
+        Err(SchemaError { line, message })
+    }
+
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos).map(|(token, _)| token)
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        if self.peek() == Some(&Token::Punct(c)) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, c: char) -> Result<(), SchemaError> {
+        if self.eat(c) {
+            Ok(())
+        } else {
+            self.unexpected(&format!("`{}`", c))
+        }
+    }
+
+    fn unexpected<T>(&self, expected: &str) -> Result<T, SchemaError> {
+        match self.peek() {
+            Some(token) => self.error(format!("expected {}, found {}", expected, token)),
+            None => self.error(format!("expected {}, found end of schema", expected)),
+        }
+    }
+
+    fn ident(&mut self) -> Result<String, SchemaError> {
+        match self.peek() {
+            Some(Token::Ident(ident)) => {
+                let ident = ident.clone();
+                self.pos += 1;
+                Ok(ident)
+            }
+            _ => self.unexpected("a name"),
+        }
+    }
+
+    fn number(&mut self) -> Result<u64, SchemaError> {
+        match self.peek() {
+            Some(Token::Number(n)) => {
+                let n = *n;
+                self.pos += 1;
+                Ok(n)
+            }
+            _ => self.unexpected("a number"),
+        }
+    }
+
+    fn peek_keyword(&self, keyword: &str) -> bool {
+        matches!(self.peek(), Some(Token::Ident(ident)) if ident == keyword)
+    }
+
+    fn declaration(&mut self) -> Result<(), SchemaError> {
+        let keyword = self.ident()?;
+        if keyword != "type" && keyword != "enum" {
+            self.pos -= 1;
+            return self.unexpected("`type` or `enum`");
+        }
+        let name = self.ident()?;
+        match keyword.as_str() {
+            "type" => {
+                if self.eat('{') {
+                    self.struct_body(&name)
+                } else if self.peek_keyword("struct") {
+                    self.pos += 1;
+                    self.expect('{')?;
+                    self.struct_body(&name)
+                } else if self.eat('(') {
+                    self.union_body(&name, ')')
+                } else if self.peek_keyword("union") {
+                    self.pos += 1;
+                    self.expect('{')?;
+                    self.union_body(&name, '}')
+                } else if self.peek_keyword("enum") {
+                    self.pos += 1;
+                    self.enum_body(&name)
+                } else {
+                    let ty = self.ty()?;
+                    let _ = writeln!(self.out, "\npub type {} = {};", name, ty);
+                    Ok(())
+                }
+            }
+            _ => self.enum_body(&name),
+        }
+    }
+
+    fn struct_body(&mut self, name: &str) -> Result<(), SchemaError> {
+        let mut fields = String::new();
+        while !self.eat('}') {
+            let field = field_name(&self.ident()?);
+            self.expect(':')?;
+            let ty = self.ty()?;
+            let _ = writeln!(fields, "    pub {}: {},", field, ty);
+        }
+        let _ = write!(
+            self.out,
+            "\n{}pub struct {} {{\n{}}}\n",
+            DERIVE, name, fields
+        );
+        Ok(())
+    }
+
+    fn union_body(&mut self, name: &str, close: char) -> Result<(), SchemaError> {
+        let mut variants = String::new();
+        let mut tags = Vec::new();
+        self.eat('|');
+        loop {
+            let member = self.ident()?;
+            let variant = match member.as_str() {
+                "void" => "Void".to_string(),
+                _ => {
+                    self.pos -= 1;
+                    let ty = self.ty()?;
+                    let variant = if ty == member {
+                        member
+                    } else if primitive(&member).is_some() {
+                        pascal_case(&member)
+                    } else {
+                        self.pos -= 1;
+                        return self.error(format!(
+                            "union member `{}` must be a named type or primitive",
+                            member
+                        ));
+                    };
+                    format!("{}({})", variant, ty)
+                }
+            };
+            let _ = writeln!(variants, "    {},", variant);
+            tags.push(self.tag(tags.last())?);
+            if !self.eat('|') {
+                break;
+            }
+        }
+        self.expect(close)?;
+        self.emit_enum(name, &variants, &tags);
+        Ok(())
+    }
+
+    fn enum_body(&mut self, name: &str) -> Result<(), SchemaError> {
+        self.expect('{')?;
+        let mut variants = String::new();
+        let mut tags = Vec::new();
+        while !self.eat('}') {
+            let value = self.ident()?;
+            let _ = writeln!(variants, "    {},", variant_name(&value));
+            tags.push(self.tag(tags.last())?);
+        }
+        self.emit_enum(name, &variants, &tags);
+        Ok(())
+    }
+
+    /// An optional `= tag` after a union member or enum value.
+    fn tag(&mut self, previous: Option<&u32>) -> Result<u32, SchemaError> {
+        let tag = if self.eat('=') {
+            self.number()?
+        } else {
+            previous.map_or(0, |&tag| u64::from(tag) + 1)
+        };
+        if tag > u64::from(u32::MAX) {
+            self.pos -= 1;
+            return self.error(format!("tag {} is too large", tag));
+        }
+        Ok(tag as u32)
+    }
+
+    fn emit_enum(&mut self, name: &str, variants: &str, tags: &[u32]) {
+        if tags.iter().enumerate().all(|(i, &tag)| i as u32 == tag) {
+            let _ = write!(
+                self.out,
+                "\n{}pub enum {} {{\n{}}}\n",
+                DERIVE, name, variants
+            );
+            return;
+        }
+        // serde's derive only uses declaration order, so derive a remote implementation on the
+        // type itself and wrap it with the schema's tags.
+        let tags = tags
+            .iter()
+            .map(u32::to_string)
+            .collect::<Vec<_>>()
+            .join(", ");
+        let _ = write!(
+            self.out,
+            "
+{derive}#[serde(remote = \"Self\")]
+pub enum {name} {{
+{variants}}}
+
+impl serde::Serialize for {name} {{
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {{
+        struct Untagged<'a>(&'a {name});
+        impl serde::Serialize for Untagged<'_> {{
+            fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {{
+                {name}::serialize(self.0, serializer)
+            }}
+        }}
+        serde_bare::tags::serialize(&Untagged(self), &[{tags}], serializer)
+    }}
+}}
+
+impl<'de> serde::Deserialize<'de> for {name} {{
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {{
+        struct Untagged({name});
+        impl<'de> serde::Deserialize<'de> for Untagged {{
+            fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {{
+                {name}::deserialize(deserializer).map(Untagged)
+            }}
+        }}
+        serde_bare::tags::deserialize(&[{tags}], deserializer).map(|Untagged(value)| value)
+    }}
+}}
+",
+            derive = DERIVE,
+            name = name,
+            variants = variants,
+            tags = tags,
+        );
+    }
+
+    fn ty(&mut self) -> Result<String, SchemaError> {
+        if self.eat('[') {
+            return if self.eat(']') {
+                Ok(format!("Vec<{}>", self.ty()?))
+            } else {
+                let len = self.number()?;
+                self.expect(']')?;
+                Ok(format!("serde_bare::FixedSeq<{}, {}>", self.ty()?, len))
+            };
+        }
+        if matches!(
+            self.peek(),
+            Some(Token::Punct('{')) | Some(Token::Punct('('))
+        ) {
+            return self.error("anonymous structs and unions are not supported".to_string());
+        }
+        let ident = self.ident()?;
+        if let Some(ty) = primitive(&ident) {
+            return Ok(ty.to_string());
+        }
+        match ident.as_str() {
+            "data" => {
+                if self.eat('<') {
+                    let len = self.number()?;
+                    self.expect('>')?;
+                    Ok(format!("serde_bare::Data<{}>", len))
+                } else {
+                    Ok("Vec<u8>".to_string())
+                }
+            }
+            "optional" => {
+                self.expect('<')?;
+                let ty = self.ty()?;
+                self.expect('>')?;
+                Ok(format!("Option<{}>", ty))
+            }
+            "list" => {
+                self.expect('<')?;
+                let ty = self.ty()?;
+                self.expect('>')?;
+                if self.eat('[') {
+                    let len = self.number()?;
+                    self.expect(']')?;
+                    Ok(format!("serde_bare::FixedSeq<{}, {}>", ty, len))
+                } else {
+                    Ok(format!("Vec<{}>", ty))
+                }
+            }
+            "map" => {
+                let (key, value) = if self.eat('[') {
+                    let key = self.ty()?;
+                    self.expect(']')?;
+                    (key, self.ty()?)
+                } else {
+                    self.expect('<')?;
+                    let key = self.ty()?;
+                    self.expect('>')?;
+                    self.expect('<')?;
+                    let value = self.ty()?;
+                    self.expect('>')?;
+                    (key, value)
+                };
+                Ok(format!("std::collections::BTreeMap<{}, {}>", key, value))
+            }
+            "struct" | "union" | "enum" => {
+                self.pos -= 1;
+                self.error(format!("anonymous {} types are not supported", ident))
+            }
+            _ if ident.starts_with(|c: char| c.is_ascii_uppercase()) => Ok(ident),
+            _ => {
+                self.pos -= 1;
+                self.error(format!("unknown type `{}`", ident))
+            }
+        }
+    }
+}
+
+const DERIVE: &str = "#[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]\n";
+
+fn primitive(ident: &str) -> Option<&'static str> {
+    Some(match ident {
+        "uint" => "serde_bare::Uint",
+        "int" => "serde_bare::Int",
+        "u8" => "u8",
+        "u16" => "u16",
+        "u32" => "u32",
+        "u64" => "u64",
+        "i8" => "i8",
+        "i16" => "i16",
+        "i32" => "i32",
+        "i64" => "i64",
+        "f32" => "f32",
+        "f64" => "f64",
+        "bool" => "bool",
+        "str" | "string" => "String",
+        "void" => "()",
+        _ => return None,
+    })
+}
+
+fn pascal_case(ident: &str) -> String {
+    ident
+        .split('_')
+        .filter(|word| !word.is_empty())
+        .map(|word| {
+            let mut chars = word.chars();
+            let first = chars.next().map(|c| c.to_ascii_uppercase());
+            first
+                .into_iter()
+                .chain(chars.map(|c| c.to_ascii_lowercase()))
+                .collect::<String>()
+        })
+        .collect()
+}
+
+/// Enum values are conventionally `SCREAMING_CASE` in schemas.
+fn variant_name(ident: &str) -> String {
+    if ident.chars().any(|c| c.is_ascii_lowercase()) {
+        ident.to_string()
+    } else {
+        pascal_case(ident)
+    }
+}
+
+/// Field names are conventionally `camelCase` in schemas.
+fn field_name(ident: &str) -> String {
+    let mut name = String::new();
+    let mut previous_lower = false;
+    for c in ident.chars() {
+        if c.is_ascii_uppercase() && previous_lower {
+            name.push('_');
+        }
+        previous_lower = c.is_ascii_lowercase() || c.is_ascii_digit();
+        name.push(c.to_ascii_lowercase());
+    }
+    match name.as_str() {
+        "self" | "super" | "crate" => name + "_",
+        "as" | "async" | "await" | "break" | "const" | "continue" | "dyn" | "else" | "enum"
+        | "extern" | "false" | "fn" | "for" | "if" | "impl" | "in" | "let" | "loop" | "match"
+        | "mod" | "move" | "mut" | "pub" | "ref" | "return" | "static" | "struct" | "trait"
+        | "true" | "type" | "unsafe" | "use" | "where" | "while" => format!("r#{}", name),
+        _ => name,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_generate() {
+        let schema = "
+# A comment
+type PublicKey data<128>
+type Time str # ISO 8601
+
+enum Department {
+    ACCOUNTING
+    ADMINISTRATION
+    CUSTOMER_SERVICE
+    DEVELOPMENT = 10
+}
+
+type Address {
+    street: [4]string
+    city: string
+}
+
+type Employee {
+    name: str
+    homeAddress: Address
+    department: Department
+    hireDate: Time
+    publicKey: optional<PublicKey>
+    metadata: map[str]data
+    type: list<u8>[2]
+}
+
+type Person (Employee | void)
+
+type Message union { Person = 1 | Address | uint }
+";
+        let code = generate(schema).unwrap();
+        syn::parse_file(&code).unwrap();
+        assert!(code.contains("pub type PublicKey = serde_bare::Data<128>;"));
+        assert!(code.contains("    CustomerService,\n    Development,\n"));
+        assert!(code
+            .contains("serde_bare::tags::serialize(&Untagged(self), &[0, 1, 2, 10], serializer)"));
+        assert!(code.contains("    pub street: serde_bare::FixedSeq<String, 4>,\n"));
+        assert!(code.contains("    pub home_address: Address,\n"));
+        assert!(code.contains("    pub metadata: std::collections::BTreeMap<String, Vec<u8>>,\n"));
+        assert!(code.contains("    pub r#type: serde_bare::FixedSeq<u8, 2>,\n"));
+        assert!(code.contains("pub enum Person {\n    Employee(Employee),\n    Void,\n}"));
+        assert!(code.contains("&[1, 2, 3]"));
+        assert!(code.contains("    Uint(serde_bare::Uint),\n"));
+
+        let err = generate("type Customer {\n    orders: []{ orderId: i64 }\n}").unwrap_err();
+        assert_eq!(err.line, 2);
+        assert!(generate("type X optional<").is_err());
+        assert!(generate("type X foo").is_err());
+        assert!(generate("type X (optional<Y>)").is_err());
+    }
+}
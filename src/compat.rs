@@ -0,0 +1,5 @@
+//! Re-exports that paper over the differences between `std` and `no_std` builds.
+
+pub mod io {
+    pub use no_std_io::io::{Cursor, Error, ErrorKind, Read, Result, Write};
+}
@@ -1,40 +1,577 @@
-use crate::{error::Error, Uint};
-use serde::de;
-use std::{
-    convert::TryInto,
-    i16, i32, i64, i8,
-    io::{Cursor, Read},
-    str, u16, u32, u64, u8,
+use crate::{
+    compat::io::{self, Cursor, Read},
+    decode_uint,
+    error::Error,
+    value::Layout,
+    Data, Uint, CANONICAL_NAN_F32, CANONICAL_NAN_F64, MAX_VARINT_LEN,
+};
+use alloc::{
+    collections::BTreeSet,
+    format,
+    string::{String, ToString},
+    vec::Vec,
 };
+use core::{
+    convert::{TryFrom, TryInto},
+    marker::PhantomData,
+    str,
+};
+use serde::de;
+
+/// Why a type would call `deserialize_any`, for Error::AnyUnsupported.
+const ANY_HINT: &str = "the type decides what to read from the input, which BARE can't describe; \
+    this is usually #[serde(flatten)], an internally or adjacently tagged enum \
+    (#[serde(tag = \"...\")]), an untagged enum, or a dynamic type like serde_json::Value";
+
+/// Why a type would call `deserialize_ignored_any`, for Error::AnyUnsupported.
+const IGNORED_ANY_HINT: &str = "the type skips a value without saying what it is, which BARE \
+    can't do; this is usually IgnoredAny, or an enum or struct skipping unknown data";
 
 /// Try and return a Vec<u8> of `len` bytes from a Reader
 #[inline]
-fn read_bytes<R: Read>(reader: R, len: usize) -> Result<Vec<u8>, std::io::Error> {
-    // Allocate at most 4096 bytes to start with. Growing a Vec is fairly efficient once you get out
-    // of the region of the first few hundred bytes.
-    let capacity = len.min(4096);
-    let mut buffer = Vec::with_capacity(capacity);
-    let read = reader.take(len as u64).read_to_end(&mut buffer)?;
+pub(crate) fn read_bytes<R: Read>(reader: R, len: usize) -> Result<Vec<u8>, Error> {
+    let mut buffer = Vec::new();
+    read_bytes_into(reader, len, &mut buffer, UNTRUSTED_CAPACITY)?;
+    Ok(buffer)
+}
+
+/// The most that's allocated up front for a length read from a reader of unknown size.
+/// Growing a Vec is fairly efficient once you get out of the region of the first few hundred
+/// bytes.
+pub(crate) const UNTRUSTED_CAPACITY: usize = 4096;
+
+/// Replace the contents of `buffer` with `len` bytes from a Reader, allocating at most
+/// `max_capacity` bytes to start with.
+fn read_bytes_into<R: Read>(
+    reader: R,
+    len: usize,
+    buffer: &mut Vec<u8>,
+    max_capacity: usize,
+) -> Result<(), Error> {
+    buffer.clear();
+    buffer.reserve(len.min(max_capacity));
+    let read = reader
+        .take(len as u64)
+        .read_to_end(buffer)
+        .map_err(read_error)?;
     if read < len {
-        Err(std::io::Error::new(
-            std::io::ErrorKind::UnexpectedEof,
-            "Unexpected EOF reading number of bytes expected in field prefix",
-        ))
+        Err(Error::Eof)
     } else {
-        Ok(buffer)
+        Ok(())
+    }
+}
+
+/// The tuple struct name that [`Uint`] deserializes through. Both deserializers read the whole
+/// varint themselves and visit it as a `u64`, instead of visiting its bytes as a sequence.
+pub(crate) const UINT: &str = "$serde_bare::Uint";
+
+/// The tuple struct name that [`heapless`](crate::heapless) deserializes bounded strings and data
+/// through. Both deserializers read the length prefix and check it against the tuple struct's
+/// `len` before reading any of the contents, which are then visited as bytes.
+pub(crate) const BOUNDED_BYTES: &str = "$serde_bare::BoundedBytes";
+
+/// The tuple struct name that [`bitflags::strict`](crate::bitflags::strict) deserializes flags
+/// through. The tuple struct's `len` is the width of the flags in bytes, at most 8. Both
+/// deserializers read that many bytes as a little-endian integer and visit it as a `u64`, and
+/// turn the visitor rejecting it into Error::InvalidFlags.
+#[cfg(feature = "bitflags")]
+pub(crate) const CHECKED_FLAGS: &str = "$serde_bare::CheckedFlags";
+
+/// The first `len` bytes of `buf`, for reading CHECKED_FLAGS into.
+#[cfg(feature = "bitflags")]
+fn flags_bytes(buf: &mut [u8; 8], len: usize) -> Result<&mut [u8], Error> {
+    buf.get_mut(..len)
+        .ok_or_else(|| de::Error::invalid_length(len, &"flags of at most 8 bytes"))
+}
+
+/// Error::LimitExceeded if a length prefix is larger than `limit`.
+fn check_limit(length: u64, limit: usize) -> Result<usize, Error> {
+    match checked_length(length) {
+        Ok(length) if length <= limit => Ok(length),
+        _ => Err(Error::LimitExceeded { length, limit }),
+    }
+}
+
+/// Convert a length read from the input to a usize.
+/// Error::LengthOverflow if it doesn't fit, which can happen on 32-bit targets.
+pub(crate) fn checked_length(length: u64) -> Result<usize, Error> {
+    usize::try_from(length).map_err(|_| Error::LengthOverflow)
+}
+
+/// Parse the length prefix of a frame at the start of `bytes`, returning the number of bytes the
+/// prefix takes up and the length of the body after it, or None if `bytes` ends partway through
+/// the prefix. Every reader of frames parses their prefixes here.
+pub(crate) fn frame_prefix(bytes: &[u8]) -> Result<Option<(usize, usize)>, Error> {
+    match decode_uint(bytes) {
+        Ok((length, prefix_len)) => Ok(Some((prefix_len, checked_length(length)?))),
+        Err(Error::Eof) | Err(Error::VarintTruncated) => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+/// Deserialize the body of a frame, which the value has to use up.
+/// Error::TrailingData if it doesn't.
+pub(crate) fn from_frame_body<T>(body: &[u8]) -> Result<T, Error>
+where
+    T: de::DeserializeOwned,
+{
+    let mut deserializer = Deserializer::new(body);
+    let value = T::deserialize(&mut deserializer)?;
+    if !deserializer.reader.is_empty() {
+        return Err(Error::TrailingData);
+    }
+    Ok(value)
+}
+
+/// Input that ends early is reported as Error::Eof rather than as an io::Error.
+pub(crate) fn read_error(e: io::Error) -> Error {
+    if e.kind() == io::ErrorKind::UnexpectedEof {
+        Error::Eof
+    } else {
+        Error::Io(e)
+    }
+}
+
+/// The options of a `Deserializer`, for setting them all at once with `Deserializer::with_config`
+/// or `from_slice_with_config`. Each field is documented on the `Deserializer` method that sets
+/// it. The default matches `Deserializer::new`, which accepts anything it can make sense of.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub struct DeserializerConfig {
+    pub length_prefixed_variants: bool,
+    pub strict_bool: bool,
+    pub strict_lengths: bool,
+    pub reject_duplicate_keys: bool,
+    pub strict_tuples: bool,
+    pub human_readable: bool,
+    pub strict_floats: bool,
+}
+
+impl DeserializerConfig {
+    /// Reject everything that isn't canonical BARE or that the lenient default lets through
+    /// silently: bools other than 0 and 1, overlong length prefixes, duplicate map keys,
+    /// partially read tuples and NaNs other than the canonical ones.
+    pub fn strict() -> Self {
+        DeserializerConfig {
+            strict_bool: true,
+            strict_lengths: true,
+            reject_duplicate_keys: true,
+            strict_tuples: true,
+            strict_floats: true,
+            ..DeserializerConfig::default()
+        }
     }
 }
 
 pub struct Deserializer<R> {
     reader: R,
+    fields: Option<Vec<FieldRecord>>,
+    /// Index into `fields` of a field whose optional hasn't been read yet.
+    pending_field: Option<usize>,
+    config: DeserializerConfig,
+    /// Holds strings and data for visitors that only borrow them.
+    scratch: Vec<u8>,
+    /// Counts the bytes left in the input, for readers that know. Lengths up to that are trusted
+    /// enough to allocate for up front.
+    remaining_len: Option<fn(&R) -> usize>,
+    /// Set by `new_limited`.
+    read_limit: Option<ReadLimit>,
+}
+
+/// How much of the input a deserializer may read, and how much it has read so far.
+#[derive(Copy, Clone)]
+struct ReadLimit {
+    limit: usize,
+    used: usize,
+}
+
+/// A struct field the deserializer started decoding, recorded when field tracking is enabled.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct FieldRecord {
+    pub name: &'static str,
+    /// Whether the first optional read while decoding the field was present, or None if no
+    /// optional was read.
+    pub present: Option<bool>,
 }
 
 impl<R> Deserializer<R> {
     pub fn new(reader: R) -> Self {
-        Deserializer { reader }
+        Deserializer {
+            reader,
+            fields: None,
+            pending_field: None,
+            config: DeserializerConfig::default(),
+            scratch: Vec::new(),
+            remaining_len: None,
+            read_limit: None,
+        }
+    }
+
+    pub fn with_config(reader: R, config: DeserializerConfig) -> Self {
+        Deserializer {
+            config,
+            ..Deserializer::new(reader)
+        }
+    }
+
+    /// A deserializer that reads at most `limit` bytes from `reader`, for decoding a frame of
+    /// known length from a reader that has more after it. Decoding that would read past the
+    /// limit fails with Error::LimitExceeded before anything more is read.
+    pub fn new_limited(reader: R, limit: usize) -> Self {
+        Deserializer {
+            read_limit: Some(ReadLimit { limit, used: 0 }),
+            ..Deserializer::new(reader)
+        }
+    }
+
+    pub fn config(&self) -> DeserializerConfig {
+        self.config
+    }
+
+    /// Read enums written by a `Serializer` with `length_prefixed_variants` enabled.
+    /// Each variant's data is read in full before it's decoded, so an unknown variant can be
+    /// skipped by mapping it to a `#[serde(other)]` unit variant, and bytes left over after a
+    /// known variant's data are ignored.
+    pub fn length_prefixed_variants(&mut self) {
+        self.config.length_prefixed_variants = true;
+    }
+
+    /// Reject bools other than 0 and 1 with Error::InvalidBool, as the BARE spec requires.
+    /// By default any nonzero byte is read as `true`.
+    pub fn strict_bool(&mut self) {
+        self.config.strict_bool = true;
+    }
+
+    /// Reject length prefixes of strings, data, lists and maps that aren't encoded in as few
+    /// bytes as possible with Error::NonCanonicalInt.
+    pub fn strict_lengths(&mut self) {
+        self.config.strict_lengths = true;
+    }
+
+    /// Fail with Error::DuplicateMapKey when a map has two keys that serialize to the same bytes,
+    /// rather than leaving it to the map type, which usually keeps one of the values.
+    pub fn reject_duplicate_keys(&mut self) {
+        self.config.reject_duplicate_keys = true;
+    }
+
+    /// Fail when a type stops reading a fixed-length array or tuple struct before its last
+    /// element, rather than leaving the rest of the input where the next value is read from.
+    /// Input that runs out before the last element is Error::Eof either way.
+    pub fn strict_tuples(&mut self) {
+        self.config.strict_tuples = true;
+    }
+
+    /// Reject NaN floats other than `CANONICAL_NAN_F32` and `CANONICAL_NAN_F64`, such as
+    /// signaling NaNs or NaNs with a payload, with Error::NonCanonicalFloat. Other floats are
+    /// read bit for bit as usual. Pair it with `canonical_nan` on the `Serializer`.
+    pub fn strict_floats(&mut self) {
+        self.config.strict_floats = true;
+    }
+
+    /// Make `is_human_readable` return true, to read what a `Serializer` with `human_readable`
+    /// enabled wrote. Like there, this doesn't change how BARE types are decoded.
+    pub fn human_readable(&mut self) {
+        self.config.human_readable = true;
+    }
+
+    /// Returns the reader, positioned after the last value read.
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+
+    pub fn get_ref(&self) -> &R {
+        &self.reader
+    }
+
+    /// Start reading from `reader`, returning the old reader. Options are kept, and so is the
+    /// buffer that borrowed strings and data are read into, so a deserializer reset between
+    /// messages doesn't have to allocate it again.
+    pub fn reset(&mut self, reader: R) -> R {
+        self.pending_field = None;
+        if let Some(read_limit) = &mut self.read_limit {
+            read_limit.used = 0;
+        }
+        if let Some(fields) = &mut self.fields {
+            fields.clear();
+        }
+        core::mem::replace(&mut self.reader, reader)
+    }
+
+    /// Start recording struct fields as they are decoded. Meant for diagnosing failures.
+    pub fn track_fields(&mut self) {
+        self.fields.get_or_insert_with(Vec::new);
+    }
+
+    /// The struct fields recorded so far, in the order they were started.
+    /// After an error the last record is the field that was being decoded.
+    pub fn fields(&self) -> &[FieldRecord] {
+        self.fields.as_deref().unwrap_or(&[])
+    }
+
+    /// A deserializer with the same options reading from `reader`. Fields aren't tracked.
+    fn nested<T>(&self, reader: T) -> Deserializer<T> {
+        Deserializer {
+            reader,
+            fields: None,
+            pending_field: None,
+            config: self.config,
+            scratch: Vec::new(),
+            remaining_len: None,
+            read_limit: None,
+        }
+    }
+
+    /// Read `len` bytes into a new Vec.
+    fn read_owned(&mut self, len: usize) -> Result<Vec<u8>, Error>
+    where
+        R: Read,
+    {
+        let mut buffer = Vec::new();
+        self.consume(len)?;
+        let max_capacity = self.max_capacity();
+        read_bytes_into(&mut self.reader, len, &mut buffer, max_capacity)?;
+        Ok(buffer)
+    }
+
+    /// The most that's allocated up front for a length read from the input: what's left of the
+    /// input if the reader knows, and UNTRUSTED_CAPACITY otherwise.
+    fn max_capacity(&self) -> usize {
+        self.remaining_len
+            .map_or(UNTRUSTED_CAPACITY, |remaining_len| {
+                remaining_len(&self.reader)
+            })
+    }
+
+    /// Count `len` more bytes against the read limit, if there is one.
+    /// Error::LimitExceeded if that would go past it.
+    fn consume(&mut self, len: usize) -> Result<(), Error> {
+        if let Some(read_limit) = &mut self.read_limit {
+            let used = read_limit.used.saturating_add(len);
+            if used > read_limit.limit {
+                return Err(Error::LimitExceeded {
+                    length: used as u64,
+                    limit: read_limit.limit,
+                });
+            }
+            read_limit.used = used;
+        }
+        Ok(())
+    }
+
+    fn start_field(&mut self, name: &'static str) {
+        if let Some(fields) = &mut self.fields {
+            self.pending_field = Some(fields.len());
+            fields.push(FieldRecord {
+                name,
+                present: None,
+            });
+        }
+    }
+
+    fn record_presence(&mut self, present: bool) {
+        if let (Some(fields), Some(i)) = (&mut self.fields, self.pending_field.take()) {
+            fields[i].present = Some(present);
+        }
+    }
+}
+
+impl<R> Deserializer<R>
+where
+    R: Read,
+{
+    /// Deserialize the next value from the reader, with this deserializer's options and limit.
+    /// Unlike `from_reader`, calling this repeatedly reads values one after another without
+    /// setting up a new deserializer for each.
+    pub fn deserialize_value<T>(&mut self) -> Result<T, Error>
+    where
+        T: de::DeserializeOwned,
+    {
+        T::deserialize(self)
+    }
+
+    /// Reads `expected.len()` bytes and checks that they match `expected`.
+    /// Error::BadMagic if they differ.
+    pub fn expect_bytes(&mut self, expected: &[u8]) -> Result<(), Error> {
+        self.consume(expected.len())?;
+        let buf = read_bytes(&mut self.reader, expected.len())?;
+        if buf == expected {
+            Ok(())
+        } else {
+            Err(Error::BadMagic)
+        }
+    }
+
+    /// Read past a value laid out as `layout` without decoding it, for example fields added to
+    /// the end of a struct by a newer version of a protocol. Lengths, optionals and union tags
+    /// are checked as they would be when decoding, but nothing else is.
+    pub fn skip(&mut self, layout: &Layout) -> Result<(), Error> {
+        match layout {
+            Layout::Uint | Layout::Int => {
+                self.read_uint()?;
+            }
+            Layout::U8 | Layout::I8 => self.discard(1)?,
+            Layout::U16 | Layout::I16 => self.discard(2)?,
+            Layout::U32 | Layout::I32 | Layout::F32 => self.discard(4)?,
+            Layout::U64 | Layout::I64 | Layout::F64 => self.discard(8)?,
+            Layout::Bool => {
+                <bool as de::Deserialize>::deserialize(&mut *self)?;
+            }
+            Layout::Str | Layout::Data => {
+                let length = self.read_length()?;
+                self.discard(length)?;
+            }
+            Layout::FixedData(len) => self.discard(*len as u64)?,
+            Layout::Void => {}
+            Layout::Optional(inner) => {
+                if <bool as de::Deserialize>::deserialize(&mut *self)? {
+                    self.skip(inner)?;
+                }
+            }
+            Layout::List(inner) => {
+                let length = self.read_length()?;
+                if length > 0 && inner.is_zero_width() {
+                    return Err(Error::ZeroWidthElements);
+                }
+                for _ in 0..length {
+                    self.skip(inner)?;
+                }
+            }
+            Layout::FixedList(inner, len) => {
+                for _ in 0..*len {
+                    self.skip(inner)?;
+                }
+            }
+            Layout::Map(key, value) => {
+                let length = self.read_length()?;
+                if length > 0 && key.is_zero_width() && value.is_zero_width() {
+                    return Err(Error::ZeroWidthElements);
+                }
+                for _ in 0..length {
+                    self.skip(key)?;
+                    self.skip(value)?;
+                }
+            }
+            Layout::Union(members) => {
+                let tag = self.read_uint()?;
+                let (_, member) = members
+                    .iter()
+                    .find(|(t, _)| *t == tag)
+                    .ok_or(Error::UnknownUnionTag(tag))?;
+                self.skip(member)?;
+            }
+            Layout::Struct(fields) => {
+                for field in fields {
+                    self.skip(field)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Read and throw away `len` bytes.
+    fn discard(&mut self, len: u64) -> Result<(), Error> {
+        let mut buf = [0u8; 256];
+        let mut left = len;
+        while left > 0 {
+            let chunk = left.min(buf.len() as u64) as usize;
+            self.read_exact(&mut buf[..chunk])?;
+            left -= chunk as u64;
+        }
+        Ok(())
+    }
+
+    fn read_exact(&mut self, buf: &mut [u8]) -> Result<(), Error> {
+        self.consume(buf.len())?;
+        self.reader.read_exact(buf).map_err(read_error)
+    }
+
+    /// Read a `uint` straight from the reader. This is also what `deserialize_tuple_struct` does
+    /// for `Uint`, rather than handing it the bytes one at a time.
+    fn read_uint(&mut self) -> Result<u64, Error> {
+        self.read_varint().map(|(x, _)| x)
+    }
+
+    /// Read a `uint`, returning it along with the number of bytes it took up.
+    fn read_varint(&mut self) -> Result<(u64, usize), Error> {
+        let mut buf = [0u8; MAX_VARINT_LEN];
+        let mut len = 0;
+        loop {
+            match self.read_exact(&mut buf[len..len + 1]) {
+                Err(Error::Eof) if len > 0 => return Err(Error::VarintTruncated),
+                result => result?,
+            }
+            len += 1;
+            if buf[len - 1] < 0x80 || len == MAX_VARINT_LEN {
+                return decode_uint(&buf[..len]);
+            }
+        }
+    }
+
+    /// Read a BARE `uint` written by `Serializer::write_len`, for framing built on top of BARE.
+    /// Like the crate's own length prefixes, it must be minimally encoded if `strict_lengths` is
+    /// enabled.
+    pub fn read_len(&mut self) -> Result<u64, Error> {
+        self.read_length()
+    }
+
+    /// Read the tag of a union or enum variant, the way derived `Deserialize` impls read it, for
+    /// hand-written ones. Tags above `u32::MAX` aren't supported.
+    pub fn read_variant_index(&mut self) -> Result<u32, Error> {
+        let id = self.read_uint()?;
+        id.try_into().map_err(|_| {
+            Error::Message("Enum identifiers larger than u32 are not supported".to_string())
+        })
+    }
+
+    /// Read a length prefix, checking that it's minimally encoded if `strict_lengths` is enabled.
+    fn read_length(&mut self) -> Result<u64, Error> {
+        let (length, len) = self.read_varint()?;
+        if self.config.strict_lengths && Uint(length).encoded_len() != len {
+            return Err(Error::NonCanonicalInt);
+        }
+        Ok(length)
+    }
+
+    /// Hand `visitor` the `len` elements of a tuple, checking that it read all of them if
+    /// `strict_tuples` is enabled.
+    fn visit_tuple<'de, V>(&mut self, len: usize, visitor: V) -> Result<V::Value, Error>
+    where
+        V: de::Visitor<'de>,
+    {
+        struct Seq<'a, R>(&'a mut Deserializer<R>, usize);
+
+        impl<'de, 'a, R> de::SeqAccess<'de> for Seq<'a, R>
+        where
+            R: Read,
+        {
+            type Error = Error;
+
+            fn next_element_seed<T>(&mut self, seed: T) -> Result<Option<T::Value>, Self::Error>
+            where
+                T: de::DeserializeSeed<'de>,
+            {
+                if self.1 == 0 {
+                    Ok(None)
+                } else {
+                    self.1 -= 1;
+                    Ok(Some(seed.deserialize(&mut *self.0)?))
+                }
+            }
+        }
+
+        let strict = self.config.strict_tuples;
+        let mut seq = Seq(self, len);
+        let value = visitor.visit_seq(&mut seq)?;
+        if strict && seq.1 != 0 {
+            return Err(de::Error::invalid_length(
+                len - seq.1,
+                &format!("{} elements", len).as_str(),
+            ));
+        }
+        Ok(value)
     }
 }
 
+#[allow(deprecated)]
 impl<'de, 'a, R> de::Deserializer<'de> for &'a mut Deserializer<R>
 where
     R: Read,
@@ -46,7 +583,7 @@ where
     where
         V: de::Visitor<'de>,
     {
-        Err(Error::AnyUnsupported)
+        Err(Error::AnyUnsupported(ANY_HINT))
     }
 
     /// BARE type: bool
@@ -54,8 +591,10 @@ where
     where
         V: de::Visitor<'de>,
     {
-        match <u8 as de::Deserialize>::deserialize(self)? {
+        match <u8 as de::Deserialize>::deserialize(&mut *self)? {
             0 => visitor.visit_bool(false),
+            1 => visitor.visit_bool(true),
+            b if self.config.strict_bool => Err(Error::InvalidBool(b)),
             _ => visitor.visit_bool(true),
         }
     }
@@ -66,7 +605,7 @@ where
         V: de::Visitor<'de>,
     {
         let mut buf = [0u8; 1];
-        self.reader.read_exact(&mut buf).map_err(Error::Io)?;
+        self.read_exact(&mut buf)?;
         visitor.visit_i8(i8::from_le_bytes(buf))
     }
 
@@ -76,7 +615,7 @@ where
         V: de::Visitor<'de>,
     {
         let mut buf = [0u8; 2];
-        self.reader.read_exact(&mut buf).map_err(Error::Io)?;
+        self.read_exact(&mut buf)?;
         visitor.visit_i16(i16::from_le_bytes(buf))
     }
 
@@ -86,7 +625,7 @@ where
         V: de::Visitor<'de>,
     {
         let mut buf = [0u8; 4];
-        self.reader.read_exact(&mut buf).map_err(Error::Io)?;
+        self.read_exact(&mut buf)?;
         visitor.visit_i32(i32::from_le_bytes(buf))
     }
 
@@ -96,7 +635,7 @@ where
         V: de::Visitor<'de>,
     {
         let mut buf = [0u8; 8];
-        self.reader.read_exact(&mut buf).map_err(Error::Io)?;
+        self.read_exact(&mut buf)?;
         visitor.visit_i64(i64::from_le_bytes(buf))
     }
 
@@ -106,8 +645,11 @@ where
         where
             V: de::Visitor<'de>
         {
+            if !cfg!(feature = "i128") {
+                return Err(Error::I128Disabled);
+            }
             let mut buf = [0u8; 16];
-            self.reader.read_exact(&mut buf).map_err(Error::Io)?;
+            self.read_exact(&mut buf)?;
             visitor.visit_i128(i128::from_le_bytes(buf))
         }
     }
@@ -118,7 +660,7 @@ where
         V: de::Visitor<'de>,
     {
         let mut buf = [0u8; 1];
-        self.reader.read_exact(&mut buf).map_err(Error::Io)?;
+        self.read_exact(&mut buf)?;
         visitor.visit_u8(u8::from_le_bytes(buf))
     }
 
@@ -128,7 +670,7 @@ where
         V: de::Visitor<'de>,
     {
         let mut buf = [0u8; 2];
-        self.reader.read_exact(&mut buf).map_err(Error::Io)?;
+        self.read_exact(&mut buf)?;
         visitor.visit_u16(u16::from_le_bytes(buf))
     }
 
@@ -138,7 +680,7 @@ where
         V: de::Visitor<'de>,
     {
         let mut buf = [0u8; 4];
-        self.reader.read_exact(&mut buf).map_err(Error::Io)?;
+        self.read_exact(&mut buf)?;
         visitor.visit_u32(u32::from_le_bytes(buf))
     }
 
@@ -148,7 +690,7 @@ where
         V: de::Visitor<'de>,
     {
         let mut buf = [0u8; 8];
-        self.reader.read_exact(&mut buf).map_err(Error::Io)?;
+        self.read_exact(&mut buf)?;
         visitor.visit_u64(u64::from_le_bytes(buf))
     }
 
@@ -158,8 +700,11 @@ where
         where
             V: de::Visitor<'de>
         {
+            if !cfg!(feature = "i128") {
+                return Err(Error::I128Disabled);
+            }
             let mut buf = [0u8; 16];
-            self.reader.read_exact(&mut buf).map_err(Error::Io)?;
+            self.read_exact(&mut buf)?;
             visitor.visit_u128(u128::from_le_bytes(buf))
         }
     }
@@ -170,8 +715,12 @@ where
         V: de::Visitor<'de>,
     {
         let mut buf = [0u8; 4];
-        self.reader.read_exact(&mut buf).map_err(Error::Io)?;
-        visitor.visit_f32(f32::from_le_bytes(buf))
+        self.read_exact(&mut buf)?;
+        let v = f32::from_le_bytes(buf);
+        if self.config.strict_floats && v.is_nan() && v.to_bits() != CANONICAL_NAN_F32 {
+            return Err(Error::NonCanonicalFloat);
+        }
+        visitor.visit_f32(v)
     }
 
     /// BARE type: f64
@@ -180,8 +729,12 @@ where
         V: de::Visitor<'de>,
     {
         let mut buf = [0u8; 8];
-        self.reader.read_exact(&mut buf).map_err(Error::Io)?;
-        visitor.visit_f64(f64::from_le_bytes(buf))
+        self.read_exact(&mut buf)?;
+        let v = f64::from_le_bytes(buf);
+        if self.config.strict_floats && v.is_nan() && v.to_bits() != CANONICAL_NAN_F64 {
+            return Err(Error::NonCanonicalFloat);
+        }
+        visitor.visit_f64(v)
     }
 
     /// BARE type: u32
@@ -190,7 +743,11 @@ where
         V: de::Visitor<'de>,
     {
         let codepoint = <u32 as de::Deserialize>::deserialize(self)?;
-        visitor.visit_char(codepoint.try_into().map_err(|_| Error::InvalidChar)?)
+        visitor.visit_char(
+            codepoint
+                .try_into()
+                .map_err(|_| Error::InvalidChar(codepoint))?,
+        )
     }
 
     /// BARE type: string
@@ -198,10 +755,12 @@ where
     where
         V: de::Visitor<'de>,
     {
-        let Uint(length) = <Uint as de::Deserialize>::deserialize(&mut *self)?;
-        let length = length as usize;
-        let buf = read_bytes(&mut self.reader, length).map_err(Error::Io)?;
-        let utf8 = str::from_utf8(&buf).map_err(|_| Error::InvalidUtf8)?;
+        let length = self.read_length()?;
+        let length = checked_length(length)?;
+        self.consume(length)?;
+        let max_capacity = self.max_capacity();
+        read_bytes_into(&mut self.reader, length, &mut self.scratch, max_capacity)?;
+        let utf8 = str::from_utf8(&self.scratch)?;
         visitor.visit_str(utf8)
     }
 
@@ -210,10 +769,10 @@ where
     where
         V: de::Visitor<'de>,
     {
-        let Uint(length) = <Uint as de::Deserialize>::deserialize(&mut *self)?;
-        let length = length as usize;
-        let buf = read_bytes(&mut self.reader, length).map_err(Error::Io)?;
-        let utf8 = String::from_utf8(buf).map_err(|_| Error::InvalidUtf8)?;
+        let length = self.read_length()?;
+        let length = checked_length(length)?;
+        let buf = self.read_owned(length)?;
+        let utf8 = String::from_utf8(buf).map_err(|e| e.utf8_error())?;
         visitor.visit_string(utf8)
     }
 
@@ -222,10 +781,12 @@ where
     where
         V: de::Visitor<'de>,
     {
-        let Uint(length) = <Uint as de::Deserialize>::deserialize(&mut *self)?;
-        let length = length as usize;
-        let buf = read_bytes(&mut self.reader, length).map_err(Error::Io)?;
-        visitor.visit_bytes(&buf)
+        let length = self.read_length()?;
+        let length = checked_length(length)?;
+        self.consume(length)?;
+        let max_capacity = self.max_capacity();
+        read_bytes_into(&mut self.reader, length, &mut self.scratch, max_capacity)?;
+        visitor.visit_bytes(&self.scratch)
     }
 
     /// BARE type: data
@@ -233,9 +794,9 @@ where
     where
         V: de::Visitor<'de>,
     {
-        let Uint(length) = <Uint as de::Deserialize>::deserialize(&mut *self)?;
-        let length = length as usize;
-        let buf = read_bytes(&mut self.reader, length).map_err(Error::Io)?;
+        let length = self.read_length()?;
+        let length = checked_length(length)?;
+        let buf = self.read_owned(length)?;
         visitor.visit_byte_buf(buf)
     }
 
@@ -244,7 +805,9 @@ where
     where
         V: de::Visitor<'de>,
     {
-        if <bool as de::Deserialize>::deserialize(&mut *self)? {
+        let present = <bool as de::Deserialize>::deserialize(&mut *self)?;
+        self.record_presence(present);
+        if present {
             visitor.visit_some(self)
         } else {
             visitor.visit_none()
@@ -288,9 +851,10 @@ where
     where
         V: de::Visitor<'de>,
     {
-        let Uint(length) = <Uint as de::Deserialize>::deserialize(&mut *self)?;
+        let length = self.read_length()?;
 
-        struct Seq<'a, R>(&'a mut Deserializer<R>, u64);
+        /// The length of the sequence and the number of elements read so far.
+        struct Seq<'a, R>(&'a mut Deserializer<R>, u64, u64);
 
         impl<'de, 'a, R> de::SeqAccess<'de> for Seq<'a, R>
         where
@@ -302,82 +866,62 @@ where
             where
                 T: de::DeserializeSeed<'de>,
             {
-                if self.1 == 0 {
-                    Ok(None)
-                } else {
-                    self.1 -= 1;
-                    Ok(Some(seed.deserialize(&mut *self.0)?))
+                if self.2 == self.1 {
+                    return Ok(None);
                 }
+                let element = seed
+                    .deserialize(&mut *self.0)
+                    .map_err(|e| seq_truncated(e, self.1, self.2))?;
+                self.2 += 1;
+                Ok(Some(element))
             }
         }
 
-        visitor.visit_seq(Seq::<'a, R>(self, length))
+        visitor.visit_seq(Seq::<'a, R>(self, length, 0))
     }
 
     /// BARE type: \[len\]T
-    /// Deserializing fewer elements than `len` is allowed.
+    /// Deserializing fewer elements than `len` is allowed, unless `strict_tuples` is enabled.
     fn deserialize_tuple<V>(self, len: usize, visitor: V) -> Result<V::Value, Self::Error>
     where
         V: de::Visitor<'de>,
     {
-        struct Seq<'a, R>(&'a mut Deserializer<R>, usize);
-
-        impl<'de, 'a, R> de::SeqAccess<'de> for Seq<'a, R>
-        where
-            R: Read,
-        {
-            type Error = Error;
-
-            fn next_element_seed<T>(&mut self, seed: T) -> Result<Option<T::Value>, Self::Error>
-            where
-                T: de::DeserializeSeed<'de>,
-            {
-                if self.1 == 0 {
-                    Ok(None)
-                } else {
-                    self.1 -= 1;
-                    Ok(Some(seed.deserialize(&mut *self.0)?))
-                }
-            }
-        }
-
-        visitor.visit_seq(Seq::<'a, R>(self, len))
+        self.visit_tuple(len, visitor)
     }
 
     /// BARE type: struct
-    /// `name` is ignored.
-    /// Deserializing fewer elements than `len` is allowed.
+    /// `name` is ignored, except for the names used by `Uint`, `heapless` and `bitflags`.
+    /// Deserializing fewer elements than `len` is allowed, unless `strict_tuples` is enabled.
     fn deserialize_tuple_struct<V>(
         self,
-        _name: &'static str,
+        name: &'static str,
         len: usize,
         visitor: V,
     ) -> Result<V::Value, Self::Error>
     where
         V: de::Visitor<'de>,
     {
-        struct Seq<'a, R>(&'a mut Deserializer<R>, usize);
-
-        impl<'de, 'a, R> de::SeqAccess<'de> for Seq<'a, R>
-        where
-            R: Read,
-        {
-            type Error = Error;
-
-            fn next_element_seed<T>(&mut self, seed: T) -> Result<Option<T::Value>, Self::Error>
-            where
-                T: de::DeserializeSeed<'de>,
-            {
-                if self.1 == 0 {
-                    Ok(None)
-                } else {
-                    self.1 -= 1;
-                    Ok(Some(seed.deserialize(&mut *self.0)?))
-                }
-            }
+        if name == UINT {
+            // Read in one go, which is faster, and reports a malformed one with a specific error.
+            return visitor.visit_u64(self.read_uint()?);
+        }
+        if name == BOUNDED_BYTES {
+            let length = check_limit(self.read_length()?, len)?;
+            self.consume(length)?;
+            read_bytes_into(&mut self.reader, length, &mut self.scratch, len)?;
+            return visitor.visit_bytes(&self.scratch);
+        }
+        #[cfg(feature = "bitflags")]
+        if name == CHECKED_FLAGS {
+            let mut buf = [0u8; 8];
+            self.read_exact(flags_bytes(&mut buf, len)?)?;
+            let bits = u64::from_le_bytes(buf);
+            return visitor
+                .visit_u64(bits)
+                .map_err(|_: Error| Error::InvalidFlags(bits));
         }
 
-        visitor.visit_seq(Seq::<'a, R>(self, len))
+        self.visit_tuple(len, visitor)
     }
 
     /// BARE type: map\[T\]U
@@ -385,9 +929,10 @@ where
     where
         V: de::Visitor<'de>,
     {
-        let Uint(length) = <Uint as de::Deserialize>::deserialize(&mut *self)?;
+        let length = self.read_length()?;
 
-        struct Map<'a, R>(&'a mut Deserializer<R>, u64);
+        /// The keys read so far are kept when rejecting duplicates.
+        struct Map<'a, R>(&'a mut Deserializer<R>, u64, Option<BTreeSet<Vec<u8>>>);
 
         impl<'de, 'a, R> de::MapAccess<'de> for Map<'a, R>
         where
@@ -400,12 +945,29 @@ where
                 K: de::DeserializeSeed<'de>,
             {
                 if self.1 == 0 {
-                    Ok(None)
-                } else {
-                    Ok(Some(seed.deserialize(&mut *self.0)?))
+                    return Ok(None);
+                }
+                let keys = match &mut self.2 {
+                    Some(keys) => keys,
+                    None => return Ok(Some(seed.deserialize(&mut *self.0)?)),
+                };
+                // Copy the options out first, as the recorder borrows the reader.
+                let options = self.0.nested(());
+                let mut recorder = Recorder {
+                    reader: &mut self.0.reader,
+                    bytes: Vec::new(),
+                };
+                let mut key_deserializer = options.nested(&mut recorder);
+                key_deserializer.read_limit = self.0.read_limit;
+                let key = seed.deserialize(&mut key_deserializer)?;
+                self.0.read_limit = key_deserializer.read_limit;
+                if !keys.insert(recorder.bytes) {
+                    return Err(Error::DuplicateMapKey);
                 }
+                Ok(Some(key))
             }
 
+            #[allow(clippy::needless_question_mark)]
             fn next_value_seed<V>(&mut self, seed: V) -> Result<V::Value, Self::Error>
             where
                 V: de::DeserializeSeed<'de>,
@@ -415,20 +977,21 @@ where
             }
         }
 
-        visitor.visit_map(Map::<'a, R>(self, length))
+        let keys = self.config.reject_duplicate_keys.then(BTreeSet::new);
+        visitor.visit_map(Map::<'a, R>(self, length, keys))
     }
 
     /// BARE type: struct
     fn deserialize_struct<V>(
         self,
         _name: &'static str,
-        _fields: &'static [&'static str],
+        fields: &'static [&'static str],
         visitor: V,
     ) -> Result<V::Value, Self::Error>
     where
         V: de::Visitor<'de>,
     {
-        struct Seq<'a, R>(&'a mut Deserializer<R>);
+        struct Seq<'a, R>(&'a mut Deserializer<R>, &'static [&'static str], usize);
 
         impl<'de, 'a, R> de::SeqAccess<'de> for Seq<'a, R>
         where
@@ -439,11 +1002,14 @@ where
             where
                 T: de::DeserializeSeed<'de>,
             {
+                self.0
+                    .start_field(self.1.get(self.2).copied().unwrap_or(""));
+                self.2 += 1;
                 Ok(Some(seed.deserialize(&mut *self.0)?))
             }
         }
 
-        visitor.visit_seq(Seq::<'a, R>(self))
+        visitor.visit_seq(Seq::<'a, R>(self, fields, 0))
     }
 
     fn deserialize_enum<V>(
@@ -513,6 +1079,9 @@ where
             }
         }
 
+        if self.config.length_prefixed_variants {
+            return visitor.visit_enum(PrefixedEnum(self));
+        }
         visitor.visit_enum(Enum::<'a, R>(self))
     }
 
@@ -521,11 +1090,7 @@ where
     where
         V: de::Visitor<'de>,
     {
-        let Uint(id) = <Uint as de::Deserialize>::deserialize(&mut *self)?;
-        let variant: u32 = id.try_into().map_err(|_| {
-            Error::Message("Enum identifiers larger than u32 are not supported".to_string())
-        })?;
-        visitor.visit_u32(variant)
+        visitor.visit_u32(self.read_variant_index()?)
     }
 
     /// Returns Error::AnyUnsupported.
@@ -533,44 +1098,1472 @@ where
     where
         V: de::Visitor<'de>,
     {
-        Err(Error::AnyUnsupported)
+        Err(Error::AnyUnsupported(IGNORED_ANY_HINT))
     }
 
-    /// Returns false.
+    /// Returns false unless `human_readable` is enabled.
     fn is_human_readable(&self) -> bool {
-        false
+        self.config.human_readable
     }
 }
 
-pub fn from_reader<R, T>(reader: R) -> Result<T, Error>
+/// Wraps a reader and keeps a copy of the bytes read through it.
+struct Recorder<R> {
+    reader: R,
+    bytes: Vec<u8>,
+}
+
+impl<R> Read for Recorder<R>
 where
     R: Read,
-    T: de::DeserializeOwned,
 {
-    T::deserialize(&mut Deserializer { reader })
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = self.reader.read(buf)?;
+        self.bytes.extend_from_slice(&buf[..read]);
+        Ok(read)
+    }
 }
 
-pub fn from_slice<T>(slice: &[u8]) -> Result<T, Error>
+/// An enum whose variant data is preceded by its length.
+struct PrefixedEnum<'a, R>(&'a mut Deserializer<R>);
+
+impl<'de, R> de::EnumAccess<'de> for PrefixedEnum<'_, R>
 where
-    T: de::DeserializeOwned,
+    R: Read,
 {
-    T::deserialize(&mut Deserializer {
-        reader: Cursor::new(slice),
-    })
-}
+    type Error = Error;
+    type Variant = PrefixedVariant;
+
+    fn variant_seed<V>(self, seed: V) -> Result<(V::Value, Self::Variant), Self::Error>
+    where
+        V: de::DeserializeSeed<'de>,
+    {
+        let val = seed.deserialize(&mut *self.0)?;
+        let length = self.0.read_length()?;
+        let length = checked_length(length)?;
+        self.0.consume(length)?;
+        let data = read_bytes(&mut self.0.reader, length)?;
+        Ok((val, PrefixedVariant(self.0.nested(Cursor::new(data)))))
+    }
+}
+
+/// The data of a length-prefixed variant, read in full.
+struct PrefixedVariant(Deserializer<Cursor<Vec<u8>>>);
+
+impl<'de> de::VariantAccess<'de> for PrefixedVariant {
+    type Error = Error;
+
+    /// Unserialized type. Any data, such as that of an unknown variant, is skipped.
+    fn unit_variant(self) -> Result<(), Self::Error> {
+        Ok(())
+    }
+
+    fn newtype_variant_seed<T>(mut self, seed: T) -> Result<T::Value, Self::Error>
+    where
+        T: de::DeserializeSeed<'de>,
+    {
+        seed.deserialize(&mut self.0)
+    }
+
+    fn tuple_variant<V>(mut self, len: usize, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: de::Visitor<'de>,
+    {
+        de::Deserializer::deserialize_tuple(&mut self.0, len, visitor)
+    }
+
+    fn struct_variant<V>(
+        mut self,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Self::Error>
+    where
+        V: de::Visitor<'de>,
+    {
+        de::Deserializer::deserialize_struct(&mut self.0, "", fields, visitor)
+    }
+}
+
+/// Reads from a byte slice, keeping track of how much of it has been read. This is what
+/// `from_slice` reads through; unlike `Cursor` it only supports reading forwards, and doesn't
+/// need `std`.
+#[derive(Clone, Debug)]
+pub struct SliceReader<'a> {
+    slice: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> SliceReader<'a> {
+    pub fn new(slice: &'a [u8]) -> Self {
+        SliceReader { slice, pos: 0 }
+    }
+
+    /// The number of bytes read so far.
+    pub fn position(&self) -> usize {
+        self.pos
+    }
+
+    /// The bytes that haven't been read yet.
+    pub fn remaining(&self) -> &'a [u8] {
+        &self.slice[self.pos..]
+    }
+}
+
+impl Read for SliceReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let remaining = self.remaining();
+        let len = buf.len().min(remaining.len());
+        buf[..len].copy_from_slice(&remaining[..len]);
+        self.pos += len;
+        Ok(len)
+    }
+
+    /// Nothing is read if there aren't enough bytes left.
+    fn read_exact(&mut self, buf: &mut [u8]) -> io::Result<()> {
+        let bytes = self
+            .remaining()
+            .get(..buf.len())
+            .ok_or_else(|| io::Error::from(io::ErrorKind::UnexpectedEof))?;
+        buf.copy_from_slice(bytes);
+        self.pos += buf.len();
+        Ok(())
+    }
+}
+
+/// A deserializer over an in-memory buffer that hands out strings and byte slices borrowed from
+/// it instead of copying them.
+pub struct SliceDeserializer<'de> {
+    slice: &'de [u8],
+}
+
+impl<'de> SliceDeserializer<'de> {
+    pub fn new(slice: &'de [u8]) -> Self {
+        SliceDeserializer { slice }
+    }
+
+    /// Read a `uint` length prefix followed by that many bytes, borrowed from the input.
+    fn read_prefixed(&mut self) -> Result<&'de [u8], Error> {
+        let Uint(length) = <Uint as de::Deserialize>::deserialize(&mut *self)?;
+        let length = checked_length(length)?;
+        self.read_borrowed(length)
+    }
+
+    /// Read `length` bytes, borrowed from the input.
+    fn read_borrowed(&mut self, length: usize) -> Result<&'de [u8], Error> {
+        if length > self.slice.len() {
+            return Err(Error::Eof);
+        }
+        let (bytes, rest) = self.slice.split_at(length);
+        self.slice = rest;
+        Ok(bytes)
+    }
+}
+
+/// Fixed-size values can't borrow anything, so they are read by a `Deserializer` over the
+/// remaining input, which advances it.
+macro_rules! forward_to_reader {
+    ($($method:ident)*) => {
+        $(
+            fn $method<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+            where
+                V: de::Visitor<'de>,
+            {
+                de::Deserializer::$method(&mut Deserializer::new(&mut self.slice), visitor)
+            }
+        )*
+    };
+}
+
+impl<'de> de::Deserializer<'de> for &mut SliceDeserializer<'de> {
+    type Error = Error;
+
+    forward_to_reader! {
+        deserialize_any deserialize_bool deserialize_i8 deserialize_i16 deserialize_i32
+        deserialize_i64 deserialize_i128 deserialize_u8 deserialize_u16 deserialize_u32
+        deserialize_u64 deserialize_u128 deserialize_f32 deserialize_f64 deserialize_char
+        deserialize_unit deserialize_identifier deserialize_ignored_any
+    }
+
+    /// BARE type: string
+    fn deserialize_str<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: de::Visitor<'de>,
+    {
+        let buf = self.read_prefixed()?;
+        let utf8 = str::from_utf8(buf)?;
+        visitor.visit_borrowed_str(utf8)
+    }
+
+    /// BARE type: string
+    fn deserialize_string<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: de::Visitor<'de>,
+    {
+        self.deserialize_str(visitor)
+    }
+
+    /// BARE type: data
+    fn deserialize_bytes<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: de::Visitor<'de>,
+    {
+        let buf = self.read_prefixed()?;
+        visitor.visit_borrowed_bytes(buf)
+    }
+
+    /// BARE type: data
+    fn deserialize_byte_buf<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: de::Visitor<'de>,
+    {
+        self.deserialize_bytes(visitor)
+    }
+
+    /// BARE type: optional<type>
+    fn deserialize_option<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: de::Visitor<'de>,
+    {
+        if <bool as de::Deserialize>::deserialize(&mut *self)? {
+            visitor.visit_some(self)
+        } else {
+            visitor.visit_none()
+        }
+    }
+
+    /// BARE type: void
+    fn deserialize_unit_struct<V>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error>
+    where
+        V: de::Visitor<'de>,
+    {
+        visitor.visit_unit()
+    }
+
+    /// BARE type: void
+    fn deserialize_newtype_struct<V>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error>
+    where
+        V: de::Visitor<'de>,
+    {
+        visitor.visit_newtype_struct(self)
+    }
+
+    /// BARE type: []T
+    fn deserialize_seq<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: de::Visitor<'de>,
+    {
+        let Uint(length) = <Uint as de::Deserialize>::deserialize(&mut *self)?;
+        let length = checked_length(length)?;
+        let mut got = 0;
+        visitor
+            .visit_seq(SliceSeq(self, length, Some(&mut got)))
+            .map_err(|e| seq_truncated(e, length as u64, got as u64))
+    }
+
+    /// BARE type: \[len\]T
+    /// Deserializing fewer elements than `len` is allowed.
+    fn deserialize_tuple<V>(self, len: usize, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: de::Visitor<'de>,
+    {
+        visitor.visit_seq(SliceSeq(self, len, None))
+    }
+
+    /// BARE type: struct
+    /// `name` is ignored, except for the names used by `Uint`, `heapless` and `bitflags`.
+    /// Deserializing fewer elements than `len` is allowed.
+    fn deserialize_tuple_struct<V>(
+        self,
+        name: &'static str,
+        len: usize,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error>
+    where
+        V: de::Visitor<'de>,
+    {
+        if name == UINT {
+            // Read by the reader's deserializer in one go.
+            return de::Deserializer::deserialize_tuple_struct(
+                &mut Deserializer::new(&mut self.slice),
+                name,
+                len,
+                visitor,
+            );
+        }
+        if name == BOUNDED_BYTES {
+            let Uint(length) = <Uint as de::Deserialize>::deserialize(&mut *self)?;
+            let length = check_limit(length, len)?;
+            return visitor.visit_borrowed_bytes(self.read_borrowed(length)?);
+        }
+        #[cfg(feature = "bitflags")]
+        if name == CHECKED_FLAGS {
+            let mut buf = [0u8; 8];
+            let bytes = flags_bytes(&mut buf, len)?;
+            bytes.copy_from_slice(self.read_borrowed(len)?);
+            let bits = u64::from_le_bytes(buf);
+            return visitor
+                .visit_u64(bits)
+                .map_err(|_: Error| Error::InvalidFlags(bits));
+        }
+        visitor.visit_seq(SliceSeq(self, len, None))
+    }
+
+    /// BARE type: map\[T\]U
+    fn deserialize_map<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: de::Visitor<'de>,
+    {
+        let Uint(length) = <Uint as de::Deserialize>::deserialize(&mut *self)?;
+
+        struct Map<'a, 'de>(&'a mut SliceDeserializer<'de>, u64);
+
+        impl<'de, 'a> de::MapAccess<'de> for Map<'a, 'de> {
+            type Error = Error;
+
+            fn next_key_seed<K>(&mut self, seed: K) -> Result<Option<K::Value>, Self::Error>
+            where
+                K: de::DeserializeSeed<'de>,
+            {
+                if self.1 == 0 {
+                    Ok(None)
+                } else {
+                    Ok(Some(seed.deserialize(&mut *self.0)?))
+                }
+            }
+
+            fn next_value_seed<V>(&mut self, seed: V) -> Result<V::Value, Self::Error>
+            where
+                V: de::DeserializeSeed<'de>,
+            {
+                self.1 -= 1;
+                seed.deserialize(&mut *self.0)
+            }
+        }
+
+        visitor.visit_map(Map(self, length))
+    }
+
+    /// BARE type: struct
+    fn deserialize_struct<V>(
+        self,
+        _name: &'static str,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Self::Error>
+    where
+        V: de::Visitor<'de>,
+    {
+        visitor.visit_seq(SliceSeq(self, fields.len(), None))
+    }
+
+    fn deserialize_enum<V>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Self::Error>
+    where
+        V: de::Visitor<'de>,
+    {
+        struct Enum<'a, 'de>(&'a mut SliceDeserializer<'de>);
+
+        impl<'de, 'a> de::EnumAccess<'de> for Enum<'a, 'de> {
+            type Error = Error;
+            type Variant = Self;
+
+            fn variant_seed<V>(self, seed: V) -> Result<(V::Value, Self::Variant), Self::Error>
+            where
+                V: de::DeserializeSeed<'de>,
+            {
+                let val = seed.deserialize(&mut *self.0)?;
+                Ok((val, self))
+            }
+        }
+
+        impl<'de, 'a> de::VariantAccess<'de> for Enum<'a, 'de> {
+            type Error = Error;
+
+            /// Unserialized type.
+            fn unit_variant(self) -> Result<(), Self::Error> {
+                Ok(())
+            }
+
+            /// Bare type: T
+            fn newtype_variant_seed<T>(self, seed: T) -> Result<T::Value, Self::Error>
+            where
+                T: de::DeserializeSeed<'de>,
+            {
+                seed.deserialize(self.0)
+            }
+
+            /// Bare type: struct
+            fn tuple_variant<V>(self, len: usize, visitor: V) -> Result<V::Value, Self::Error>
+            where
+                V: de::Visitor<'de>,
+            {
+                de::Deserializer::deserialize_tuple(self.0, len, visitor)
+            }
+
+            /// Bare type: struct
+            fn struct_variant<V>(
+                self,
+                fields: &'static [&'static str],
+                visitor: V,
+            ) -> Result<V::Value, Self::Error>
+            where
+                V: de::Visitor<'de>,
+            {
+                de::Deserializer::deserialize_struct(self.0, "", fields, visitor)
+            }
+        }
+
+        visitor.visit_enum(Enum(self))
+    }
+
+    /// Returns false.
+    fn is_human_readable(&self) -> bool {
+        false
+    }
+}
+
+/// The elements left, and for sequences the count of those read so far.
+struct SliceSeq<'a, 'de>(&'a mut SliceDeserializer<'de>, usize, Option<&'a mut usize>);
+
+impl<'de, 'a> de::SeqAccess<'de> for SliceSeq<'a, 'de> {
+    type Error = Error;
+
+    fn next_element_seed<T>(&mut self, seed: T) -> Result<Option<T::Value>, Self::Error>
+    where
+        T: de::DeserializeSeed<'de>,
+    {
+        if self.1 == 0 {
+            return Ok(None);
+        }
+        let element = seed.deserialize(&mut *self.0)?;
+        self.1 -= 1;
+        if let Some(got) = &mut self.2 {
+            **got += 1;
+        }
+        Ok(Some(element))
+    }
+}
+
+/// Turn input ending in the middle of element `got` of a sequence into Error::SeqTruncated.
+/// Errors from inner sequences are already more precise, and are kept.
+fn seq_truncated(e: Error, expected: u64, got: u64) -> Error {
+    match e {
+        Error::Eof => Error::SeqTruncated { expected, got },
+        e => e,
+    }
+}
+
+/// Deserialize a `Vec<Data<N>>`, such as a list of hashes, the way serde would, with one read per
+/// few thousand bytes instead of one per byte. serde can't tell a `Deserializer` the element type
+/// of a `Vec`, so hot paths have to call this directly.
+pub fn deserialize_data_vec<R, const N: usize>(
+    de: &mut Deserializer<R>,
+) -> Result<Vec<Data<N>>, Error>
+where
+    R: Read,
+{
+    let length = checked_length(de.read_length()?)?;
+    if N == 0 {
+        return if length == 0 {
+            Ok(Vec::new())
+        } else {
+            Err(Error::ZeroWidthElements)
+        };
+    }
+    let mut left = length.checked_mul(N).ok_or(Error::LengthOverflow)?;
+    // Read as much at a time as is trusted to be allocated up front, in whole elements.
+    let chunk_len = de.max_capacity().max(N) / N * N;
+    let mut values = Vec::with_capacity(length.min(chunk_len / N));
+    let mut buf = core::mem::take(&mut de.scratch);
+    while left > 0 {
+        buf.resize(left.min(chunk_len), 0);
+        de.read_exact(&mut buf)?;
+        values.extend(
+            buf.chunks_exact(N)
+                .map(|bytes| Data(bytes.try_into().unwrap())),
+        );
+        left -= buf.len();
+    }
+    de.scratch = buf;
+    Ok(values)
+}
+
+/// Deserialize a value from `reader`. Only the value's own bytes are read, so passing a
+/// `&mut` reader, such as a `BufReader`, leaves it positioned at whatever follows.
+pub fn from_reader<R, T>(reader: R) -> Result<T, Error>
+where
+    R: Read,
+    T: de::DeserializeOwned,
+{
+    T::deserialize(&mut Deserializer::new(reader))
+}
+
+/// Like `from_reader`, with the options in `config`.
+pub fn from_reader_with_config<R, T>(reader: R, config: DeserializerConfig) -> Result<T, Error>
+where
+    R: Read,
+    T: de::DeserializeOwned,
+{
+    T::deserialize(&mut Deserializer::with_config(reader, config))
+}
+
+pub fn from_slice<T>(slice: &[u8]) -> Result<T, Error>
+where
+    T: de::DeserializeOwned,
+{
+    from_slice_with_config(slice, DeserializerConfig::default())
+}
+
+/// Like `from_slice`, with the options in `config`.
+pub fn from_slice_with_config<T>(slice: &[u8], config: DeserializerConfig) -> Result<T, Error>
+where
+    T: de::DeserializeOwned,
+{
+    let mut deserializer = Deserializer::with_config(SliceReader::new(slice), config);
+    deserializer.remaining_len = Some(|reader| reader.remaining().len());
+    T::deserialize(&mut deserializer)
+}
+
+/// Read a frame written by `to_writer_framed`: a BARE `uint` length followed by that many bytes
+/// holding the value. Only the frame is consumed.
+/// Error::TrailingData if the value doesn't use every byte of the frame.
+pub fn from_reader_framed<R, T>(mut reader: R) -> Result<T, Error>
+where
+    R: Read,
+    T: de::DeserializeOwned,
+{
+    // Read the prefix a byte at a time so that nothing past the frame is consumed.
+    let mut prefix = [0u8; MAX_VARINT_LEN];
+    let mut read = 0;
+    let length = loop {
+        reader
+            .read_exact(&mut prefix[read..=read])
+            .map_err(read_error)?;
+        read += 1;
+        if let Some((_, length)) = frame_prefix(&prefix[..read])? {
+            break length;
+        }
+    };
+    from_frame_body(&read_bytes(&mut reader, length)?)
+}
+
+/// Deserialize a value that takes up exactly the next `len` bytes of `reader`, such as the body
+/// of a frame whose length came from some other layer. At most `len` bytes are read.
+/// Error::Eof if the value runs past `len` bytes, and Error::TrailingData if it ends before them,
+/// in which case the rest of the body is left unread.
+pub fn from_reader_sized<R, T>(reader: R, len: usize) -> Result<T, Error>
+where
+    R: Read,
+    T: de::DeserializeOwned,
+{
+    let mut deserializer = Deserializer::new(reader.take(len as u64));
+    let value = T::deserialize(&mut deserializer)?;
+    if deserializer.reader.limit() != 0 {
+        return Err(Error::TrailingData);
+    }
+    Ok(value)
+}
+
+/// Deserialize a value followed by zero bytes padding it to a multiple of `align` bytes, and
+/// consume the padding too. An `align` of 0 or 1 means there's no padding.
+/// Error::BadPadding if a padding byte isn't zero.
+pub fn from_reader_padded<R, T>(reader: R, align: usize) -> Result<T, Error>
+where
+    R: Read,
+    T: de::DeserializeOwned,
+{
+    let mut deserializer = Deserializer::new(CountingReader { reader, count: 0 });
+    let value = T::deserialize(&mut deserializer)?;
+    let CountingReader { reader, count } = deserializer.reader;
+    let padding = match align {
+        0 | 1 => 0,
+        _ => (align - count % align) % align,
+    };
+    if read_bytes(reader, padding)?.iter().any(|&b| b != 0) {
+        return Err(Error::BadPadding);
+    }
+    Ok(value)
+}
+
+/// Wraps a reader and tracks how many bytes have been read through it.
+struct CountingReader<R> {
+    reader: R,
+    count: usize,
+}
+
+impl<R> Read for CountingReader<R>
+where
+    R: Read,
+{
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = self.reader.read(buf)?;
+        self.count += read;
+        Ok(read)
+    }
+}
+
+/// Read the schema version written by `to_vec_versioned` and pass it to `pick` along with the
+/// rest of `bytes`. `pick` decodes the body however that version requires, or rejects the version.
+pub fn from_slice_versioned<'a, T, F>(mut bytes: &'a [u8], pick: F) -> Result<T, Error>
+where
+    F: FnOnce(u64, &'a [u8]) -> Result<T, Error>,
+{
+    let Uint(version) = <Uint as de::Deserialize>::deserialize(&mut Deserializer::new(&mut bytes))?;
+    pick(version, bytes)
+}
+
+/// Try to deserialize a value from the start of `slice`, which may hold only part of it.
+/// Returns the value and the number of bytes it used, or None if `slice` ends before the value
+/// does. Malformed input is still an error.
+pub fn try_from_slice<T>(slice: &[u8]) -> Result<Option<(T, usize)>, Error>
+where
+    T: de::DeserializeOwned,
+{
+    let mut deserializer = Deserializer::new(slice);
+    match T::deserialize(&mut deserializer) {
+        Ok(value) => Ok(Some((value, slice.len() - deserializer.reader.len()))),
+        Err(Error::Eof | Error::SeqTruncated { .. } | Error::VarintTruncated) => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+/// Like `from_slice`, but strings and byte slices in `T` may borrow from `slice`.
+/// `Cow<str>` and `Cow<[u8]>` fields marked `#[serde(borrow)]` come out as `Cow::Borrowed`.
+pub fn from_slice_borrowed<'de, T>(slice: &'de [u8]) -> Result<T, Error>
+where
+    T: de::Deserialize<'de>,
+{
+    T::deserialize(&mut SliceDeserializer::new(slice))
+}
+
+/// Deserialize back-to-back values of type `T` from `reader`, one per call to `next`.
+/// Iteration ends when the reader ends between two values. A reader that ends partway through a
+/// value yields Error::Eof, or Error::SeqTruncated or Error::VarintTruncated if it ends inside a
+/// sequence or integer. Nothing more is read after an error.
+pub fn from_reader_iter<R, T>(reader: R) -> ReaderIter<R, T>
+where
+    R: Read,
+    T: de::DeserializeOwned,
+{
+    ReaderIter {
+        reader: Some(reader),
+        _marker: PhantomData,
+    }
+}
+
+/// The iterator returned by `from_reader_iter`.
+pub struct ReaderIter<R, T> {
+    /// None once the reader is exhausted or has failed.
+    reader: Option<R>,
+    _marker: PhantomData<fn() -> T>,
+}
+
+impl<R, T> Iterator for ReaderIter<R, T>
+where
+    R: Read,
+    T: de::DeserializeOwned,
+{
+    type Item = Result<T, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let reader = self.reader.as_mut()?;
+
+        // Read the first byte separately to tell a clean end from a truncated value.
+        let mut first = [0u8; 1];
+        let result = loop {
+            match reader.read(&mut first) {
+                Ok(0) => break None,
+                Ok(_) => {
+                    let mut deserializer = Deserializer::new((&first[..]).chain(&mut *reader));
+                    break Some(T::deserialize(&mut deserializer));
+                }
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => break Some(Err(read_error(e))),
+            }
+        };
+        if !matches!(result, Some(Ok(_))) {
+            self.reader = None;
+        }
+        result
+    }
+}
 
 #[cfg(test)]
 mod test {
     use super::*;
 
     #[test]
-    fn test_bool() {
-        assert_eq!(false, from_slice(&[0]).unwrap());
-        assert_eq!(true, from_slice(&[1]).unwrap());
-        assert_eq!(true, from_slice(&[2]).unwrap());
+    #[allow(clippy::bool_assert_comparison)]
+    fn test_bool() {
+        assert_eq!(false, from_slice(&[0]).unwrap());
+        assert_eq!(true, from_slice(&[1]).unwrap());
+        // Lenient by default
+        assert_eq!(true, from_slice(&[2]).unwrap());
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn test_from_reader_buffered() {
+        use std::io::{BufReader, Cursor, Read};
+        let mut bytes = crate::to_vec(&("first", 1u8)).unwrap();
+        bytes.extend(crate::to_vec(&("second", 2u8)).unwrap());
+        bytes.extend_from_slice(b"rest");
+        // A small buffer, so messages straddle refills
+        let mut reader = BufReader::with_capacity(4, Cursor::new(bytes));
+        let first: (String, u8) = from_reader(&mut reader).unwrap();
+        assert_eq!(first, ("first".to_string(), 1));
+        let second: (String, u8) = from_reader(&mut reader).unwrap();
+        assert_eq!(second, ("second".to_string(), 2));
+        let mut rest = Vec::new();
+        reader.read_to_end(&mut rest).unwrap();
+        assert_eq!(rest, b"rest");
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn test_presize_from_slice() {
+        let bytes = crate::to_vec(&serde_bytes::Bytes::new(&[7; 100_000])).unwrap();
+        let buf: serde_bytes::ByteBuf = from_slice(&bytes).unwrap();
+        assert_eq!(buf.len(), 100_000);
+        // Allocated once, up front
+        assert_eq!(buf.capacity(), 100_000);
+
+        // A length longer than the input isn't trusted
+        let buf: Result<serde_bytes::ByteBuf, _> = from_slice(&[0xff, 0xff, 0xff, 0xff, 0x0f]);
+        assert!(matches!(buf, Err(Error::Eof)));
+
+        // Only what's left of the input counts, not what's been read already
+        let bytes = [1, 2, 3, 4, 5];
+        let mut deserializer = Deserializer::new(SliceReader::new(&bytes));
+        deserializer.remaining_len = Some(|reader| reader.remaining().len());
+        assert_eq!(deserializer.max_capacity(), 5);
+        <u16 as de::Deserialize>::deserialize(&mut deserializer).unwrap();
+        assert_eq!(deserializer.max_capacity(), 3);
+    }
+
+    #[test]
+    fn test_reset() {
+        use serde_derive::Deserialize;
+
+        /// Borrows its bytes from the deserializer while it's decoded.
+        #[derive(Debug, PartialEq)]
+        struct Sum(u32);
+
+        impl<'de> de::Deserialize<'de> for Sum {
+            fn deserialize<D: de::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+                struct Visitor;
+                impl de::Visitor<'_> for Visitor {
+                    type Value = Sum;
+                    fn expecting(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+                        f.write_str("bytes")
+                    }
+                    fn visit_bytes<E>(self, v: &[u8]) -> Result<Sum, E> {
+                        Ok(Sum(v.iter().map(|&b| u32::from(b)).sum()))
+                    }
+                }
+                deserializer.deserialize_bytes(Visitor)
+            }
+        }
+
+        #[derive(Deserialize, Debug, PartialEq)]
+        struct Message {
+            sum: Sum,
+            flag: bool,
+        }
+
+        let first = [3, 1, 2, 3, 1];
+        let second = [1, 10, 0];
+        let mut deserializer = Deserializer::new(&first[..]);
+        deserializer.strict_bool();
+        let message = <Message as de::Deserialize>::deserialize(&mut deserializer).unwrap();
+        assert_eq!(message.sum, Sum(6));
+        assert!(message.flag);
+        let capacity = deserializer.scratch.capacity();
+
+        assert!(deserializer.reset(&second[..]).is_empty());
+        let message = <Message as de::Deserialize>::deserialize(&mut deserializer).unwrap();
+        assert_eq!(message.sum, Sum(10));
+        assert!(!message.flag);
+        assert_eq!(deserializer.scratch.capacity(), capacity);
+        assert!(deserializer.config().strict_bool);
+    }
+
+    #[test]
+    fn test_from_reader_padded() {
+        let mut bytes = crate::to_vec(&(7u32, "hello")).unwrap();
+        assert_eq!(bytes.len(), 10);
+        bytes.resize(16, 0);
+        bytes.push(0xaa);
+        let mut reader = &bytes[..];
+        let value: (u32, String) = from_reader_padded(&mut reader, 16).unwrap();
+        assert_eq!(value, (7, "hello".to_string()));
+        assert_eq!(reader, [0xaa]);
+
+        // Already aligned
+        let mut reader = &[1u8, 2, 3, 4][..];
+        assert_eq!(
+            from_reader_padded::<_, u32>(&mut reader, 4).unwrap(),
+            0x04030201
+        );
+        assert!(reader.is_empty());
+
+        bytes[12] = 1;
+        assert!(matches!(
+            from_reader_padded::<_, (u32, String)>(&bytes[..], 16),
+            Err(Error::BadPadding)
+        ));
+        assert!(matches!(
+            from_reader_padded::<_, (u32, String)>(&bytes[..12], 16),
+            Err(Error::Eof)
+        ));
+    }
+
+    #[test]
+    fn test_read_uint() {
+        let read = |bytes: &[u8]| Deserializer::new(bytes).read_varint();
+        assert_eq!(read(&[0]).unwrap(), (0, 1));
+        assert_eq!(read(&[147, 2]).unwrap(), (275, 2));
+        let max = [255, 255, 255, 255, 255, 255, 255, 255, 255, 1];
+        assert_eq!(read(&max).unwrap(), (u64::MAX, 10));
+        // Too many bits, and too many bytes
+        assert!(matches!(
+            read(&[255, 255, 255, 255, 255, 255, 255, 255, 255, 2]),
+            Err(Error::VarintOverflow)
+        ));
+        assert!(matches!(read(&[128; 11]), Err(Error::VarintTooLong)));
+        assert!(matches!(read(&[128]), Err(Error::VarintTruncated)));
+        assert!(matches!(read(&[]), Err(Error::Eof)));
+    }
+
+    #[test]
+    fn test_config() {
+        use alloc::collections::BTreeMap;
+        use serde_derive::Deserialize;
+
+        #[derive(Debug, Deserialize, PartialEq)]
+        struct Settings {
+            verbose: bool,
+            pair: (u8, u8),
+            tags: BTreeMap<u8, u8>,
+        }
+
+        let canonical = [1, 2, 3, 1, 5, 6];
+        let expected = Settings {
+            verbose: true,
+            pair: (2, 3),
+            tags: vec![(5, 6)].into_iter().collect(),
+        };
+        assert_eq!(from_slice::<Settings>(&canonical).unwrap(), expected);
+        let strict = DeserializerConfig::strict();
+        assert_eq!(
+            from_slice_with_config::<Settings>(&canonical, strict).unwrap(),
+            expected
+        );
+        assert_eq!(
+            from_reader_with_config::<_, Settings>(&canonical[..], strict).unwrap(),
+            expected
+        );
+
+        // An overlong length prefix and a bool of 2, each rejected by its own option
+        let sloppy = [2, 2, 3, 0x81, 0, 5, 6];
+        assert!(from_slice::<Settings>(&sloppy).unwrap().verbose);
+        let lengths_only = DeserializerConfig {
+            strict_lengths: true,
+            ..DeserializerConfig::default()
+        };
+        assert!(matches!(
+            from_slice_with_config::<Settings>(&sloppy, lengths_only),
+            Err(Error::NonCanonicalInt)
+        ));
+        assert!(matches!(
+            from_slice_with_config::<Settings>(&sloppy, strict),
+            Err(Error::InvalidBool(2))
+        ));
+
+        let duplicate = [1, 2, 3, 2, 5, 6, 5, 7];
+        assert_eq!(from_slice::<Settings>(&duplicate).unwrap().tags[&5], 7);
+        let mut deserializer = Deserializer::with_config(&duplicate[..], strict);
+        assert_eq!(deserializer.config(), strict);
+        assert!(matches!(
+            <Settings as de::Deserialize>::deserialize(&mut deserializer),
+            Err(Error::DuplicateMapKey)
+        ));
+
+        let payload_nan = 0x7fc0_0001u32.to_le_bytes();
+        assert!(from_slice::<f32>(&payload_nan).unwrap().is_nan());
+        assert!(matches!(
+            from_slice_with_config::<f32>(&payload_nan, strict),
+            Err(Error::NonCanonicalFloat)
+        ));
+    }
+
+    #[test]
+    fn test_deserialize_value() {
+        let mut bytes = crate::to_vec(&1u16).unwrap();
+        bytes.extend(crate::to_vec("two").unwrap());
+        bytes.extend(crate::to_vec(&Some(3u8)).unwrap());
+        let mut deserializer = Deserializer::with_config(&bytes[..], DeserializerConfig::strict());
+        assert_eq!(deserializer.deserialize_value::<u16>().unwrap(), 1);
+        assert_eq!(deserializer.deserialize_value::<String>().unwrap(), "two");
+        assert_eq!(
+            deserializer.deserialize_value::<Option<u8>>().unwrap(),
+            Some(3)
+        );
+        assert!(deserializer.into_inner().is_empty());
+    }
+
+    #[test]
+    fn test_any_unsupported() {
+        use alloc::collections::BTreeMap;
+
+        #[derive(Debug, serde_derive::Deserialize)]
+        #[allow(dead_code)]
+        struct Flattened {
+            id: u8,
+            #[serde(flatten)]
+            extra: BTreeMap<String, u8>,
+        }
+
+        #[derive(Debug, serde_derive::Deserialize)]
+        #[serde(tag = "type")]
+        #[allow(dead_code)]
+        enum Tagged {
+            A { x: u8 },
+        }
+
+        let error = from_slice::<Flattened>(&[1, 0]).unwrap_err();
+        assert!(matches!(error, Error::AnyUnsupported(_)));
+        assert!(error.to_string().contains("#[serde(flatten)]"));
+        let error = from_slice_borrowed::<Tagged>(&[0, 1]).unwrap_err();
+        assert!(error
+            .to_string()
+            .contains("internally or adjacently tagged enum"));
+
+        let error = from_slice::<de::IgnoredAny>(&[0]).unwrap_err();
+        assert!(matches!(error, Error::AnyUnsupported(_)));
+        assert!(error.to_string().contains("IgnoredAny"));
+    }
+
+    #[test]
+    fn test_unit_enum() {
+        /// The `UserRole` of the `user_sessions` benchmark. Its tags are its declaration order.
+        #[derive(Debug, PartialEq, serde_derive::Serialize, serde_derive::Deserialize)]
+        enum UserRole {
+            Admin,
+            User,
+            Guest,
+        }
+
+        let cases = [
+            (UserRole::Admin, [0]),
+            (UserRole::User, [1]),
+            (UserRole::Guest, [2]),
+        ];
+        for (role, bytes) in cases {
+            assert_eq!(crate::to_vec(&role).unwrap(), bytes);
+            assert_eq!(from_slice::<UserRole>(&bytes).unwrap(), role);
+            assert_eq!(from_slice_borrowed::<UserRole>(&bytes).unwrap(), role);
+        }
+
+        // serde's derived visitor rejects unknown tags.
+        for bytes in [&[3][..], &[5], &[0x80, 0x01]] {
+            assert!(matches!(
+                from_slice::<UserRole>(bytes),
+                Err(Error::Message(message)) if message.contains("variant index")
+            ));
+        }
+    }
+
+    #[test]
+    fn test_read_variant_index() {
+        let bytes = crate::to_vec(&Uint(300)).unwrap();
+        let mut deserializer = Deserializer::new(&bytes[..]);
+        assert_eq!(deserializer.read_variant_index().unwrap(), 300);
+
+        #[derive(Debug, PartialEq, serde_derive::Deserialize)]
+        enum Shape {
+            Point,
+            Circle(u8),
+        }
+        let mut deserializer = Deserializer::new(&[1, 5, 0][..]);
+        assert_eq!(deserializer.read_variant_index().unwrap(), 1);
+        assert_eq!(deserializer.deserialize_value::<u8>().unwrap(), 5);
+        assert_eq!(
+            deserializer.deserialize_value::<Shape>().unwrap(),
+            Shape::Point
+        );
+
+        let too_large = crate::to_vec(&Uint(u64::from(u32::MAX) + 1)).unwrap();
+        let error = Deserializer::new(&too_large[..])
+            .read_variant_index()
+            .unwrap_err();
+        let derived = from_slice::<Shape>(&too_large).unwrap_err();
+        assert_eq!(error.to_string(), derived.to_string());
+        let max = crate::to_vec(&Uint(u32::MAX.into())).unwrap();
+        assert_eq!(
+            Deserializer::new(&max[..]).read_variant_index().unwrap(),
+            u32::MAX
+        );
+    }
+
+    #[test]
+    fn test_strict_floats() {
+        // A signaling NaN: quiet bit clear, nonzero payload.
+        let signaling32 = f32::from_bits(0x7fa0_0001);
+        let signaling64 = f64::from_bits(0x7ff4_0000_0000_0001);
+        let bytes = crate::to_vec(&(signaling32, signaling64)).unwrap();
+        assert_eq!(bytes[..4], 0x7fa0_0001u32.to_le_bytes());
+        assert_eq!(bytes[4..], 0x7ff4_0000_0000_0001u64.to_le_bytes());
+
+        // Bit for bit by default.
+        let (a, b) = from_slice::<(f32, f64)>(&bytes).unwrap();
+        assert_eq!(
+            (a.to_bits(), b.to_bits()),
+            (0x7fa0_0001, 0x7ff4_0000_0000_0001)
+        );
+
+        let strict = DeserializerConfig {
+            strict_floats: true,
+            ..DeserializerConfig::default()
+        };
+        assert!(matches!(
+            from_slice_with_config::<f32>(&bytes[..4], strict),
+            Err(Error::NonCanonicalFloat)
+        ));
+        assert!(matches!(
+            from_slice_with_config::<f64>(&bytes[4..], strict),
+            Err(Error::NonCanonicalFloat)
+        ));
+        let negative_nan = (-f32::from_bits(CANONICAL_NAN_F32)).to_le_bytes();
+        assert!(from_slice_with_config::<f32>(&negative_nan, strict).is_err());
+
+        // Canonicalized on the way out, then accepted.
+        let config = crate::SerializerConfig {
+            canonical_nan: true,
+            ..crate::SerializerConfig::default()
+        };
+        let canonical = crate::to_vec_with_config(&(signaling32, signaling64), config).unwrap();
+        assert_eq!(canonical[..4], CANONICAL_NAN_F32.to_le_bytes());
+        assert_eq!(canonical[4..], CANONICAL_NAN_F64.to_le_bytes());
+        let (a, b) = from_slice_with_config::<(f32, f64)>(&canonical, strict).unwrap();
+        assert_eq!(
+            (a.to_bits(), b.to_bits()),
+            (CANONICAL_NAN_F32, CANONICAL_NAN_F64)
+        );
+
+        let ordinary = (1.5f32, -0.0f64, f32::INFINITY);
+        let bytes = crate::to_vec_with_config(&ordinary, config).unwrap();
+        assert_eq!(bytes, crate::to_vec(&ordinary).unwrap());
+        let decoded = from_slice_with_config::<(f32, f64, f32)>(&bytes, strict).unwrap();
+        assert_eq!(decoded.1.to_bits(), (-0.0f64).to_bits());
+        assert_eq!(decoded, ordinary);
+    }
+
+    #[test]
+    fn test_seq_truncated() {
+        // Three elements declared, two present.
+        let bytes = [3, 1, 0, 0, 0, 2, 0, 0, 0];
+        assert!(matches!(
+            from_slice::<Vec<u32>>(&bytes),
+            Err(Error::SeqTruncated {
+                expected: 3,
+                got: 2
+            })
+        ));
+        assert!(matches!(
+            from_slice_borrowed::<Vec<u32>>(&bytes),
+            Err(Error::SeqTruncated {
+                expected: 3,
+                got: 2
+            })
+        ));
+        assert!(matches!(
+            from_reader::<_, Vec<u32>>(&bytes[..]),
+            Err(Error::SeqTruncated {
+                expected: 3,
+                got: 2
+            })
+        ));
+
+        // The innermost sequence is reported.
+        let bytes = [2, 1, 7, 2, 8];
+        assert!(matches!(
+            from_slice::<Vec<Vec<u8>>>(&bytes),
+            Err(Error::SeqTruncated {
+                expected: 2,
+                got: 1
+            })
+        ));
+
+        // A truncated length is reported as such, and tuples are plain Eof.
+        assert!(matches!(
+            from_slice::<Vec<u32>>(&[0x80]),
+            Err(Error::VarintTruncated)
+        ));
+        assert!(matches!(
+            from_slice::<(u32, u32)>(&[1, 0, 0, 0]),
+            Err(Error::Eof)
+        ));
+        assert!(matches!(try_from_slice::<Vec<u32>>(&bytes[..5]), Ok(None)));
+    }
+
+    #[test]
+    fn test_slice_reader() {
+        let mut bytes = crate::to_vec(&(7u32, "seven", Some(-7i64))).unwrap();
+        let value_len = bytes.len();
+        bytes.push(0xff);
+
+        let mut deserializer = Deserializer::new(SliceReader::new(&bytes));
+        let value = <(u32, String, Option<i64>) as de::Deserialize>::deserialize(&mut deserializer);
+        assert_eq!(value.unwrap(), (7, "seven".to_string(), Some(-7)));
+        let mut reader = deserializer.into_inner();
+        assert_eq!(reader.position(), value_len);
+        assert_eq!(reader.remaining(), [0xff]);
+
+        // A read that doesn't fit takes nothing, whether it's exact or not.
+        let mut buf = [0; 2];
+        assert_eq!(
+            reader.read_exact(&mut buf).unwrap_err().kind(),
+            io::ErrorKind::UnexpectedEof
+        );
+        assert_eq!(reader.position(), value_len);
+        assert_eq!(reader.read(&mut buf).unwrap(), 1);
+        assert_eq!(buf, [0xff, 0]);
+        assert_eq!(reader.read(&mut buf).unwrap(), 0);
+        assert_eq!(reader.position(), bytes.len());
+
+        // Same results as reading through a Cursor, for whole, cut short and malformed inputs.
+        let inputs: [&[u8]; 5] = [
+            &bytes,
+            &bytes[..value_len - 3],
+            &[],
+            &[1, 0, 0, 0, 9],
+            &[0; 4],
+        ];
+        for input in inputs.iter() {
+            type Value = (u32, String, Option<i64>);
+            let via_slice = <Value as de::Deserialize>::deserialize(&mut Deserializer::new(
+                SliceReader::new(input),
+            ));
+            let via_cursor =
+                <Value as de::Deserialize>::deserialize(&mut Deserializer::new(Cursor::new(input)));
+            assert_eq!(format!("{:?}", via_slice), format!("{:?}", via_cursor));
+        }
+    }
+
+    #[test]
+    fn test_new_limited() {
+        let bytes: Vec<u8> = (0..20).collect();
+        let mut reader = &bytes[..];
+        let mut deserializer = Deserializer::new_limited(&mut reader, 10);
+        let value = <(u32, u32) as de::Deserialize>::deserialize(&mut deserializer).unwrap();
+        assert_eq!(value, (0x03020100, 0x07060504));
+        assert!(matches!(
+            <u32 as de::Deserialize>::deserialize(&mut deserializer),
+            Err(Error::LimitExceeded {
+                length: 12,
+                limit: 10
+            })
+        ));
+        // Nothing past the limit was read, and the last two bytes of the frame are still there.
+        assert_eq!(reader.len(), 12);
+        let mut deserializer = Deserializer::new_limited(&mut reader, 2);
+        assert_eq!(
+            <u16 as de::Deserialize>::deserialize(&mut deserializer).unwrap(),
+            0x0908
+        );
+        assert_eq!(reader, &bytes[10..]);
+
+        // A length prefix past the limit fails before its contents are read.
+        let bytes = crate::to_vec(&vec![7u8; 100]).unwrap();
+        let mut deserializer = Deserializer::new_limited(&bytes[..], 50);
+        assert!(matches!(
+            <Vec<u8> as de::Deserialize>::deserialize(&mut deserializer),
+            Err(Error::LimitExceeded { limit: 50, .. })
+        ));
+        let mut deserializer = Deserializer::new_limited(&bytes[..], bytes.len());
+        assert_eq!(
+            <Vec<u8> as de::Deserialize>::deserialize(&mut deserializer).unwrap(),
+            [7; 100]
+        );
     }
 
     #[test]
+    fn test_skip() {
+        use alloc::{boxed::Box, collections::BTreeMap};
+        use serde_derive::{Deserialize, Serialize};
+
+        #[derive(Serialize)]
+        enum Shape {
+            Circle(f64),
+            Polygon(Vec<(i32, i32)>),
+        }
+
+        #[derive(Serialize)]
+        struct Extension {
+            id: Uint,
+            label: Option<String>,
+            shapes: Vec<Shape>,
+            weights: BTreeMap<u8, f32>,
+            digest: crate::Data<4>,
+        }
+
+        #[derive(Serialize)]
+        struct V2 {
+            name: String,
+            extension: Extension,
+            sentinel: u16,
+        }
+
+        #[derive(Deserialize)]
+        struct V1 {
+            name: String,
+        }
+
+        let bytes = crate::to_vec(&V2 {
+            name: "v2".to_string(),
+            extension: Extension {
+                id: Uint(300),
+                label: Some("ext".to_string()),
+                shapes: vec![Shape::Polygon(vec![(0, 0), (1, -1)]), Shape::Circle(0.5)],
+                weights: vec![(1, 0.25), (2, 0.75)].into_iter().collect(),
+                digest: crate::Data([9; 4]),
+            },
+            sentinel: 0xbeef,
+        })
+        .unwrap();
+
+        let extension = Layout::Struct(vec![
+            Layout::Uint,
+            Layout::Optional(Box::new(Layout::Str)),
+            Layout::List(Box::new(Layout::Union(vec![
+                (0, Layout::F64),
+                (
+                    1,
+                    Layout::List(Box::new(Layout::Struct(vec![Layout::I32, Layout::I32]))),
+                ),
+            ]))),
+            Layout::Map(Box::new(Layout::U8), Box::new(Layout::F32)),
+            Layout::FixedData(4),
+        ]);
+        let mut deserializer = Deserializer::new(&bytes[..]);
+        let v1 = <V1 as de::Deserialize>::deserialize(&mut deserializer).unwrap();
+        assert_eq!(v1.name, "v2");
+        deserializer.skip(&extension).unwrap();
+        let sentinel = <u16 as de::Deserialize>::deserialize(&mut deserializer).unwrap();
+        assert_eq!(sentinel, 0xbeef);
+        assert!(deserializer.into_inner().is_empty());
+
+        // Running out of input while skipping
+        let mut deserializer = Deserializer::new(&bytes[3..10]);
+        assert!(matches!(deserializer.skip(&extension), Err(Error::Eof)));
+        // A tag that isn't in the union
+        let mut deserializer = Deserializer::new(&[1, 7][..]);
+        let union = Layout::Union(vec![(0, Layout::Void)]);
+        assert!(matches!(
+            deserializer.skip(&union),
+            Err(Error::UnknownUnionTag(1))
+        ));
+        // A huge length of elements that take up no bytes
+        let mut deserializer = Deserializer::new(&[0xff, 0xff, 0xff, 0xff, 0x0f][..]);
+        let voids = Layout::List(Box::new(Layout::Struct(vec![
+            Layout::Void,
+            Layout::FixedData(0),
+        ])));
+        assert!(matches!(
+            deserializer.skip(&voids),
+            Err(Error::ZeroWidthElements)
+        ));
+        let mut deserializer = Deserializer::new(&[0][..]);
+        deserializer.skip(&voids).unwrap();
+    }
+
+    #[test]
+    fn test_fixed_arrays() {
+        // Arrays are BARE fixed-length arrays, with no length prefix.
+        let bytes = [1, 0, 2, 0, 3, 0, 4, 0];
+        assert_eq!(crate::to_vec(&[1u16, 2, 3, 4]).unwrap(), bytes);
+        assert_eq!(from_slice::<[u16; 4]>(&bytes).unwrap(), [1, 2, 3, 4]);
+        assert_eq!(
+            from_slice_borrowed::<[u16; 4]>(&bytes).unwrap(),
+            [1, 2, 3, 4]
+        );
+
+        // Exactly 8 bytes are read, whatever follows them.
+        let mut reader = &[4, 0, 1, 0, 2, 0, 3, 0, 4, 0][..];
+        assert_eq!(
+            from_reader::<_, [u16; 4]>(&mut reader).unwrap(),
+            [4, 1, 2, 3]
+        );
+        assert_eq!(reader, [4, 0]);
+
+        assert!(matches!(
+            from_slice::<[u16; 4]>(&bytes[..7]),
+            Err(Error::Eof)
+        ));
+        assert!(matches!(
+            from_slice_borrowed::<[u16; 4]>(&bytes[..6]),
+            Err(Error::Eof)
+        ));
+        assert!(matches!(
+            from_reader::<_, [u16; 4]>(&bytes[..2]),
+            Err(Error::Eof)
+        ));
+    }
+
+    #[test]
+    fn test_strict_tuples() {
+        use core::fmt;
+
+        /// Declares three elements but only reads two of them.
+        #[derive(Debug, PartialEq)]
+        struct Short(u8, u8);
+
+        /// Declares usize::MAX elements but only reads two of them.
+        #[derive(Debug, PartialEq)]
+        struct Long(Short);
+
+        struct ShortVisitor;
+
+        impl<'de> de::Visitor<'de> for ShortVisitor {
+            type Value = Short;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter.write_str("two bytes")
+            }
+
+            fn visit_seq<A: de::SeqAccess<'de>>(self, mut seq: A) -> Result<Short, A::Error> {
+                let a = seq.next_element()?.unwrap_or_default();
+                let b = seq.next_element()?.unwrap_or_default();
+                Ok(Short(a, b))
+            }
+        }
+
+        impl<'de> de::Deserialize<'de> for Short {
+            fn deserialize<D: de::Deserializer<'de>>(deserializer: D) -> Result<Short, D::Error> {
+                deserializer.deserialize_tuple(3, ShortVisitor)
+            }
+        }
+
+        impl<'de> de::Deserialize<'de> for Long {
+            fn deserialize<D: de::Deserializer<'de>>(deserializer: D) -> Result<Long, D::Error> {
+                deserializer
+                    .deserialize_tuple(usize::MAX, ShortVisitor)
+                    .map(Long)
+            }
+        }
+
+        let mut deserializer = Deserializer::new(&[1, 2, 3][..]);
+        deserializer.strict_tuples();
+        assert!(matches!(
+            <Short as de::Deserialize>::deserialize(&mut deserializer),
+            Err(Error::Message(_))
+        ));
+        assert_eq!(deserializer.into_inner(), [3]);
+        assert_eq!(
+            from_reader::<_, Short>(&[1, 2, 3][..]).unwrap(),
+            Short(1, 2)
+        );
+
+        let mut deserializer = Deserializer::new(&[1, 0, 0, 0, 2, 0, 0, 0][..]);
+        deserializer.strict_tuples();
+        assert!(matches!(
+            <[u32; 3] as de::Deserialize>::deserialize(&mut deserializer),
+            Err(Error::Eof)
+        ));
+        let mut deserializer = Deserializer::new(&[1, 0, 0, 0, 2, 0, 0, 0, 3, 0, 0, 0][..]);
+        deserializer.strict_tuples();
+        assert_eq!(
+            <[u32; 3] as de::Deserialize>::deserialize(&mut deserializer).unwrap(),
+            [1, 2, 3]
+        );
+        // Uint stops reading its tuple at the last byte.
+        assert_eq!(
+            <Uint as de::Deserialize>::deserialize(&mut deserializer.nested(&[0x80, 0x01][..]))
+                .unwrap(),
+            Uint(128)
+        );
+        // Other tuples are sequences whatever their length.
+        assert_eq!(
+            from_reader::<_, Long>(&[1, 2, 3][..]).unwrap(),
+            Long(Short(1, 2))
+        );
+        assert_eq!(from_slice::<Long>(&[1, 2, 3]).unwrap(), Long(Short(1, 2)));
+    }
+
+    #[test]
+    fn test_strict_lengths() {
+        let read = |bytes: &[u8], strict: bool| {
+            let mut deserializer = Deserializer::new(bytes);
+            if strict {
+                deserializer.strict_lengths();
+            }
+            <String as de::Deserialize>::deserialize(&mut deserializer)
+        };
+        let bytes = [0x85, 0x00, b'h', b'e', b'l', b'l', b'o'];
+        assert_eq!(read(&bytes, false).unwrap(), "hello");
+        assert!(matches!(read(&bytes, true), Err(Error::NonCanonicalInt)));
+        assert_eq!(read(&[0], true).unwrap(), "");
+        assert_eq!(
+            read(&[5, b'h', b'e', b'l', b'l', b'o'], true).unwrap(),
+            "hello"
+        );
+
+        let mut deserializer = Deserializer::new(&[0x83, 0x80, 0x00, 1, 2, 3][..]);
+        deserializer.strict_lengths();
+        assert!(matches!(
+            <Vec<u8> as de::Deserialize>::deserialize(&mut deserializer),
+            Err(Error::NonCanonicalInt)
+        ));
+    }
+
+    #[test]
+    fn test_reject_duplicate_keys() {
+        use alloc::collections::BTreeMap;
+        let read = |bytes: &[u8], reject: bool| {
+            let mut deserializer = Deserializer::new(bytes);
+            if reject {
+                deserializer.reject_duplicate_keys();
+            }
+            <BTreeMap<String, u8> as de::Deserialize>::deserialize(&mut deserializer)
+        };
+        let duplicate = [2, 1, b'a', 1, 1, b'a', 2];
+        assert_eq!(read(&duplicate, false).unwrap().len(), 1);
+        assert!(matches!(
+            read(&duplicate, true),
+            Err(Error::DuplicateMapKey)
+        ));
+        let distinct = [2, 1, b'a', 1, 1, b'b', 2];
+        assert_eq!(read(&distinct, true).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_strict_bool() {
+        let read = |bytes: &[u8]| {
+            let mut deserializer = Deserializer::new(bytes);
+            deserializer.strict_bool();
+            <bool as de::Deserialize>::deserialize(&mut deserializer)
+        };
+        assert!(!read(&[0]).unwrap());
+        assert!(read(&[1]).unwrap());
+        assert!(matches!(read(&[2]), Err(Error::InvalidBool(2))));
+        assert!(matches!(read(&[0xff]), Err(Error::InvalidBool(0xff))));
+    }
+
+    #[test]
+    #[allow(deprecated)]
     fn test_signed() {
         assert_eq!(1i8, from_slice(&[1]).unwrap());
         assert_eq!(513i16, from_slice(&[1, 2]).unwrap());
@@ -579,6 +2572,7 @@ mod test {
             578437695752307201i64,
             from_slice(&[1, 2, 3, 4, 5, 6, 7, 8]).unwrap()
         );
+        #[cfg(feature = "i128")]
         serde::serde_if_integer128! {
             assert_eq!(
                 21345817372864405881847059188222722561i128,
@@ -588,6 +2582,7 @@ mod test {
     }
 
     #[test]
+    #[allow(deprecated)]
     fn test_unsigned() {
         assert_eq!(1u8, from_slice(&[1]).unwrap());
         assert_eq!(513u16, from_slice(&[1, 2]).unwrap());
@@ -596,6 +2591,7 @@ mod test {
             578437695752307201u64,
             from_slice(&[1, 2, 3, 4, 5, 6, 7, 8]).unwrap()
         );
+        #[cfg(feature = "i128")]
         serde::serde_if_integer128! {
             assert_eq!(
                 21345817372864405881847059188222722561u128,
@@ -604,6 +2600,36 @@ mod test {
         }
     }
 
+    #[test]
+    fn test_nonzero() {
+        use core::num::{NonZeroI64, NonZeroU32};
+
+        let id = NonZeroU32::new(67305985).unwrap();
+        assert_eq!(crate::to_vec(&id).unwrap(), [1, 2, 3, 4]);
+        assert_eq!(id, from_slice(&[1, 2, 3, 4]).unwrap());
+        let delta = NonZeroI64::new(-2).unwrap();
+        assert_eq!(
+            crate::to_vec(&delta).unwrap(),
+            crate::to_vec(&-2i64).unwrap()
+        );
+        assert_eq!(delta, from_slice(&crate::to_vec(&-2i64).unwrap()).unwrap());
+
+        // Option<NonZeroU32> is the same size as u32 in memory, but still has a tag on the wire.
+        assert_eq!(crate::to_vec(&Some(id)).unwrap(), [1, 1, 2, 3, 4]);
+        assert_eq!(Some(id), from_slice(&[1, 1, 2, 3, 4]).unwrap());
+        assert_eq!(None::<NonZeroU32>, from_slice(&[0]).unwrap());
+
+        match from_slice::<NonZeroU32>(&[0; 4]) {
+            Err(Error::Message(message)) => assert_eq!(
+                message,
+                "invalid value: integer `0`, expected a nonzero u32"
+            ),
+            other => panic!("unexpected result {:?}", other),
+        }
+        assert!(from_slice::<NonZeroI64>(&[0; 8]).is_err());
+        assert!(from_slice::<Option<NonZeroU32>>(&[1, 0, 0, 0, 0]).is_err());
+    }
+
     #[test]
     fn test_float() {
         assert_eq!(1.0f32, from_slice(&1.0f32.to_le_bytes()).unwrap());
@@ -628,6 +2654,30 @@ mod test {
         );
     }
 
+    #[test]
+    fn test_char() {
+        for c in ['\0', 'a', '\u{d7ff}', '\u{e000}', '\u{10ffff}'] {
+            let bytes = crate::to_vec(&c).unwrap();
+            assert_eq!(bytes, u32::from(c).to_le_bytes());
+            assert_eq!(from_slice::<char>(&bytes).unwrap(), c);
+            assert_eq!(from_slice_borrowed::<char>(&bytes).unwrap(), c);
+        }
+        for codepoint in [0xd800, 0xdbff, 0xdc00, 0xdfff, 0x110000, u32::MAX] {
+            let bytes = codepoint.to_le_bytes();
+            match from_slice::<char>(&bytes) {
+                Err(e @ Error::InvalidChar(got)) => {
+                    assert_eq!(got, codepoint);
+                    assert!(e.to_string().contains(&format!("{:#x}", codepoint)));
+                }
+                other => panic!("unexpected result {:?}", other),
+            }
+            assert!(matches!(
+                from_slice_borrowed::<char>(&bytes),
+                Err(Error::InvalidChar(got)) if got == codepoint
+            ));
+        }
+    }
+
     #[test]
     fn test_string() {
         assert_eq!(
@@ -636,6 +2686,28 @@ mod test {
         )
     }
 
+    #[test]
+    fn test_invalid_utf8() {
+        let bytes = [5, b'a', b'b', b'c', 0xff, b'd'];
+        assert!(matches!(
+            from_slice::<String>(&bytes),
+            Err(Error::InvalidUtf8 { valid_up_to: 3 })
+        ));
+        assert!(matches!(
+            from_slice_borrowed::<&str>(&bytes),
+            Err(Error::InvalidUtf8 { valid_up_to: 3 })
+        ));
+        // Checked in the deserializer's scratch buffer, before the visitor sees it.
+        assert!(matches!(
+            <&str as de::Deserialize>::deserialize(&mut Deserializer::new(&bytes[..])),
+            Err(Error::InvalidUtf8 { valid_up_to: 3 })
+        ));
+        // A multi-byte sequence cut short is invalid from its first byte.
+        let error = from_slice::<String>(&[4, 0xc3, 0xa9, 0xe2, 0x82]).unwrap_err();
+        assert!(matches!(error, Error::InvalidUtf8 { valid_up_to: 2 }));
+        assert_eq!(error.to_string(), "invalid utf-8 in string after byte 2");
+    }
+
     #[test]
     fn test_data() {
         assert_eq!(
@@ -653,6 +2725,31 @@ mod test {
         );
     }
 
+    #[test]
+    fn test_nested_optional() {
+        let cases: [(Option<Option<u32>>, &[u8]); 3] = [
+            (None, &[0]),
+            (Some(None), &[1, 0]),
+            (Some(Some(5)), &[1, 1, 5, 0, 0, 0]),
+        ];
+        for (value, bytes) in cases {
+            assert_eq!(crate::to_vec(&value).unwrap(), bytes);
+            assert_eq!(from_slice::<Option<Option<u32>>>(bytes).unwrap(), value);
+            assert_eq!(
+                from_slice_borrowed::<Option<Option<u32>>>(bytes).unwrap(),
+                value
+            );
+        }
+
+        let value = Some(Some(None::<u8>));
+        let bytes = crate::to_vec(&value).unwrap();
+        assert_eq!(bytes, [1, 1, 0]);
+        assert_eq!(
+            from_slice::<Option<Option<Option<u8>>>>(&bytes).unwrap(),
+            value
+        );
+    }
+
     #[test]
     fn test_slice() {
         assert_eq!(
@@ -660,4 +2757,511 @@ mod test {
             &*from_slice::<Box<[u8]>>(&[4, 0, 0, 0, 0]).unwrap()
         );
     }
+
+    #[test]
+    #[cfg(all(feature = "indexmap", feature = "std"))]
+    fn test_indexmap() {
+        use indexmap::IndexMap;
+
+        let map: IndexMap<String, u8> = [("zebra", 1), ("apple", 2), ("mango", 3)]
+            .iter()
+            .map(|&(k, v)| (k.to_string(), v))
+            .collect();
+        let bytes = crate::to_vec(&map).unwrap();
+        assert_eq!(bytes, b"\x03\x05zebra\x01\x05apple\x02\x05mango\x03");
+        let decoded = from_slice::<IndexMap<String, u8>>(&bytes).unwrap();
+        assert!(decoded.keys().eq(map.keys()));
+        assert_eq!(crate::to_vec(&decoded).unwrap(), bytes);
+        let decoded = from_reader::<_, IndexMap<String, u8>>(&bytes[..]).unwrap();
+        assert!(decoded.keys().eq(map.keys()));
+
+        // The second "b" replaces the first's value but keeps its place.
+        let duplicate = b"\x03\x01b\x01\x01a\x02\x01b\x03";
+        let decoded = from_slice::<IndexMap<String, u8>>(duplicate).unwrap();
+        assert_eq!(
+            decoded.into_iter().collect::<Vec<_>>(),
+            [("b".to_string(), 3), ("a".to_string(), 2)]
+        );
+        let config = DeserializerConfig {
+            reject_duplicate_keys: true,
+            ..DeserializerConfig::default()
+        };
+        assert!(matches!(
+            from_slice_with_config::<IndexMap<String, u8>>(duplicate, config),
+            Err(Error::DuplicateMapKey)
+        ));
+        assert!(from_slice_with_config::<IndexMap<String, u8>>(&bytes, config).is_ok());
+    }
+
+    #[test]
+    #[cfg(feature = "smallvec")]
+    fn test_smallvec() {
+        use smallvec::SmallVec;
+
+        let small: SmallVec<[u32; 4]> = (1..=3).collect();
+        let bytes = crate::to_vec(&small).unwrap();
+        assert_eq!(bytes, crate::to_vec(&[1u32, 2, 3][..]).unwrap());
+        let decoded = from_slice::<SmallVec<[u32; 4]>>(&bytes).unwrap();
+        assert_eq!(decoded, small);
+        assert!(!decoded.spilled());
+        let decoded = from_reader::<_, SmallVec<[u32; 4]>>(&bytes[..]).unwrap();
+        assert!(!decoded.spilled());
+
+        let large: SmallVec<[u32; 4]> = (1..=5).collect();
+        let bytes = crate::to_vec(&large).unwrap();
+        let decoded = from_slice_borrowed::<SmallVec<[u32; 4]>>(&bytes).unwrap();
+        assert_eq!(decoded, large);
+        assert!(decoded.spilled());
+
+        // A huge length with little behind it fails without reserving space for it.
+        assert!(matches!(
+            from_slice::<SmallVec<[u32; 4]>>(&[0xff, 0xff, 0xff, 0xff, 0x0f, 1, 0, 0, 0]),
+            Err(Error::SeqTruncated {
+                expected: 0xffff_ffff,
+                got: 1
+            })
+        ));
+    }
+
+    #[test]
+    fn test_smart_pointers() {
+        use alloc::{boxed::Box, rc::Rc, sync::Arc};
+
+        let string = crate::to_vec("hello").unwrap();
+        let data = crate::to_vec(&[1u8, 2, 3][..]).unwrap();
+        let list = crate::to_vec(&["a", "b"][..]).unwrap();
+
+        let value = from_slice::<Box<str>>(&string).unwrap();
+        assert_eq!(&*value, "hello");
+        assert_eq!(crate::to_vec(&value).unwrap(), string);
+        let value = from_slice::<Rc<str>>(&string).unwrap();
+        assert_eq!(&*value, "hello");
+        assert_eq!(crate::to_vec(&value).unwrap(), string);
+        let value = from_slice::<Arc<str>>(&string).unwrap();
+        assert_eq!(&*value, "hello");
+        assert_eq!(crate::to_vec(&value).unwrap(), string);
+
+        let value = from_slice::<Box<[u8]>>(&data).unwrap();
+        assert_eq!(&*value, [1, 2, 3]);
+        assert_eq!(crate::to_vec(&value).unwrap(), data);
+        let value = from_slice::<Rc<[u8]>>(&data).unwrap();
+        assert_eq!(&*value, [1, 2, 3]);
+        assert_eq!(crate::to_vec(&value).unwrap(), data);
+        let value = from_slice::<Arc<[u8]>>(&data).unwrap();
+        assert_eq!(&*value, [1, 2, 3]);
+        assert_eq!(crate::to_vec(&value).unwrap(), data);
+
+        let value = from_slice::<Arc<[Box<str>]>>(&list).unwrap();
+        assert_eq!(value, ["a".into(), "b".into()].into());
+        assert_eq!(crate::to_vec(&value).unwrap(), list);
+        let value = from_slice::<Rc<Option<Arc<u16>>>>(&[1, 2, 1]).unwrap();
+        assert_eq!(value, Rc::new(Some(Arc::new(258))));
+        assert_eq!(
+            from_slice_borrowed::<Arc<str>>(&string).unwrap(),
+            "hello".into()
+        );
+    }
+
+    #[test]
+    fn test_track_fields() {
+        #[derive(Debug, serde_derive::Deserialize)]
+        #[allow(dead_code)]
+        struct Optionals {
+            a: Option<u32>,
+            b: Option<u32>,
+            c: Option<String>,
+            d: Option<u8>,
+        }
+
+        // c is present but its string is cut short
+        let bytes: &[u8] = &[1, 1, 0, 0, 0, 0, 1, 5, b'h', b'i'];
+        let mut deserializer = Deserializer::new(bytes);
+        deserializer.track_fields();
+        assert!(<Optionals as de::Deserialize>::deserialize(&mut deserializer).is_err());
+        assert_eq!(
+            deserializer.fields(),
+            &[
+                FieldRecord {
+                    name: "a",
+                    present: Some(true)
+                },
+                FieldRecord {
+                    name: "b",
+                    present: Some(false)
+                },
+                FieldRecord {
+                    name: "c",
+                    present: Some(true)
+                },
+            ]
+        );
+
+        let mut deserializer = Deserializer::new(bytes);
+        assert!(<Optionals as de::Deserialize>::deserialize(&mut deserializer).is_err());
+        assert!(deserializer.fields().is_empty());
+    }
+
+    #[test]
+    fn test_reader_iter() {
+        #[derive(Debug, PartialEq, serde_derive::Serialize, serde_derive::Deserialize)]
+        struct Record {
+            id: u8,
+            name: String,
+        }
+
+        let records = [
+            Record {
+                id: 1,
+                name: "one".to_string(),
+            },
+            Record {
+                id: 2,
+                name: "".to_string(),
+            },
+            Record {
+                id: 3,
+                name: "three".to_string(),
+            },
+        ];
+        let mut bytes = Vec::new();
+        for record in &records {
+            crate::to_writer(&mut bytes, record).unwrap();
+        }
+
+        let got = from_reader_iter::<_, Record>(&bytes[..])
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(got, records);
+
+        let mut iter = from_reader_iter::<_, Record>(&bytes[..bytes.len() - 2]);
+        assert_eq!(iter.next().unwrap().unwrap(), records[0]);
+        assert_eq!(iter.next().unwrap().unwrap(), records[1]);
+        assert!(matches!(iter.next(), Some(Err(Error::Eof))));
+        assert!(iter.next().is_none());
+
+        assert!(from_reader_iter::<_, Record>(&[][..]).next().is_none());
+    }
+
+    #[test]
+    fn test_framed() {
+        let mut bytes = Vec::new();
+        crate::to_writer_framed(&mut bytes, "x".repeat(200).as_str()).unwrap();
+        crate::to_writer_framed(&mut bytes, &()).unwrap();
+        crate::to_writer_framed(&mut bytes, &7u16).unwrap();
+        assert_eq!(&bytes[..3], &[202, 1, 200]);
+        assert_eq!(&bytes[bytes.len() - 4..], &[0, 2, 7, 0]);
+
+        let mut reader = &bytes[..];
+        assert_eq!(
+            from_reader_framed::<_, String>(&mut reader).unwrap(),
+            "x".repeat(200)
+        );
+        from_reader_framed::<_, ()>(&mut reader).unwrap();
+        assert_eq!(from_reader_framed::<_, u16>(&mut reader).unwrap(), 7);
+        assert!(reader.is_empty());
+
+        assert!(matches!(
+            from_reader_framed::<_, u8>(&[2, 7, 0][..]),
+            Err(Error::TrailingData)
+        ));
+        assert!(matches!(
+            from_reader_framed::<_, u16>(&[2, 7][..]),
+            Err(Error::Eof)
+        ));
+    }
+
+    #[test]
+    fn test_sized() {
+        let bytes = [3, 0, 0, 0, 4, 0, 0, 0, 9];
+        let mut reader = &bytes[..];
+        assert_eq!(
+            from_reader_sized::<_, (u32, u32)>(&mut reader, 8).unwrap(),
+            (3, 4)
+        );
+        assert_eq!(reader, [9]);
+
+        // The value ends before the body does.
+        let mut reader = &bytes[..];
+        assert!(matches!(
+            from_reader_sized::<_, u32>(&mut reader, 8),
+            Err(Error::TrailingData)
+        ));
+        assert_eq!(reader.len(), 5);
+
+        // The value runs past the body, and nothing beyond it is read.
+        let mut reader = &bytes[..];
+        assert!(matches!(
+            from_reader_sized::<_, (u32, u32)>(&mut reader, 6),
+            Err(Error::Eof)
+        ));
+        assert_eq!(reader, [0, 0, 9]);
+    }
+
+    #[test]
+    fn test_versioned() {
+        #[derive(serde_derive::Deserialize)]
+        struct UserV1 {
+            name: String,
+        }
+        #[derive(Debug, PartialEq, serde_derive::Serialize, serde_derive::Deserialize)]
+        struct User {
+            name: String,
+            age: u8,
+        }
+        fn decode(bytes: &[u8]) -> Result<User, Error> {
+            from_slice_versioned(bytes, |version, body| match version {
+                1 => from_slice::<UserV1>(body).map(|v1| User {
+                    name: v1.name,
+                    age: 0,
+                }),
+                2 => from_slice(body),
+                _ => Err(Error::Message(format!("unknown version {}", version))),
+            })
+        }
+
+        let user = User {
+            name: "ann".to_string(),
+            age: 30,
+        };
+        let bytes = crate::to_vec_versioned(2, &user).unwrap();
+        assert_eq!(bytes, [2, 3, b'a', b'n', b'n', 30]);
+        assert_eq!(decode(&bytes).unwrap(), user);
+
+        let bytes = crate::to_vec_versioned(1, "bob").unwrap();
+        assert_eq!(
+            decode(&bytes).unwrap(),
+            User {
+                name: "bob".to_string(),
+                age: 0
+            }
+        );
+
+        let bytes = crate::to_vec_versioned(300, &user).unwrap();
+        assert!(matches!(decode(&bytes), Err(Error::Message(_))));
+    }
+
+    #[test]
+    fn test_length_prefixed_variants() {
+        #[derive(Debug, PartialEq, serde_derive::Serialize)]
+        enum NewMessage {
+            Ping,
+            Text(String),
+            Move { x: i8, y: i8 },
+            Resize(u16, u16),
+            Point(Vec<NewMessage>),
+        }
+
+        #[derive(Debug, PartialEq, serde_derive::Deserialize)]
+        enum OldMessage {
+            Ping,
+            Text(String),
+            Move {
+                x: i8,
+                y: i8,
+            },
+            #[serde(other)]
+            Unknown,
+        }
+
+        let messages = vec![
+            NewMessage::Text("hi".to_string()),
+            NewMessage::Resize(640, 480),
+            NewMessage::Point(vec![NewMessage::Ping, NewMessage::Move { x: 1, y: -1 }]),
+            NewMessage::Move { x: 3, y: 4 },
+            NewMessage::Ping,
+        ];
+        let mut bytes = Vec::new();
+        let mut serializer = crate::Serializer::new(&mut bytes);
+        serializer.length_prefixed_variants();
+        serde::Serialize::serialize(&messages, &mut serializer).unwrap();
+        assert_eq!(&bytes[..5], &[5, 1, 3, 2, b'h']);
+        assert_eq!(&bytes[6..12], &[3, 4, 128, 2, 224, 1]);
+
+        let mut deserializer = Deserializer::new(&bytes[..]);
+        deserializer.length_prefixed_variants();
+        let old: Vec<OldMessage> = de::Deserialize::deserialize(&mut deserializer).unwrap();
+        assert_eq!(
+            old,
+            vec![
+                OldMessage::Text("hi".to_string()),
+                OldMessage::Unknown,
+                OldMessage::Unknown,
+                OldMessage::Move { x: 3, y: 4 },
+                OldMessage::Ping,
+            ]
+        );
+        assert!(deserializer.reader.is_empty());
+
+        // The variant's length prefix is a length like any other.
+        let padded = [0, 0x80, 0x00];
+        let mut deserializer = Deserializer::new(&padded[..]);
+        deserializer.length_prefixed_variants();
+        let ping: OldMessage = de::Deserialize::deserialize(&mut deserializer).unwrap();
+        assert_eq!(ping, OldMessage::Ping);
+        let mut deserializer = Deserializer::new(&padded[..]);
+        deserializer.length_prefixed_variants();
+        deserializer.strict_lengths();
+        assert!(matches!(
+            <OldMessage as de::Deserialize>::deserialize(&mut deserializer),
+            Err(Error::NonCanonicalInt)
+        ));
+    }
+
+    #[test]
+    fn test_deserialize_data_vec() {
+        let hashes: Vec<crate::Data<32>> =
+            (0..300u32).map(|i| crate::Data([i as u8; 32])).collect();
+        let bytes = crate::to_vec(&hashes).unwrap();
+
+        // With the length of the input known, in one read
+        let mut deserializer = Deserializer::new(SliceReader::new(&bytes));
+        deserializer.remaining_len = Some(|reader| reader.remaining().len());
+        assert_eq!(deserialize_data_vec(&mut deserializer).unwrap(), hashes);
+        // From a reader, a chunk at a time
+        let mut deserializer = Deserializer::new(&bytes[..]);
+        assert_eq!(deserialize_data_vec(&mut deserializer).unwrap(), hashes);
+        assert!(deserializer.reader.is_empty());
+
+        let mut deserializer = Deserializer::new(&bytes[..bytes.len() - 1]);
+        assert!(matches!(
+            deserialize_data_vec::<_, 32>(&mut deserializer),
+            Err(Error::Eof)
+        ));
+        let mut deserializer = Deserializer::new(&[0xff, 0xff, 0xff, 0x7f][..]);
+        assert!(matches!(
+            deserialize_data_vec::<_, 0>(&mut deserializer),
+            Err(Error::ZeroWidthElements)
+        ));
+        let mut deserializer = Deserializer::new(&[0][..]);
+        assert!(deserialize_data_vec::<_, 0>(&mut deserializer)
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn test_length_overflow() {
+        // A length of 0x1_0000_0005
+        let bytes = [0x85, 0x80, 0x80, 0x80, 0x10, b'h', b'e', b'l', b'l', b'o'];
+        let owned = from_slice::<String>(&bytes);
+        let borrowed = from_slice_borrowed::<&str>(&bytes);
+        if cfg!(target_pointer_width = "32") {
+            assert!(matches!(owned, Err(Error::LengthOverflow)));
+            assert!(matches!(borrowed, Err(Error::LengthOverflow)));
+        } else {
+            assert!(matches!(owned, Err(Error::Eof)));
+            assert!(matches!(borrowed, Err(Error::Eof)));
+        }
+        assert!(matches!(checked_length(5), Ok(5)));
+    }
+
+    #[test]
+    fn test_try_from_slice() {
+        let bytes: &[u8] = &[5, b'h', b'e', b'l', b'l', b'o', 1, 2];
+        for end in 0..8 {
+            assert!(try_from_slice::<(String, u16)>(&bytes[..end])
+                .unwrap()
+                .is_none());
+        }
+        assert_eq!(
+            try_from_slice::<(String, u16)>(bytes).unwrap(),
+            Some((("hello".to_string(), 513), 8))
+        );
+        assert_eq!(
+            try_from_slice::<String>(bytes).unwrap(),
+            Some(("hello".to_string(), 6))
+        );
+        // Incomplete varint
+        assert!(try_from_slice::<crate::Uint>(&[0x80]).unwrap().is_none());
+
+        assert!(try_from_slice::<String>(&[2, 0xff, 0xff]).is_err());
+        assert!(try_from_slice::<char>(&[0, 0xd8, 0, 0]).is_err());
+    }
+
+    #[test]
+    fn test_borrowed() {
+        #[derive(Debug, PartialEq, serde_derive::Deserialize)]
+        struct Borrowed<'a> {
+            name: &'a str,
+            data: &'a [u8],
+            tags: Vec<&'a str>,
+            id: u16,
+        }
+
+        let bytes: &[u8] = &[2, b'h', b'i', 3, 1, 2, 3, 1, 1, b'x', 1, 2];
+        let borrowed = from_slice_borrowed::<Borrowed>(bytes).unwrap();
+        assert_eq!(
+            borrowed,
+            Borrowed {
+                name: "hi",
+                data: &[1, 2, 3],
+                tags: vec!["x"],
+                id: 513,
+            }
+        );
+        let range = bytes.as_ptr_range();
+        assert!(range.contains(&borrowed.name.as_ptr()));
+        assert!(range.contains(&borrowed.data.as_ptr()));
+        assert!(range.contains(&borrowed.tags[0].as_ptr()));
+
+        assert!(from_slice_borrowed::<&str>(&[2, 0xff, 0xff]).is_err());
+        assert!(from_slice_borrowed::<&[u8]>(&[5, 1, 2]).is_err());
+        assert_eq!(
+            String::from("hi"),
+            from_slice_borrowed::<String>(&[2, b'h', b'i']).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_cow() {
+        use alloc::borrow::Cow;
+
+        #[derive(Debug, PartialEq, serde_derive::Deserialize)]
+        struct Cows<'a> {
+            #[serde(borrow)]
+            name: Cow<'a, str>,
+            #[serde(borrow, with = "serde_bytes")]
+            data: Cow<'a, [u8]>,
+        }
+
+        let bytes: &[u8] = &[2, b'h', b'i', 3, 1, 2, 3];
+        let borrowed = from_slice_borrowed::<Cows>(bytes).unwrap();
+        assert!(matches!(borrowed.name, Cow::Borrowed("hi")));
+        assert!(matches!(borrowed.data, Cow::Borrowed([1, 2, 3])));
+
+        // A reader can't lend out its bytes, so the same type owns them instead.
+        let owned = <Cows as de::Deserialize>::deserialize(&mut Deserializer::new(bytes)).unwrap();
+        assert_eq!(owned, borrowed);
+        assert!(matches!(owned.name, Cow::Owned(_)));
+        assert!(matches!(owned.data, Cow::Owned(_)));
+    }
+
+    #[cfg(not(feature = "std"))]
+    #[test]
+    fn test_no_std() {
+        let bytes: &[u8] = &[2, b'h', b'i', 1, 1, 2, 3, 4];
+        assert_eq!(
+            ("hi".to_string(), Some(67305985u32)),
+            from_slice(bytes).unwrap()
+        );
+        assert_eq!(
+            ("hi".to_string(), Some(67305985u32)),
+            from_reader(bytes).unwrap()
+        );
+        assert!(from_slice::<u32>(&[1, 2]).is_err());
+    }
+
+    #[test]
+    fn test_expect_bytes() {
+        let bytes: &[u8] = &[b'B', b'A', b'R', b'E', 5, b'h', b'e', b'l', b'l', b'o'];
+        let mut deserializer = Deserializer::new(bytes);
+        deserializer.expect_bytes(b"BARE").unwrap();
+        let body = <String as de::Deserialize>::deserialize(&mut deserializer).unwrap();
+        assert_eq!(body, "hello");
+
+        let mut deserializer = Deserializer::new(bytes);
+        assert!(matches!(
+            deserializer.expect_bytes(b"BEAR"),
+            Err(Error::BadMagic)
+        ));
+    }
 }
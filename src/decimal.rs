@@ -0,0 +1,75 @@
+//! `rust_decimal::Decimal` as the BARE struct `{ mantissa: data<16>, scale: u8 }`, for use with
+//! `#[serde(with = "serde_bare::decimal")]`.
+//!
+//! `mantissa` is the decimal's 96-bit integer value as a little-endian two's complement `i128`,
+//! and the decimal is `mantissa / 10^scale`. This doesn't depend on the `i128` feature.
+use crate::Data;
+use rust_decimal::Decimal;
+use serde::{de::Error as _, Deserialize, Deserializer, Serialize, Serializer};
+
+pub fn serialize<S>(value: &Decimal, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    // Decimal's scale is at most 28.
+    let scale = value.scale() as u8;
+    (Data(value.mantissa().to_le_bytes()), scale).serialize(serializer)
+}
+
+pub fn deserialize<'de, D>(deserializer: D) -> Result<Decimal, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let (Data(mantissa), scale) = <(Data<16>, u8)>::deserialize(deserializer)?;
+    let mantissa = i128::from_le_bytes(mantissa);
+    Decimal::try_from_i128_with_scale(mantissa, u32::from(scale)).map_err(D::Error::custom)
+}
+
+#[cfg(test)]
+mod test {
+    use rust_decimal::Decimal;
+    use serde_derive::{Deserialize, Serialize};
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    struct Price {
+        #[serde(with = "super")]
+        amount: Decimal,
+    }
+
+    fn round_trip(amount: Decimal) -> Vec<u8> {
+        let price = Price { amount };
+        let bytes = crate::to_vec(&price).unwrap();
+        let got: Price = crate::from_slice(&bytes).unwrap();
+        assert_eq!(got, price);
+        assert_eq!(got.amount.scale(), amount.scale());
+        bytes
+    }
+
+    #[test]
+    fn test_round_trip() {
+        assert_eq!(round_trip(Decimal::ZERO), [0; 17]);
+
+        // -12.345
+        let bytes = round_trip(Decimal::new(-12345, 3));
+        let mut expected = (-12345i128).to_le_bytes().to_vec();
+        expected.push(3);
+        assert_eq!(bytes, expected);
+
+        round_trip(Decimal::MAX);
+        round_trip(Decimal::MIN);
+        // 0.0000000000000000000000000001
+        round_trip(Decimal::new(1, 28));
+        round_trip(Decimal::from_i128_with_scale(
+            -79_228_162_514_264_337_593_543_950_335,
+            28,
+        ));
+
+        let mut bad_scale = 1i128.to_le_bytes().to_vec();
+        bad_scale.push(29);
+        assert!(crate::from_slice::<Price>(&bad_scale).is_err());
+        // Wider than 96 bits
+        let mut too_large = (1i128 << 96).to_le_bytes().to_vec();
+        too_large.push(0);
+        assert!(crate::from_slice::<Price>(&too_large).is_err());
+    }
+}
@@ -1,23 +1,111 @@
+use crate::compat::io;
+use alloc::string::{String, ToString};
+use core::fmt::{self, Display};
 use serde::{de, ser};
-use std::{
-    fmt::{self, Display},
-    io,
-};
 
-pub type Result<T> = std::result::Result<T, Error>;
+pub type Result<T> = core::result::Result<T, Error>;
 
 #[derive(Debug)]
 pub enum Error {
     Message(String),
     Io(io::Error),
+    /// The input ended in the middle of a value.
+    Eof,
+    /// The input ended in the middle of element `got` of a sequence of `expected` elements,
+    /// counting from 0.
+    SeqTruncated {
+        expected: u64,
+        got: u64,
+    },
+    /// Bytes were left over after a value that should have used all of them.
+    TrailingData,
+    /// A length prefix is larger than `usize::MAX`.
+    LengthOverflow,
+    /// A length prefix is larger than the capacity of the type being decoded into, or decoding
+    /// would read past the limit of a `Deserializer::new_limited`.
+    LimitExceeded {
+        length: u64,
+        limit: usize,
+    },
+    /// A variable-length integer wasn't encoded in as few bytes as possible.
+    NonCanonicalInt,
+    /// A variable-length integer ran past `MAX_VARINT_LEN` bytes.
+    VarintTooLong,
+    /// A variable-length integer held more than 64 bits.
+    VarintOverflow,
+    /// The input ended after the first byte of a variable-length integer but before its last.
+    VarintTruncated,
+    /// A NaN float other than the canonical one, with strict float decoding enabled.
+    NonCanonicalFloat,
+    /// The buffer given to `to_slice` was too small. The value needs at least `needed_at_least`
+    /// bytes; more may follow the write that didn't fit.
+    BufferFull {
+        needed_at_least: usize,
+    },
 
-    AnyUnsupported,
+    /// A type asked for `deserialize_any` or `deserialize_ignored_any`, which need a
+    /// self-describing format. Holds a hint at what in the type usually causes it.
+    AnyUnsupported(&'static str),
 
-    InvalidUtf8,
-    InvalidChar,
+    /// A 128-bit integer, with the `i128` feature disabled.
+    I128Disabled,
 
+    /// A string wasn't valid UTF-8. The first `valid_up_to` bytes of it were.
+    InvalidUtf8 {
+        valid_up_to: usize,
+    },
+    /// A char that isn't a Unicode scalar value: a surrogate, or above U+10FFFF.
+    InvalidChar(u32),
+    /// A bool byte other than 0 or 1, with strict bool decoding enabled.
+    InvalidBool(u8),
+    /// Flags with bits set that the flags type doesn't define, read with
+    /// [`bitflags::strict`](crate::bitflags::strict).
+    InvalidFlags(u64),
+
+    /// An enum variant's tag was above the `Serializer`'s `max_variant_tag`.
+    VariantTagOutOfRange(u32),
+
+    /// A sequence of unknown length, with `buffer_unknown_lengths` disabled.
     SequenceLengthRequired,
+    /// A map of unknown length, which BARE can't represent.
     MapLengthRequired,
+    /// Map entries weren't in ascending order of their encoded keys.
+    UnsortedMapKeys,
+    /// The entries given to `serialize_map_sorted_iter` ran out before `expected` of them, or
+    /// had more left after them.
+    MapLengthMismatch {
+        expected: usize,
+    },
+    /// A map had the same key twice, with duplicate keys rejected.
+    DuplicateMapKey,
+
+    /// The bytes read by `Deserializer::expect_bytes` weren't the expected ones.
+    BadMagic,
+    /// Padding after a value wasn't all zero bytes.
+    BadPadding,
+    /// A type id with no decoder in the `TypeRegistry`.
+    UnregisteredType,
+    /// Values nested deeper than the configured `max_depth`.
+    DepthLimitExceeded,
+    /// A union tag that isn't one of the members of the union's `Layout`.
+    UnknownUnionTag(u64),
+    /// A nonempty list or map whose elements take up no bytes, so its length prefix alone
+    /// would decide how long decoding it takes.
+    ZeroWidthElements,
+}
+
+impl From<core::str::Utf8Error> for Error {
+    fn from(e: core::str::Utf8Error) -> Self {
+        Error::InvalidUtf8 {
+            valid_up_to: e.valid_up_to(),
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Self {
+        Error::Io(e)
+    }
 }
 
 impl ser::Error for Error {
@@ -37,13 +125,78 @@ impl Display for Error {
         match self {
             Error::Message(msg) => formatter.write_str(msg),
             Error::Io(e) => e.fmt(formatter),
-            Error::AnyUnsupported => formatter.write_str("BARE does not support any"),
-            Error::InvalidUtf8 => formatter.write_str("invalid utf-8 in string"),
-            Error::InvalidChar => formatter.write_str("invalid unicode codepoint in char"),
+            Error::Eof => formatter.write_str("unexpected end of input"),
+            Error::SeqTruncated { expected, got } => write!(
+                formatter,
+                "sequence truncated at element {} of {}",
+                got, expected
+            ),
+            Error::VarintTooLong => {
+                formatter.write_str("continuation bit indicated an invalid variable-length integer")
+            }
+            Error::VarintOverflow => {
+                formatter.write_str("variable-length integer overflowed 64 bits")
+            }
+            Error::VarintTruncated => {
+                formatter.write_str("expected further bytes in variable-length integer")
+            }
+            Error::TrailingData => formatter.write_str("trailing bytes after value"),
+            Error::LengthOverflow => {
+                formatter.write_str("length prefix too large for this platform")
+            }
+            Error::LimitExceeded { length, limit } => {
+                write!(formatter, "length {} exceeds capacity of {}", length, limit)
+            }
+            Error::NonCanonicalInt => {
+                formatter.write_str("variable-length integer not minimally encoded")
+            }
+            Error::NonCanonicalFloat => formatter.write_str("non-canonical NaN float"),
+            Error::BufferFull { needed_at_least } => write!(
+                formatter,
+                "buffer too small, need at least {} bytes",
+                needed_at_least
+            ),
+            Error::AnyUnsupported(hint) => {
+                write!(formatter, "BARE does not support any: {}", hint)
+            }
+            Error::I128Disabled => {
+                formatter.write_str("128-bit integers need the `i128` feature of serde_bare")
+            }
+            Error::InvalidUtf8 { valid_up_to } => write!(
+                formatter,
+                "invalid utf-8 in string after byte {}",
+                valid_up_to
+            ),
+            Error::InvalidChar(codepoint) => {
+                write!(
+                    formatter,
+                    "invalid unicode codepoint {:#x} in char",
+                    codepoint
+                )
+            }
+            Error::InvalidBool(b) => write!(formatter, "invalid bool byte {:#04x}", b),
+            Error::InvalidFlags(bits) => write!(formatter, "unknown bits set in flags {:#x}", bits),
+            Error::VariantTagOutOfRange(tag) => {
+                write!(formatter, "variant tag {} out of range", tag)
+            }
             Error::SequenceLengthRequired => formatter.write_str("sequence length required"),
             Error::MapLengthRequired => formatter.write_str("map length required"),
+            Error::UnsortedMapKeys => formatter.write_str("map keys not in ascending order"),
+            Error::MapLengthMismatch { expected } => {
+                write!(formatter, "map entries did not number {}", expected)
+            }
+            Error::DuplicateMapKey => formatter.write_str("duplicate map key"),
+            Error::BadMagic => formatter.write_str("bytes did not match expected magic"),
+            Error::BadPadding => formatter.write_str("nonzero padding byte"),
+            Error::UnregisteredType => formatter.write_str("no decoder registered for type"),
+            Error::DepthLimitExceeded => formatter.write_str("nesting depth limit exceeded"),
+            Error::UnknownUnionTag(tag) => write!(formatter, "unknown union tag {}", tag),
+            Error::ZeroWidthElements => {
+                formatter.write_str("nonempty list or map of zero-width elements")
+            }
         }
     }
 }
 
-impl std::error::Error for Error {}
+/// `std::error::Error` with `std`, serde's stand-in for it without.
+impl de::StdError for Error {}
@@ -0,0 +1,67 @@
+//! `[u8; N]` as BARE `data<N>`, for use with `#[serde(with = "serde_bare::fixed")]`.
+//!
+//! serde only implements `Serialize` and `Deserialize` for arrays of up to 32 elements, so
+//! fields like a 64-byte signature can't be derived as plain arrays. This encodes them the same
+//! way as [`Data`] does, for any `N`, while the field keeps its array type.
+use crate::Data;
+use serde::{Deserialize, Deserializer, Serializer};
+
+pub fn serialize<S, const N: usize>(value: &[u8; N], serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    use serde::ser::SerializeTuple;
+
+    let mut s = serializer.serialize_tuple(N)?;
+    for b in value.iter() {
+        s.serialize_element(b)?;
+    }
+    s.end()
+}
+
+pub fn deserialize<'de, D, const N: usize>(deserializer: D) -> Result<[u8; N], D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let Data(bytes) = Data::<N>::deserialize(deserializer)?;
+    Ok(bytes)
+}
+
+#[cfg(test)]
+mod test {
+    use serde_derive::{Deserialize, Serialize};
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    struct Signed {
+        #[serde(with = "super")]
+        signature: [u8; 64],
+        #[serde(with = "super")]
+        flag: [u8; 1],
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let mut signature = [0; 64];
+        for (i, b) in signature.iter_mut().enumerate() {
+            *b = i as u8 * 3;
+        }
+        let signed = Signed {
+            signature,
+            flag: [0xab],
+        };
+        let bytes = crate::to_vec(&signed).unwrap();
+        assert_eq!(bytes.len(), 65);
+        assert_eq!(bytes[..64], signature);
+        assert_eq!(bytes[64], 0xab);
+        assert_eq!(
+            bytes,
+            crate::to_vec(&(crate::Data(signature), crate::Data([0xab]))).unwrap()
+        );
+        assert_eq!(crate::from_slice::<Signed>(&bytes).unwrap(), signed);
+        assert_eq!(
+            crate::from_slice_borrowed::<Signed>(&bytes).unwrap(),
+            signed
+        );
+        assert!(crate::from_slice::<Signed>(&bytes[..64]).is_err());
+    }
+}
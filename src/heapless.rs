@@ -0,0 +1,163 @@
+//! Strings and data with a fixed capacity, for use with `#[serde(with = "...")]` on `no_std`
+//! targets without a heap.
+//!
+//! These encode as BARE `string` and `data`. Decoding checks the length prefix against the
+//! capacity `N` before reading anything else, and fails with `Error::LimitExceeded` if it's
+//! larger. That check relies on this crate's deserializers, so these modules can't be used with
+//! other formats.
+use crate::de::BOUNDED_BYTES;
+use core::{fmt, str};
+
+/// `heapless::String<N>` as BARE `string`.
+pub mod string {
+    use super::*;
+    use ::heapless::String;
+    use serde::{
+        de::{Error, Unexpected, Visitor},
+        Deserializer, Serializer,
+    };
+
+    pub fn serialize<S, const N: usize>(value: &String<N>, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_str(value)
+    }
+
+    pub fn deserialize<'de, D, const N: usize>(deserializer: D) -> Result<String<N>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_tuple_struct(BOUNDED_BYTES, N, StringVisitor)
+    }
+
+    struct StringVisitor<const N: usize>;
+
+    impl<'de, const N: usize> Visitor<'de> for StringVisitor<N> {
+        type Value = String<N>;
+
+        fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+            write!(formatter, "a string of at most {} bytes", N)
+        }
+
+        fn visit_bytes<E: Error>(self, v: &[u8]) -> Result<String<N>, E> {
+            let v = str::from_utf8(v).map_err(|_| E::invalid_value(Unexpected::Bytes(v), &self))?;
+            let mut string = String::new();
+            string
+                .push_str(v)
+                .map_err(|()| E::invalid_length(v.len(), &self))?;
+            Ok(string)
+        }
+    }
+}
+
+/// `heapless::Vec<u8, N>` as BARE `data`.
+pub mod vec {
+    use super::*;
+    use ::heapless::Vec;
+    use serde::{
+        de::{Error, Visitor},
+        Deserializer, Serializer,
+    };
+
+    pub fn serialize<S, const N: usize>(
+        value: &Vec<u8, N>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_bytes(value)
+    }
+
+    pub fn deserialize<'de, D, const N: usize>(deserializer: D) -> Result<Vec<u8, N>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_tuple_struct(BOUNDED_BYTES, N, BytesVisitor)
+    }
+
+    struct BytesVisitor<const N: usize>;
+
+    impl<'de, const N: usize> Visitor<'de> for BytesVisitor<N> {
+        type Value = Vec<u8, N>;
+
+        fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+            write!(formatter, "at most {} bytes", N)
+        }
+
+        fn visit_bytes<E: Error>(self, v: &[u8]) -> Result<Vec<u8, N>, E> {
+            Vec::from_slice(v).map_err(|()| E::invalid_length(v.len(), &self))
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::Error;
+    use ::heapless::{String, Vec};
+    use serde_derive::{Deserialize, Serialize};
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    struct Reading {
+        #[serde(with = "super::string")]
+        sensor: String<8>,
+        #[serde(with = "super::vec")]
+        samples: Vec<u8, 4>,
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let reading = Reading {
+            sensor: "temp".parse().unwrap(),
+            samples: Vec::from_slice(&[1, 2, 3]).unwrap(),
+        };
+        let bytes = crate::to_vec(&reading).unwrap();
+        assert_eq!(bytes, b"\x04temp\x03\x01\x02\x03");
+        assert_eq!(crate::from_slice::<Reading>(&bytes).unwrap(), reading);
+        assert_eq!(
+            crate::from_reader::<_, Reading>(&bytes[..]).unwrap(),
+            reading
+        );
+
+        // Exactly at capacity
+        let full = b"\x08pressure\x04\x01\x02\x03\x04";
+        let reading = crate::from_slice::<Reading>(full).unwrap();
+        assert_eq!(reading.sensor, "pressure");
+        assert_eq!(reading.samples, [1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn test_limit_exceeded() {
+        let bytes = b"\x09humidity!\x00";
+        assert!(matches!(
+            crate::from_slice::<Reading>(bytes),
+            Err(Error::LimitExceeded {
+                length: 9,
+                limit: 8
+            })
+        ));
+        assert!(matches!(
+            crate::from_reader::<_, Reading>(&bytes[..]),
+            Err(Error::LimitExceeded {
+                length: 9,
+                limit: 8
+            })
+        ));
+
+        // The length is rejected before the contents are read.
+        let huge = crate::to_vec(&crate::Uint(u64::MAX)).unwrap();
+        assert!(matches!(
+            crate::from_reader::<_, Reading>(&huge[..]),
+            Err(Error::LimitExceeded { limit: 8, .. })
+        ));
+        assert!(matches!(
+            crate::from_slice::<Reading>(b"\x02ok\x05\x01"),
+            Err(Error::LimitExceeded {
+                length: 5,
+                limit: 4
+            })
+        ));
+        assert!(crate::from_slice::<Reading>(b"\x02\xff\xfe\x00").is_err());
+    }
+}
@@ -0,0 +1,388 @@
+//! Hex dumps of serialized values, for comparing this crate's output with other BARE
+//! implementations.
+//!
+//! `to_hex(&User { id: 42, role: Role::Admin })` gives `2a 00 00 00 00`, and `annotate` on the
+//! same value breaks it down by field:
+//!
+//! ```text
+//! id    2a 00 00 00
+//! role  00
+//! ```
+use crate::{ser::Serializer, to_vec, Result};
+use serde::{ser, Serialize};
+use std::{fmt::Write, ops::Range, string::String, vec::Vec};
+
+/// The bytes `to_vec` gives for `value`, in hex with a space between each byte.
+pub fn to_hex<T>(value: &T) -> Result<String>
+where
+    T: ?Sized + Serialize,
+{
+    Ok(hex(&to_vec(value)?))
+}
+
+/// Like `to_hex`, with a line for each struct field giving its name and its bytes. The fields of
+/// a nested struct follow the field holding it, named by their path from the top, such as
+/// `session.token`. Structs inside sequences, maps and tuples aren't broken down. Bytes outside
+/// any field, like the tag of an enum or a value that isn't a struct at all, get a line with
+/// no name.
+pub fn annotate<T>(value: &T) -> Result<String>
+where
+    T: ?Sized + Serialize,
+{
+    let mut serializer = Serializer::new(Vec::new());
+    let mut fields = Vec::new();
+    value.serialize(Annotator {
+        ser: &mut serializer,
+        fields: &mut fields,
+        prefix: "",
+        top: true,
+    })?;
+    let bytes = serializer.into_inner();
+
+    // Fill the gaps between top-level fields with unnamed lines.
+    let mut lines = Vec::new();
+    let mut pos = 0;
+    for field in fields {
+        if field.top && field.bytes.start > pos {
+            lines.push((String::new(), pos..field.bytes.start));
+        }
+        if field.top {
+            pos = field.bytes.end;
+        }
+        lines.push((field.name, field.bytes));
+    }
+    if pos < bytes.len() {
+        lines.push((String::new(), pos..bytes.len()));
+    }
+
+    let width = lines.iter().map(|(name, _)| name.len()).max().unwrap_or(0);
+    let mut out = String::new();
+    for (name, range) in lines {
+        let line = format!("{:width$}  {}", name, hex(&bytes[range]), width = width);
+        out.push_str(line.trim_end());
+        out.push('\n');
+    }
+    Ok(out)
+}
+
+fn hex(bytes: &[u8]) -> String {
+    let mut out = String::with_capacity(bytes.len() * 3);
+    for (i, b) in bytes.iter().enumerate() {
+        if i > 0 {
+            out.push(' ');
+        }
+        write!(out, "{:02x}", b).unwrap();
+    }
+    out
+}
+
+/// A struct field and where its bytes are in the output.
+struct Field {
+    name: String,
+    bytes: Range<usize>,
+    /// Whether it's a field of the value being annotated rather than of a nested struct.
+    top: bool,
+}
+
+/// Serializes like `Serializer`, recording the bytes of each struct field it comes across.
+/// BARE structs are just their fields one after another, so they are written field by field here
+/// rather than through `Serializer`.
+struct Annotator<'a> {
+    ser: &'a mut Serializer<Vec<u8>>,
+    fields: &'a mut Vec<Field>,
+    /// The path of the struct being serialized, ending in a dot, or empty at the top.
+    prefix: &'a str,
+    top: bool,
+}
+
+type Inner<'a> = &'a mut Serializer<Vec<u8>>;
+
+macro_rules! forward {
+    ($($method:ident($($arg:ident: $ty:ty),*) -> $ret:ty;)*) => {
+        $(
+            fn $method(self, $($arg: $ty),*) -> Result<$ret> {
+                ser::Serializer::$method(self.ser, $($arg),*)
+            }
+        )*
+    };
+}
+
+impl<'a> ser::Serializer for Annotator<'a> {
+    type Ok = ();
+    type Error = crate::Error;
+    type SerializeSeq = <Inner<'a> as ser::Serializer>::SerializeSeq;
+    type SerializeTuple = <Inner<'a> as ser::Serializer>::SerializeTuple;
+    type SerializeTupleStruct = <Inner<'a> as ser::Serializer>::SerializeTupleStruct;
+    type SerializeTupleVariant = <Inner<'a> as ser::Serializer>::SerializeTupleVariant;
+    type SerializeMap = <Inner<'a> as ser::Serializer>::SerializeMap;
+    type SerializeStruct = AnnotatedStruct<'a>;
+    type SerializeStructVariant = AnnotatedStruct<'a>;
+
+    forward! {
+        serialize_bool(v: bool) -> ();
+        serialize_i8(v: i8) -> ();
+        serialize_i16(v: i16) -> ();
+        serialize_i32(v: i32) -> ();
+        serialize_i64(v: i64) -> ();
+        serialize_i128(v: i128) -> ();
+        serialize_u8(v: u8) -> ();
+        serialize_u16(v: u16) -> ();
+        serialize_u32(v: u32) -> ();
+        serialize_u64(v: u64) -> ();
+        serialize_u128(v: u128) -> ();
+        serialize_f32(v: f32) -> ();
+        serialize_f64(v: f64) -> ();
+        serialize_char(v: char) -> ();
+        serialize_str(v: &str) -> ();
+        serialize_bytes(v: &[u8]) -> ();
+        serialize_none() -> ();
+        serialize_unit() -> ();
+        serialize_unit_struct(name: &'static str) -> ();
+        serialize_unit_variant(name: &'static str, index: u32, variant: &'static str) -> ();
+        serialize_seq(len: Option<usize>) -> Self::SerializeSeq;
+        serialize_tuple(len: usize) -> Self::SerializeTuple;
+        serialize_tuple_struct(name: &'static str, len: usize) -> Self::SerializeTupleStruct;
+        serialize_tuple_variant(
+            name: &'static str,
+            index: u32,
+            variant: &'static str,
+            len: usize
+        ) -> Self::SerializeTupleVariant;
+        serialize_map(len: Option<usize>) -> Self::SerializeMap;
+    }
+
+    fn serialize_some<T>(self, value: &T) -> Result<()>
+    where
+        T: ?Sized + Serialize,
+    {
+        ser::Serializer::serialize_u8(&mut *self.ser, 1)?;
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_struct<T>(self, _name: &'static str, value: &T) -> Result<()>
+    where
+        T: ?Sized + Serialize,
+    {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T>(
+        self,
+        _name: &'static str,
+        variant_index: u32,
+        _variant: &'static str,
+        value: &T,
+    ) -> Result<()>
+    where
+        T: ?Sized + Serialize,
+    {
+        self.ser.write_variant_tag(variant_index)?;
+        value.serialize(self)
+    }
+
+    fn serialize_struct(self, _name: &'static str, _len: usize) -> Result<AnnotatedStruct<'a>> {
+        Ok(AnnotatedStruct(self))
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<AnnotatedStruct<'a>> {
+        self.ser.write_variant_tag(variant_index)?;
+        Ok(AnnotatedStruct(self))
+    }
+
+    fn collect_str<T>(self, value: &T) -> Result<()>
+    where
+        T: ?Sized + core::fmt::Display,
+    {
+        ser::Serializer::collect_str(self.ser, value)
+    }
+
+    fn is_human_readable(&self) -> bool {
+        false
+    }
+}
+
+struct AnnotatedStruct<'a>(Annotator<'a>);
+
+impl<'a> AnnotatedStruct<'a> {
+    fn field<T>(&mut self, key: &'static str, value: &T) -> Result<()>
+    where
+        T: ?Sized + Serialize,
+    {
+        let Annotator {
+            ser,
+            fields,
+            prefix,
+            top,
+        } = &mut self.0;
+        let name = format!("{}{}", prefix, key);
+        let prefix = format!("{}.", name);
+        let index = fields.len();
+        let start = ser.get_ref().len();
+        fields.push(Field {
+            name,
+            bytes: start..start,
+            top: *top,
+        });
+        value.serialize(Annotator {
+            ser,
+            fields,
+            prefix: &prefix,
+            top: false,
+        })?;
+        fields[index].bytes.end = ser.get_ref().len();
+        Ok(())
+    }
+}
+
+impl<'a> ser::SerializeStruct for AnnotatedStruct<'a> {
+    type Ok = ();
+    type Error = crate::Error;
+
+    fn serialize_field<T>(&mut self, key: &'static str, value: &T) -> Result<()>
+    where
+        T: ?Sized + Serialize,
+    {
+        self.field(key, value)
+    }
+
+    fn end(self) -> Result<()> {
+        Ok(())
+    }
+}
+
+impl<'a> ser::SerializeStructVariant for AnnotatedStruct<'a> {
+    type Ok = ();
+    type Error = crate::Error;
+
+    fn serialize_field<T>(&mut self, key: &'static str, value: &T) -> Result<()>
+    where
+        T: ?Sized + Serialize,
+    {
+        self.field(key, value)
+    }
+
+    fn end(self) -> Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{annotate, to_hex};
+    use serde_derive::Serialize;
+
+    #[derive(Serialize)]
+    enum UserRole {
+        Admin,
+    }
+
+    #[derive(Serialize)]
+    struct Session {
+        #[serde(with = "serde_bytes")]
+        token: Vec<u8>,
+        expires: u64,
+    }
+
+    /// The `User` of the `user_sessions` benchmark.
+    #[derive(Serialize)]
+    struct User {
+        id: u32,
+        name: String,
+        email: String,
+        role: UserRole,
+        session: Option<Session>,
+    }
+
+    #[test]
+    fn test_to_hex() {
+        assert_eq!(to_hex(&513u16).unwrap(), "01 02");
+        assert_eq!(to_hex("hi").unwrap(), "02 68 69");
+        assert_eq!(to_hex(&()).unwrap(), "");
+    }
+
+    #[test]
+    fn test_annotate() {
+        let user = User {
+            id: 42,
+            name: "Jane Doe".to_string(),
+            email: "jdoe@example.com".to_string(),
+            role: UserRole::Admin,
+            session: Some(Session {
+                token: b"a2b08ecd".to_vec(),
+                expires: 42424242,
+            }),
+        };
+        let hex = to_hex(&user).unwrap();
+        assert_eq!(hex.len() + 1, crate::to_vec(&user).unwrap().len() * 3);
+        assert_eq!(
+            annotate(&user).unwrap(),
+            "\
+id               2a 00 00 00
+name             08 4a 61 6e 65 20 44 6f 65
+email            10 6a 64 6f 65 40 65 78 61 6d 70 6c 65 2e 63 6f 6d
+role             00
+session          01 08 61 32 62 30 38 65 63 64 b2 57 87 02 00 00 00 00
+session.token    08 61 32 62 30 38 65 63 64
+session.expires  b2 57 87 02 00 00 00 00
+"
+        );
+
+        // A top-level enum's tag isn't part of any field.
+        #[derive(Serialize)]
+        enum Event {
+            #[allow(dead_code)]
+            Login,
+            Logout {
+                id: u16,
+            },
+        }
+        assert_eq!(
+            annotate(&Event::Logout { id: 7 }).unwrap(),
+            "    01\nid  07 00\n"
+        );
+        assert_eq!(annotate(&[1u8, 2]).unwrap(), "  01 02\n");
+    }
+
+    #[test]
+    fn test_annotate_variants() {
+        #[derive(Serialize)]
+        enum Shape {
+            #[allow(dead_code)]
+            Empty,
+            Circle(f32),
+            Square {
+                side: u16,
+                filled: bool,
+            },
+        }
+
+        /// The bytes of the top-level lines of `annotate`, which cover the whole value.
+        fn annotated_hex<T: serde::Serialize>(value: &T) -> String {
+            let annotated = annotate(value).unwrap();
+            let lines: Vec<&str> = annotated
+                .lines()
+                .filter_map(|line| line.split_once("  "))
+                .filter(|(name, _)| !name.contains('.'))
+                .map(|(_, bytes)| bytes.trim_start())
+                .collect();
+            lines.join(" ")
+        }
+
+        for shape in [
+            Shape::Circle(1.5),
+            Shape::Square {
+                side: 3,
+                filled: true,
+            },
+        ] {
+            assert_eq!(annotated_hex(&shape), to_hex(&shape).unwrap());
+            assert_eq!(annotated_hex(&[&shape]), to_hex(&[&shape]).unwrap());
+        }
+    }
+}
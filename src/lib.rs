@@ -1,4 +1,5 @@
 #![forbid(unsafe_code)]
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
 //! # serde_bare
 //!
 //! An implementation of the BARE (https://baremessages.org) encoding format draft.
@@ -8,10 +9,18 @@
 //! ### `bool`, `i8` through `i64`, `u8` through `u64`, `f32`, `f64`, `string`
 //!
 //! Serialize as the BARE types of the same name.
+//! [`F32`] and [`F64`] encode identically to `f32` and `f64`, and spell out a field's intended
+//! width where a plain float could be changed to the other one by mistake.
 //!
 //! ### `i128`, `u128`
 //!
-//! Serialize as `data<16>`.
+//! Serialize as `data<16>`: the 16 bytes of the value in little-endian order, two's complement for
+//! `i128`, with no length prefix. `-2i128` is `fe ff ff ff ff ff ff ff ff ff ff ff ff ff ff ff`
+//! and `1u128 << 64` is `00 00 00 00 00 00 00 00 01 00 00 00 00 00 00 00`.
+//!
+//! 128-bit integers aren't part of the current BARE draft, so other implementations may not
+//! accept them. They're behind the default `i128` feature; without it they fail to serialize and
+//! deserialize with `Error::I128Disabled`.
 //!
 //! ### `char`
 //!
@@ -20,6 +29,25 @@
 //! ### `byte array`
 //!
 //! Serializes as `data`.
+//! Use [`Data`] for `data<N>`, or [`fixed`] for array fields of any length, which serde can't
+//! derive past 32 elements.
+//!
+//! serde treats a plain `Vec<u8>` or `&[u8]` as a `seq` of `u8`, not a byte array. `[]u8` has
+//! the same encoding as `data`, so the output is the same, but every byte goes through the
+//! serializer and deserializer on its own, which is many times slower for large buffers (see the
+//! `byte_vectors` benchmark). The serializer at least gathers runs of bytes in a sequence into
+//! one write. Use [`ByteBuf`], `serde_bytes::ByteBuf`, or `#[serde(with = "serde_bytes")]` on
+//! the field, to read and write the bytes in one go.
+//!
+//! ### References, `Box`, `Rc` and `Arc`
+//!
+//! Serialize as the type they point to, with nothing added around it, at the top level as well
+//! as inside other values. `to_vec(&&value)` and `to_vec(&Arc::new(value))` give the same bytes
+//! as `to_vec(&value)`. So `Box<str>`, `Rc<str>` and `Arc<str>` are `string`,
+//! `Box<[T]>`, `Rc<[T]>` and `Arc<[T]>` are `[]T`, and `Box<[u8]>`, `Rc<[u8]>` and `Arc<[u8]>`
+//! are `[]u8`, which has the same encoding as `data`. `Rc` and `Arc` need serde's `rc` feature,
+//! and like serde, serializing them doesn't preserve sharing: each reference is written in
+//! full and decodes to its own allocation.
 //!
 //! ### `option`
 //!
@@ -28,7 +56,12 @@
 //! ### `seq`
 //!
 //! Serializes as `[]type`.
-//! Sequences with unknown lengths are not representable in BARE.
+//! BARE writes the length first, so by default a sequence of unknown length, such as one from
+//! `collect_seq` on a filtered iterator, fails with `Error::SequenceLengthRequired`. Enabling
+//! `Serializer::buffer_unknown_lengths` (or the `buffer_unknown_lengths` field of
+//! `SerializerConfig`) serializes such a sequence into memory until it ends and then writes the
+//! length and the buffered bytes, at the cost of holding the whole encoded sequence and copying it
+//! once. Sequences of known length are never buffered.
 //!
 //! ### `map`
 //!
@@ -48,6 +81,11 @@
 //! Serialized as the variant index as a `uint` followed by the variant data.
 //! The container name and variant name are ignored.
 //!
+//! Derived enums number their variants from 0 in declaration order, so the order is part of the
+//! wire format: reordering variants, or adding one anywhere but the end, changes the encoding.
+//! Tags without a variant fail to decode with serde's unknown variant error. [`std_enums`]
+//! encodes fieldless enums from the standard library, such as `Ordering`, the same way.
+//!
 //! ### `newtype_struct`
 //!
 //! Serialized the same as the contained type.
@@ -80,47 +118,278 @@
 //!
 //! Serialized as a `uint` followed by the variant data.
 //! The container name and variant name are ignored.
+//!
+//! ### Enum representations
+//!
+//! Only serde's default, externally tagged representation of enums is a BARE union. Internally
+//! and adjacently tagged enums (`#[serde(tag = "...")]`) and untagged enums reach the serializer
+//! as plain structs and values, which can't be told apart from ones written by hand, so they
+//! serialize without an error into something that isn't a union: an internally tagged variant
+//! becomes a struct whose first field is the variant name as a `string`. Decoding any of them
+//! fails with `Error::AnyUnsupported`. Leave the enum's representation at the default to encode
+//! it as a union.
+//!
+//! ## `no_std`
+//!
+//! Disabling the default `std` feature and enabling `alloc` builds the crate for `no_std`
+//! targets. I/O then goes through the `no_std_io` traits instead of `std::io`.
+//!
+//! With `std`, [`hexdump`] formats serialized values as hex for debugging, optionally with a line
+//! for each struct field.
+//!
+//! ## `tokio`
+//!
+//! The `tokio` feature adds functions for reading and writing with tokio's async I/O traits.
+//! The `codec` feature adds a `tokio_util` codec on top of those.
+//!
+//! ## `bytes`
+//!
+//! The `bytes` feature adds functions for reading from `bytes::Buf` and writing to
+//! `bytes::BufMut`.
+//!
+//! ## `time`
+//!
+//! The `time` feature adds [`time_crate`], with encodings for the `time` crate's types.
+//!
+//! ## `chrono`
+//!
+//! The `chrono` feature adds [`chrono`](mod@chrono), with encodings for chrono's `DateTime<Utc>`
+//! and `NaiveDateTime`.
+//!
+//! ## `bitflags`
+//!
+//! The `bitflags` feature adds [`bitflags`](mod@bitflags), which encodes types generated by
+//! `bitflags!` as their underlying unsigned integer, optionally rejecting unknown bits.
+//!
+//! ## `indexmap`
+//!
+//! The `indexmap` feature implements [`BareSchema`] and [`BareSize`] for `indexmap::IndexMap`,
+//! and turns on indexmap's serde support. An `IndexMap` is written in its iteration order and
+//! read back in the order its entries appear in the input, so decoding and re-encoding a map
+//! gives the same bytes.
+//!
+//! ## `smallvec`
+//!
+//! The `smallvec` feature implements [`BareSchema`] and [`BareSize`] for `smallvec::SmallVec`,
+//! and turns on smallvec's serde support. A `SmallVec` is a `[]type` like a `Vec`, and decoding
+//! one only allocates once it has more elements than fit inline (see the `small_seqs`
+//! benchmark). Length prefixes aren't used to reserve space up front, so a large one from
+//! untrusted input can't make it allocate more than the elements that are actually there.
+//!
+//! ## `rust_decimal`
+//!
+//! The `rust_decimal` feature adds [`decimal`], an exact encoding for `rust_decimal::Decimal`.
+//!
+//! ## `uuid`
+//!
+//! The `uuid` feature adds [`uuid`](mod@uuid), which encodes `uuid::Uuid` as `data<16>`.
+//!
+//! ## `arbitrary`
+//!
+//! The `arbitrary` feature implements `arbitrary::Arbitrary` for [`Uint`] and [`Int`], for
+//! generating them in fuzz targets.
+//!
+//! ## `heapless`
+//!
+//! The `heapless` feature adds [`heapless`](mod@heapless), which encodes `heapless::String` and
+//! `heapless::Vec<u8, N>` and rejects lengths beyond their capacity before reading them.
+//!
+//! ## `proptest`
+//!
+//! The `proptest` feature adds [`proptest`](mod@proptest), with strategies for [`Uint`] and
+//! [`Int`] and a helper for round trip property tests.
+//!
+//! ## `codegen`
+//!
+//! The `codegen` feature adds [`codegen`], which generates Rust types from a `.bare` schema.
+//!
+//! ## `derive`
+//!
+//! The `derive` feature adds a derive macro for [`BareSchema`], which describes Rust types as a
+//! `.bare` schema, see [`schema`], one for [`BareSize`], which estimates their serialized
+//! size, see [`size`], and one for [`BareUnion`], which gives enum variants explicit union tags,
+//! see [`tags`].
+
+#[cfg(not(feature = "alloc"))]
+compile_error!("serde_bare requires either the `std` or `alloc` feature");
 
+extern crate alloc;
+// Lets derived code in tests refer to this crate by name.
+#[cfg(test)]
+extern crate self as serde_bare;
+
+#[cfg(feature = "tokio")]
+pub mod async_io;
+#[cfg(feature = "bitflags")]
+pub mod bitflags;
+#[cfg(feature = "bytes")]
+pub mod buf;
+#[cfg(feature = "chrono")]
+pub mod chrono;
+#[cfg(feature = "codec")]
+pub mod codec;
+#[cfg(feature = "codegen")]
+pub mod codegen;
+mod compat;
 pub mod de;
+#[cfg(feature = "rust_decimal")]
+pub mod decimal;
 pub mod error;
+pub mod fixed;
+#[cfg(feature = "heapless")]
+pub mod heapless;
+#[cfg(feature = "std")]
+pub mod hexdump;
+#[cfg(feature = "std")]
+pub mod net;
+#[cfg(feature = "proptest")]
+pub mod proptest;
+pub mod registry;
+pub mod schema;
 pub mod ser;
+pub mod size;
+pub mod std_enums;
+pub mod tags;
+#[cfg(test)]
+mod test_util;
+pub mod time;
+#[cfg(feature = "time")]
+pub mod time_crate;
+#[cfg(feature = "uuid")]
+pub mod uuid;
+pub mod value;
 
+#[cfg(feature = "tokio")]
+#[doc(inline)]
+pub use async_io::{
+    from_async_reader, from_async_reader_framed, from_async_reader_framed_limited, to_async_writer,
+};
+#[cfg(feature = "bytes")]
 #[doc(inline)]
-pub use de::{from_reader, from_slice, Deserializer};
+pub use buf::{from_buf, to_buf_mut};
+#[doc(inline)]
+pub use de::{
+    deserialize_data_vec, from_reader, from_reader_framed, from_reader_iter, from_reader_padded,
+    from_reader_sized, from_reader_with_config, from_slice, from_slice_borrowed,
+    from_slice_versioned, from_slice_with_config, try_from_slice, Deserializer, DeserializerConfig,
+    SliceDeserializer, SliceReader,
+};
 #[doc(inline)]
 pub use error::{Error, Result};
 #[doc(inline)]
-pub use ser::{to_vec, to_writer, Serializer};
+pub use schema::{bare_schema, BareSchema};
+#[doc(inline)]
+pub use ser::{
+    serialize_f32_slice, serialize_f64_slice, serialize_i16_slice, serialize_i32_slice,
+    serialize_i64_slice, serialize_map_sorted_iter, serialize_u16_slice, serialize_u32_slice,
+    serialize_u64_slice, serialized_size, to_slice, to_vec, to_vec_in, to_vec_presized,
+    to_vec_schema_ordered, to_vec_versioned, to_vec_with_config, to_writer, to_writer_counted,
+    to_writer_framed, to_writer_with_config, transcode, CountWriter, Serializer, SerializerConfig,
+};
+#[cfg(feature = "derive")]
+pub use serde_bare_derive::{BareSchema, BareSize, BareUnion};
+#[doc(inline)]
+pub use size::BareSize;
+#[doc(inline)]
+pub use tags::{BareUnion, Union};
+#[doc(inline)]
+pub use value::{from_slice_with_layout, is_canonical_dynamic, Layout, Value};
+
+/// The most bytes a BARE `uint` or `int` takes up: 64 bits in groups of 7.
+pub const MAX_VARINT_LEN: usize = 10;
+
+/// The bits of the one NaN `f32` that strict float decoding accepts, and that `canonical_nan`
+/// encodes every NaN as: the quiet NaN with no payload and the sign bit clear.
+pub const CANONICAL_NAN_F32: u32 = 0x7fc0_0000;
+
+/// The `f64` counterpart of [`CANONICAL_NAN_F32`].
+pub const CANONICAL_NAN_F64: u64 = 0x7ff8_0000_0000_0000;
+
+/// The number of bytes `value` takes up as a BARE `uint`, from 1 to `MAX_VARINT_LEN`.
+pub const fn varint_len(value: u64) -> usize {
+    Uint(value).encoded_len()
+}
+
+/// Encode `value` as a BARE `uint` into the start of `out`, returning the number of bytes used.
+/// This is the encoding [`Uint`] serializes with.
+pub const fn encode_uint(value: u64, out: &mut [u8; MAX_VARINT_LEN]) -> usize {
+    let mut x = value;
+    let mut i = 0;
+    while x >= 0x80 {
+        out[i] = (x as u8) | 0x80;
+        x >>= 7;
+        i += 1;
+    }
+    out[i] = x as u8;
+    i + 1
+}
+
+/// Decode a BARE `uint` from the start of `bytes`, returning the value and the number of bytes
+/// it took up. Like [`Uint`], this fails with Error::VarintTooLong or Error::VarintOverflow if
+/// the integer runs past `MAX_VARINT_LEN` bytes or 64 bits, but accepts encodings longer than
+/// needed. Error::Eof if `bytes` is empty and Error::VarintTruncated if it ends partway through.
+pub fn decode_uint(bytes: &[u8]) -> Result<(u64, usize)> {
+    let mut x = 0u64;
+    for (i, &b) in bytes.iter().enumerate() {
+        // No more than MAX_VARINT_LEN bytes can be in a BARE uint/int,
+        // and the last can only hold the 64th bit
+        if i == MAX_VARINT_LEN - 1 && b >= 0x80 {
+            return Err(Error::VarintTooLong);
+        }
+        if i == MAX_VARINT_LEN - 1 && b > 1 {
+            return Err(Error::VarintOverflow);
+        }
+        if b < 0x80 {
+            // No continuation bit is set
+            return Ok((x | (b as u64) << (7 * i), i + 1));
+        }
+        x |= ((b & 0x7f) as u64) << (7 * i);
+    }
+    if bytes.is_empty() {
+        Err(Error::Eof)
+    } else {
+        Err(Error::VarintTruncated)
+    }
+}
 
 #[derive(Copy, Clone, Debug, Ord, PartialOrd, Eq, PartialEq)]
 pub struct Uint(pub u64);
 
+#[allow(clippy::derivable_impls)]
 impl Default for Uint {
     fn default() -> Uint {
         Uint(0)
     }
 }
 
+impl Uint {
+    /// The number of bytes this value takes up when serialized, from 1 to `MAX_VARINT_LEN`.
+    pub const fn encoded_len(self) -> usize {
+        let bits = 64 - self.0.leading_zeros() as usize;
+        if bits == 0 {
+            1
+        } else {
+            bits.div_ceil(7)
+        }
+    }
+
+    /// Encode into `buf`, returning the number of bytes used.
+    pub(crate) fn encode(self, buf: &mut [u8; MAX_VARINT_LEN]) -> usize {
+        encode_uint(self.0, buf)
+    }
+}
+
 impl serde::ser::Serialize for Uint {
-    fn serialize<S>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error>
+    fn serialize<S>(&self, serializer: S) -> core::result::Result<S::Ok, S::Error>
     where
         S: serde::ser::Serializer,
     {
         use serde::ser::SerializeTuple;
 
-        let Uint(mut x) = *self;
-        let mut buf = [0u8; 10];
-        let mut i = 0usize;
-        while x >= 0x80 {
-            buf[i] = (x as u8) | 0x80;
-            x >>= 7;
-            i += 1;
-        }
-        buf[i] = x as u8;
-        i += 1;
-
+        let mut buf = [0u8; MAX_VARINT_LEN];
+        let len = self.encode(&mut buf);
         let mut s = serializer.serialize_tuple(usize::MAX)?;
-        for b in buf.iter().take(i) {
+        for b in &buf[..len] {
             s.serialize_element(&b)?;
         }
         s.end()
@@ -128,11 +397,11 @@ impl serde::ser::Serialize for Uint {
 }
 
 impl<'de> serde::de::Deserialize<'de> for Uint {
-    fn deserialize<D>(deserializer: D) -> std::result::Result<Self, D::Error>
+    fn deserialize<D>(deserializer: D) -> core::result::Result<Self, D::Error>
     where
         D: serde::de::Deserializer<'de>,
     {
-        use std::fmt;
+        use core::fmt;
 
         struct UintVisitor;
         impl<'de> serde::de::Visitor<'de> for UintVisitor {
@@ -142,54 +411,63 @@ impl<'de> serde::de::Deserialize<'de> for Uint {
                 write!(formatter, "a BARE encoded variable-length integer")
             }
 
-            fn visit_seq<A>(self, mut seq: A) -> std::result::Result<Self::Value, A::Error>
+            /// This crate's deserializers read the whole integer themselves, and report malformed
+            /// ones with their own error variants rather than formatting a message.
+            fn visit_u64<E>(self, v: u64) -> core::result::Result<Self::Value, E> {
+                Ok(Uint(v))
+            }
+
+            fn visit_seq<A>(self, mut seq: A) -> core::result::Result<Self::Value, A::Error>
             where
                 A: serde::de::SeqAccess<'de>,
             {
-                let mut x = 0u64;
-                let mut s = 0usize;
-                for i in 0.. {
-                    let b = seq.next_element::<u8>()?;
-                    if let Some(b) = b {
-                        if i > 9 || i == 9 && b > 1 {
-                            // No more than 10 bytes can be in a BARE uint/int
-                            return Err(serde::de::Error::custom(
-                                "continuation bit indicated an invalid variable-length integer",
-                            ));
-                        }
-                        if b < 0x80 {
-                            // No continuation bit is set
-                            return Ok(Uint(x | (b as u64) << s));
-                        }
-                        x |= ((b & 0x7f) as u64) << s;
-                        s += 7;
-                    } else {
-                        // Since we're calling next_element for u8 it's probably impossible to
-                        // enter this branch without having raised an io::Error earlier, but better
-                        // to handle it anyway instead of introducing a potential panic
-                        return Err(serde::de::Error::custom(
+                // Read up to the last byte, or as far as MAX_VARINT_LEN, and let decode_uint
+                // check what was read.
+                let mut buf = [0u8; MAX_VARINT_LEN];
+                let mut len = 0;
+                loop {
+                    // Since we're calling next_element for u8 it's probably impossible to get
+                    // None without having raised an io::Error earlier, but better to handle it
+                    // anyway instead of introducing a potential panic
+                    let b = seq.next_element::<u8>()?.ok_or_else(|| {
+                        serde::de::Error::custom(
                             "expected further bytes in variable-length integer",
-                        ));
+                        )
+                    })?;
+                    buf[len] = b;
+                    len += 1;
+                    if b < 0x80 || len == MAX_VARINT_LEN {
+                        return decode_uint(&buf[..len])
+                            .map(|(x, _)| Uint(x))
+                            .map_err(serde::de::Error::custom);
                     }
                 }
-                unreachable!()
             }
         }
-        deserializer.deserialize_tuple(usize::MAX, UintVisitor)
+        deserializer.deserialize_tuple_struct(de::UINT, MAX_VARINT_LEN, UintVisitor)
     }
 }
 
 #[derive(Copy, Clone, Debug, Ord, PartialOrd, Eq, PartialEq)]
 pub struct Int(pub i64);
 
+#[allow(clippy::derivable_impls)]
 impl Default for Int {
     fn default() -> Int {
         Int(0)
     }
 }
 
+impl Int {
+    /// The number of bytes this value takes up when serialized, from 1 to `MAX_VARINT_LEN`.
+    pub const fn encoded_len(self) -> usize {
+        let Int(x) = self;
+        Uint(((x as u64) << 1) ^ ((x >> 63) as u64)).encoded_len()
+    }
+}
+
 impl serde::ser::Serialize for Int {
-    fn serialize<S>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error>
+    fn serialize<S>(&self, serializer: S) -> core::result::Result<S::Ok, S::Error>
     where
         S: serde::ser::Serializer,
     {
@@ -203,7 +481,7 @@ impl serde::ser::Serialize for Int {
 }
 
 impl<'de> serde::de::Deserialize<'de> for Int {
-    fn deserialize<D>(deserializer: D) -> std::result::Result<Self, D::Error>
+    fn deserialize<D>(deserializer: D) -> core::result::Result<Self, D::Error>
     where
         D: serde::de::Deserializer<'de>,
     {
@@ -216,11 +494,286 @@ impl<'de> serde::de::Deserialize<'de> for Int {
     }
 }
 
+#[cfg(feature = "arbitrary")]
+impl<'a> arbitrary::Arbitrary<'a> for Uint {
+    fn arbitrary(u: &mut arbitrary::Unstructured<'a>) -> arbitrary::Result<Self> {
+        u64::arbitrary(u).map(Uint)
+    }
+
+    fn size_hint(depth: usize) -> (usize, Option<usize>) {
+        u64::size_hint(depth)
+    }
+}
+
+#[cfg(feature = "arbitrary")]
+impl<'a> arbitrary::Arbitrary<'a> for Int {
+    fn arbitrary(u: &mut arbitrary::Unstructured<'a>) -> arbitrary::Result<Self> {
+        i64::arbitrary(u).map(Int)
+    }
+
+    fn size_hint(depth: usize) -> (usize, Option<usize>) {
+        i64::size_hint(depth)
+    }
+}
+
+/// A BARE `f32`, encoded exactly like a plain `f32`. Use it where the schema says `f32`, so that
+/// the field can't silently widen to `f64`.
+#[derive(Copy, Clone, Debug, Default, PartialOrd, PartialEq)]
+pub struct F32(pub f32);
+
+impl serde::ser::Serialize for F32 {
+    fn serialize<S>(&self, serializer: S) -> core::result::Result<S::Ok, S::Error>
+    where
+        S: serde::ser::Serializer,
+    {
+        serializer.serialize_f32(self.0)
+    }
+}
+
+impl<'de> serde::de::Deserialize<'de> for F32 {
+    fn deserialize<D>(deserializer: D) -> core::result::Result<Self, D::Error>
+    where
+        D: serde::de::Deserializer<'de>,
+    {
+        <f32 as serde::de::Deserialize>::deserialize(deserializer).map(F32)
+    }
+}
+
+/// A BARE `f64`, encoded exactly like a plain `f64`. The counterpart of [`F32`].
+#[derive(Copy, Clone, Debug, Default, PartialOrd, PartialEq)]
+pub struct F64(pub f64);
+
+impl serde::ser::Serialize for F64 {
+    fn serialize<S>(&self, serializer: S) -> core::result::Result<S::Ok, S::Error>
+    where
+        S: serde::ser::Serializer,
+    {
+        serializer.serialize_f64(self.0)
+    }
+}
+
+impl<'de> serde::de::Deserialize<'de> for F64 {
+    fn deserialize<D>(deserializer: D) -> core::result::Result<Self, D::Error>
+    where
+        D: serde::de::Deserializer<'de>,
+    {
+        <f64 as serde::de::Deserialize>::deserialize(deserializer).map(F64)
+    }
+}
+
+/// Fixed-length data, serialized as BARE `data<N>`: exactly `N` bytes with no length prefix.
+#[derive(Copy, Clone, Debug, Ord, PartialOrd, Eq, PartialEq, Hash)]
+pub struct Data<const N: usize>(pub [u8; N]);
+
+impl<const N: usize> Default for Data<N> {
+    fn default() -> Data<N> {
+        Data([0; N])
+    }
+}
+
+impl<const N: usize> serde::ser::Serialize for Data<N> {
+    fn serialize<S>(&self, serializer: S) -> core::result::Result<S::Ok, S::Error>
+    where
+        S: serde::ser::Serializer,
+    {
+        use serde::ser::SerializeTuple;
+
+        let mut s = serializer.serialize_tuple(N)?;
+        for b in self.0.iter() {
+            s.serialize_element(b)?;
+        }
+        s.end()
+    }
+}
+
+impl<'de, const N: usize> serde::de::Deserialize<'de> for Data<N> {
+    fn deserialize<D>(deserializer: D) -> core::result::Result<Self, D::Error>
+    where
+        D: serde::de::Deserializer<'de>,
+    {
+        use core::fmt;
+
+        struct DataVisitor<const N: usize>;
+        impl<'de, const N: usize> serde::de::Visitor<'de> for DataVisitor<N> {
+            type Value = Data<N>;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                write!(formatter, "{} bytes of BARE fixed-length data", N)
+            }
+
+            fn visit_seq<A>(self, mut seq: A) -> core::result::Result<Self::Value, A::Error>
+            where
+                A: serde::de::SeqAccess<'de>,
+            {
+                let mut data = [0u8; N];
+                for (i, b) in data.iter_mut().enumerate() {
+                    *b = seq
+                        .next_element()?
+                        .ok_or_else(|| serde::de::Error::invalid_length(i, &self))?;
+                }
+                Ok(Data(data))
+            }
+        }
+        deserializer.deserialize_tuple(N, DataVisitor::<N>)
+    }
+}
+
+/// A fixed-length array, serialized as BARE `[N]type`: `N` values with no length prefix.
+/// Elements may be of any type, including variable-width ones. See [`Data`] for `data<N>`.
+#[derive(Copy, Clone, Debug, Ord, PartialOrd, Eq, PartialEq, Hash)]
+pub struct FixedSeq<T, const N: usize>(pub [T; N]);
+
+impl<T, const N: usize> serde::ser::Serialize for FixedSeq<T, N>
+where
+    T: serde::ser::Serialize,
+{
+    fn serialize<S>(&self, serializer: S) -> core::result::Result<S::Ok, S::Error>
+    where
+        S: serde::ser::Serializer,
+    {
+        use serde::ser::SerializeTuple;
+
+        let mut s = serializer.serialize_tuple(N)?;
+        for element in self.0.iter() {
+            s.serialize_element(element)?;
+        }
+        s.end()
+    }
+}
+
+impl<'de, T, const N: usize> serde::de::Deserialize<'de> for FixedSeq<T, N>
+where
+    T: serde::de::Deserialize<'de>,
+{
+    fn deserialize<D>(deserializer: D) -> core::result::Result<Self, D::Error>
+    where
+        D: serde::de::Deserializer<'de>,
+    {
+        use core::{convert::TryInto, fmt, marker::PhantomData};
+
+        struct FixedSeqVisitor<T, const N: usize>(PhantomData<T>);
+        impl<'de, T, const N: usize> serde::de::Visitor<'de> for FixedSeqVisitor<T, N>
+        where
+            T: serde::de::Deserialize<'de>,
+        {
+            type Value = FixedSeq<T, N>;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                write!(formatter, "a BARE fixed-length array of {} elements", N)
+            }
+
+            fn visit_seq<A>(self, mut seq: A) -> core::result::Result<Self::Value, A::Error>
+            where
+                A: serde::de::SeqAccess<'de>,
+            {
+                let mut elements = alloc::vec::Vec::with_capacity(N);
+                for i in 0..N {
+                    let element = seq
+                        .next_element()?
+                        .ok_or_else(|| serde::de::Error::invalid_length(i, &self))?;
+                    elements.push(element);
+                }
+                elements
+                    .try_into()
+                    .map(FixedSeq)
+                    .map_err(|_| serde::de::Error::invalid_length(N, &self))
+            }
+        }
+        deserializer.deserialize_tuple(N, FixedSeqVisitor::<T, N>(PhantomData))
+    }
+}
+
+/// A byte buffer serialized as BARE `data` in one write, and read back in one go.
+///
+/// serde serializes a plain `Vec<u8>` as a `seq` of `u8`, which has the same encoding but goes
+/// through the serializer a byte at a time. `ByteBuf` derefs to the `Vec<u8>` it wraps, so it
+/// can stand in for one as a field type without `serde_bytes`.
+#[derive(Clone, Debug, Default, Ord, PartialOrd, Eq, PartialEq, Hash)]
+pub struct ByteBuf(pub alloc::vec::Vec<u8>);
+
+impl core::ops::Deref for ByteBuf {
+    type Target = alloc::vec::Vec<u8>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl core::ops::DerefMut for ByteBuf {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
+impl From<alloc::vec::Vec<u8>> for ByteBuf {
+    fn from(bytes: alloc::vec::Vec<u8>) -> Self {
+        ByteBuf(bytes)
+    }
+}
+
+impl From<ByteBuf> for alloc::vec::Vec<u8> {
+    fn from(bytes: ByteBuf) -> Self {
+        bytes.0
+    }
+}
+
+impl serde::ser::Serialize for ByteBuf {
+    fn serialize<S>(&self, serializer: S) -> core::result::Result<S::Ok, S::Error>
+    where
+        S: serde::ser::Serializer,
+    {
+        serializer.serialize_bytes(&self.0)
+    }
+}
+
+impl<'de> serde::de::Deserialize<'de> for ByteBuf {
+    fn deserialize<D>(deserializer: D) -> core::result::Result<Self, D::Error>
+    where
+        D: serde::de::Deserializer<'de>,
+    {
+        use core::fmt;
+
+        struct ByteBufVisitor;
+        impl<'de> serde::de::Visitor<'de> for ByteBufVisitor {
+            type Value = ByteBuf;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                write!(formatter, "BARE data")
+            }
+
+            fn visit_bytes<E>(self, v: &[u8]) -> core::result::Result<Self::Value, E> {
+                Ok(ByteBuf(v.to_vec()))
+            }
+
+            fn visit_byte_buf<E>(
+                self,
+                v: alloc::vec::Vec<u8>,
+            ) -> core::result::Result<Self::Value, E> {
+                Ok(ByteBuf(v))
+            }
+
+            /// For formats that hand bytes over as a sequence.
+            fn visit_seq<A>(self, mut seq: A) -> core::result::Result<Self::Value, A::Error>
+            where
+                A: serde::de::SeqAccess<'de>,
+            {
+                let mut bytes = alloc::vec::Vec::new();
+                while let Some(b) = seq.next_element()? {
+                    bytes.push(b);
+                }
+                Ok(ByteBuf(bytes))
+            }
+        }
+        deserializer.deserialize_byte_buf(ByteBufVisitor)
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
 
     #[test]
+    #[allow(clippy::redundant_static_lifetimes)]
     fn test_int() {
         const CASES: &'static [(i64, &'static [u8])] = &[
             (0, &[0]),
@@ -233,12 +786,19 @@ mod test {
             let int = Int(n);
             let got_bytes = to_vec(&int).unwrap();
             assert_eq!(got_bytes, bytes);
+            assert_eq!(int.encoded_len(), bytes.len());
             let got_int = from_slice::<Int>(&got_bytes).unwrap();
             assert_eq!(got_int, int);
+
+            // Ints are zigzag-encoded uints.
+            let (zigzag, len) = decode_uint(bytes).unwrap();
+            assert_eq!(len, bytes.len());
+            assert_eq!(to_vec(&Uint(zigzag)).unwrap(), bytes);
         }
     }
 
     #[test]
+    #[allow(clippy::redundant_static_lifetimes)]
     fn test_uint() {
         const CASES: &'static [(u64, &'static [u8])] = &[
             (0, &[0]),
@@ -251,28 +811,191 @@ mod test {
             let int = Uint(n);
             let got_bytes = to_vec(&int).unwrap();
             assert_eq!(got_bytes, bytes);
+            assert_eq!(int.encoded_len(), bytes.len());
             let got_int = from_slice::<Uint>(&got_bytes).unwrap();
             assert_eq!(got_int, int);
+
+            let mut buf = [0xaa; MAX_VARINT_LEN];
+            assert_eq!(encode_uint(n, &mut buf), bytes.len());
+            assert_eq!(&buf[..bytes.len()], bytes);
+            assert_eq!(decode_uint(bytes).unwrap(), (n, bytes.len()));
+            let mut trailing = bytes.to_vec();
+            trailing.push(0x80);
+            assert_eq!(decode_uint(&trailing).unwrap(), (n, bytes.len()));
+
+            let mut serializer = Serializer::new(alloc::vec::Vec::new());
+            serializer.write_len(n).unwrap();
+            assert_eq!(serializer.into_inner(), bytes);
+            let mut deserializer = Deserializer::new(bytes);
+            assert_eq!(deserializer.read_len().unwrap(), n);
+            assert!(deserializer.into_inner().is_empty());
         }
     }
 
     #[test]
+    fn test_read_len() {
+        let mut deserializer = Deserializer::new(&[0x80, 0x00, 3][..]);
+        assert_eq!(deserializer.read_len().unwrap(), 0);
+        assert_eq!(deserializer.read_len().unwrap(), 3);
+        assert!(matches!(deserializer.read_len(), Err(Error::Eof)));
+
+        let mut deserializer = Deserializer::new(&[0x80, 0x00][..]);
+        deserializer.strict_lengths();
+        assert!(matches!(
+            deserializer.read_len(),
+            Err(Error::NonCanonicalInt)
+        ));
+        assert!(Deserializer::new(&[0xff; 10][..]).read_len().is_err());
+    }
+
+    #[test]
+    #[allow(clippy::needless_borrow)]
     fn test_uint_too_long() {
         // Too many bytes
         let bytes: &'static [u8] = &[255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 1];
         let result = from_slice::<Uint>(&bytes);
-        assert!(result.is_err());
+        assert!(matches!(result, Err(Error::VarintTooLong)));
+        let result = from_slice_borrowed::<Uint>(bytes);
+        assert!(matches!(result, Err(Error::VarintTooLong)));
+        assert!(matches!(decode_uint(bytes), Err(Error::VarintTooLong)));
 
         // Too many bits of precision (effectively u64::MAX + 1)
         let bytes: &'static [u8] = &[255, 255, 255, 255, 255, 255, 255, 255, 255, 2];
         let result = from_slice::<Uint>(&bytes);
-        assert!(result.is_err());
+        assert!(matches!(result, Err(Error::VarintOverflow)));
+        let result = from_slice::<Int>(bytes);
+        assert!(matches!(result, Err(Error::VarintOverflow)));
+        assert!(matches!(decode_uint(bytes), Err(Error::VarintOverflow)));
+    }
+
+    #[test]
+    fn test_floats() {
+        let values = [0.0, -2.5, 1e-40, f64::INFINITY, f64::MAX];
+        for value in values {
+            let bytes = to_vec(&F64(value)).unwrap();
+            assert_eq!(bytes, to_vec(&value).unwrap());
+            assert_eq!(from_slice::<F64>(&bytes).unwrap(), F64(value));
+
+            let value = value as f32;
+            let bytes = to_vec(&F32(value)).unwrap();
+            assert_eq!(bytes, to_vec(&value).unwrap());
+            assert_eq!(from_slice::<F32>(&bytes).unwrap(), F32(value));
+            assert_eq!(from_slice_borrowed::<F32>(&bytes).unwrap(), F32(value));
+        }
+        assert_eq!(to_vec(&F32(1.0)).unwrap(), [0, 0, 0x80, 0x3f]);
+        assert_eq!(to_vec(&F64(1.0)).unwrap().len(), 8);
+        assert!(from_slice::<F32>(&[0, 0, 0x80]).is_err());
+    }
+
+    #[test]
+    fn test_data() {
+        let uuid = Data::<16>([7; 16]);
+        let bytes = to_vec(&uuid).unwrap();
+        assert_eq!(bytes, &[7; 16]);
+        assert_eq!(from_slice::<Data<16>>(&bytes).unwrap(), uuid);
+
+        let empty = Data::<0>([]);
+        assert!(to_vec(&empty).unwrap().is_empty());
+        assert_eq!(from_slice::<Data<0>>(&[]).unwrap(), empty);
+
+        let mut large = Data::<64>::default();
+        large.0[63] = 1;
+        let bytes = to_vec(&(large, 2u8)).unwrap();
+        assert_eq!(bytes.len(), 65);
+        assert_eq!(from_slice::<(Data<64>, u8)>(&bytes).unwrap(), (large, 2));
+
+        assert!(from_slice::<Data<16>>(&[7; 15]).is_err());
+    }
+
+    #[test]
+    fn test_byte_buf() {
+        use crate::test_util::Writes;
+
+        let buf = ByteBuf((0..200).collect());
+        let bytes = to_vec(&buf).unwrap();
+        assert_eq!(bytes, to_vec(&buf.0).unwrap());
+        assert_eq!(bytes[..2], [200, 1]);
+        assert_eq!(from_slice::<ByteBuf>(&bytes).unwrap(), buf);
+        assert_eq!(from_slice_borrowed::<ByteBuf>(&bytes).unwrap(), buf);
+
+        // The length prefix, then all of the data at once.
+        let mut writes = Writes(alloc::vec::Vec::new());
+        to_writer(&mut writes, &buf).unwrap();
+        assert_eq!(writes.0, [2, 200]);
     }
 
     #[test]
+    fn test_fixed_seq() {
+        let seq = FixedSeq(["a".to_string(), "".to_string(), "ccc".to_string()]);
+        let bytes = to_vec(&seq).unwrap();
+        assert_eq!(bytes, &[1, b'a', 0, 3, b'c', b'c', b'c']);
+        assert_eq!(from_slice::<FixedSeq<String, 3>>(&bytes).unwrap(), seq);
+
+        assert!(to_vec(&FixedSeq::<String, 0>([])).unwrap().is_empty());
+        assert!(from_slice::<FixedSeq<String, 4>>(&bytes).is_err());
+    }
+
+    #[test]
+    fn test_varint_len() {
+        let table = [
+            (0, 1),
+            (127, 1),
+            (128, 2),
+            (16383, 2),
+            (16384, 3),
+            ((1 << 56) - 1, 8),
+            (1 << 56, 9),
+            ((1 << 63) - 1, 9),
+            (1 << 63, 10),
+            (u64::MAX, MAX_VARINT_LEN),
+        ];
+        for (value, len) in table {
+            assert_eq!(varint_len(value), len, "{}", value);
+            assert_eq!(to_vec(&Uint(value)).unwrap().len(), len, "{}", value);
+        }
+    }
+
+    #[test]
+    #[allow(clippy::needless_borrow)]
     fn test_uint_too_short() {
         let bytes: &'static [u8] = &[255, 255, 255];
         let result = from_slice::<Uint>(&bytes);
-        assert!(result.is_err());
+        assert!(matches!(result, Err(Error::VarintTruncated)));
+        let result = from_slice_borrowed::<Uint>(bytes);
+        assert!(matches!(result, Err(Error::VarintTruncated)));
+        assert!(matches!(decode_uint(bytes), Err(Error::VarintTruncated)));
+
+        // Nothing at all is the end of the input, as for any other type.
+        assert!(matches!(from_slice::<Uint>(&[]), Err(Error::Eof)));
+        assert!(matches!(decode_uint(&[]), Err(Error::Eof)));
+    }
+
+    #[cfg(feature = "arbitrary")]
+    #[test]
+    fn test_arbitrary() {
+        use arbitrary::{Arbitrary, Unstructured};
+
+        let seed = [
+            0x80, 0x01, 0xff, 0x7f, 0x00, 0x00, 0x00, 0x00, 0xff, 0xff, 0xff, 0xff,
+        ];
+        let draw = || {
+            let mut u = Unstructured::new(&seed);
+            (
+                Uint::arbitrary(&mut u).unwrap(),
+                Int::arbitrary(&mut u).unwrap(),
+            )
+        };
+        let (uint, int) = draw();
+        assert_eq!(draw(), (uint, int));
+        assert_eq!(from_slice::<Uint>(&to_vec(&uint).unwrap()).unwrap(), uint);
+        assert_eq!(from_slice::<Int>(&to_vec(&int).unwrap()).unwrap(), int);
+
+        let mut u = Unstructured::new(&[0xff; 16]);
+        assert_eq!(Uint::arbitrary(&mut u).unwrap(), Uint(u64::MAX));
+        assert_eq!(Int::arbitrary(&mut u).unwrap(), Int(-1));
+        // Running out of input fills in zeros.
+        let mut u = Unstructured::new(&[]);
+        assert_eq!(Uint::arbitrary(&mut u).unwrap(), Uint(0));
+        assert_eq!(Int::arbitrary(&mut u).unwrap(), Int(0));
     }
 }
@@ -0,0 +1,209 @@
+//! Compact encodings for IP and socket addresses, for use with `#[serde(with = "...")]`.
+//!
+//! serde's own impls encode addresses as unions, with a `uint` tag and a `[N]u8` address; these
+//! leave the tag out where only one family is possible, and use a `u8` tag where it isn't.
+
+/// `Ipv4Addr` as `data<4>`, in network order: `127.0.0.1` is `7f 00 00 01`.
+pub mod ipv4_addr {
+    use crate::Data;
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+    use std::net::Ipv4Addr;
+
+    pub fn serialize<S>(value: &Ipv4Addr, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        Data(value.octets()).serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<Ipv4Addr, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let Data(octets) = Data::<4>::deserialize(deserializer)?;
+        Ok(Ipv4Addr::from(octets))
+    }
+}
+
+/// `Ipv6Addr` as `data<16>`, in network order.
+pub mod ipv6_addr {
+    use crate::Data;
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+    use std::net::Ipv6Addr;
+
+    pub fn serialize<S>(value: &Ipv6Addr, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        Data(value.octets()).serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<Ipv6Addr, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let Data(octets) = Data::<16>::deserialize(deserializer)?;
+        Ok(Ipv6Addr::from(octets))
+    }
+}
+
+/// `IpAddr` as a `u8` tag followed by the address: `4` and then `data<4>` for IPv4, or `6` and
+/// then `data<16>` for IPv6. Any other tag fails to deserialize.
+pub mod ip_addr {
+    use crate::Data;
+    use core::fmt;
+    use serde::{
+        de::{Error as _, SeqAccess, Visitor},
+        Deserializer, Serialize, Serializer,
+    };
+    use std::net::IpAddr;
+
+    pub fn serialize<S>(value: &IpAddr, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        match value {
+            IpAddr::V4(addr) => (4u8, Data(addr.octets())).serialize(serializer),
+            IpAddr::V6(addr) => (6u8, Data(addr.octets())).serialize(serializer),
+        }
+    }
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<IpAddr, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_tuple(2, IpAddrVisitor)
+    }
+
+    struct IpAddrVisitor;
+
+    impl<'de> Visitor<'de> for IpAddrVisitor {
+        type Value = IpAddr;
+
+        fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+            formatter.write_str("an IP address tag followed by an address")
+        }
+
+        fn visit_seq<A>(self, mut seq: A) -> Result<IpAddr, A::Error>
+        where
+            A: SeqAccess<'de>,
+        {
+            let tag: u8 = seq
+                .next_element()?
+                .ok_or_else(|| A::Error::invalid_length(0, &self))?;
+            let addr = match tag {
+                4 => seq
+                    .next_element::<Data<4>>()?
+                    .map(|Data(octets)| IpAddr::from(octets)),
+                6 => seq
+                    .next_element::<Data<16>>()?
+                    .map(|Data(octets)| IpAddr::from(octets)),
+                _ => {
+                    return Err(A::Error::custom(format_args!(
+                        "invalid IP address tag {}",
+                        tag
+                    )))
+                }
+            };
+            addr.ok_or_else(|| A::Error::invalid_length(1, &self))
+        }
+    }
+}
+
+/// `SocketAddr` as the BARE struct `{ ip: IpAddr, port: u16 }`, with `ip` encoded as in
+/// [`ip_addr`](super::ip_addr).
+///
+/// The flow info and scope ID of IPv6 socket addresses aren't encoded, and deserialize as 0.
+pub mod socket_addr {
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+    use std::net::{IpAddr, SocketAddr};
+
+    struct Ip(IpAddr);
+
+    impl Serialize for Ip {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            super::ip_addr::serialize(&self.0, serializer)
+        }
+    }
+
+    impl<'de> Deserialize<'de> for Ip {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            super::ip_addr::deserialize(deserializer).map(Ip)
+        }
+    }
+
+    pub fn serialize<S>(value: &SocketAddr, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        (Ip(value.ip()), value.port()).serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<SocketAddr, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let (Ip(ip), port) = <(Ip, u16)>::deserialize(deserializer)?;
+        Ok(SocketAddr::new(ip, port))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use serde_derive::{Deserialize, Serialize};
+    use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    struct Peer {
+        #[serde(with = "super::ipv4_addr")]
+        v4: Ipv4Addr,
+        #[serde(with = "super::ipv6_addr")]
+        v6: Ipv6Addr,
+        #[serde(with = "super::ip_addr")]
+        ip: IpAddr,
+        #[serde(with = "super::socket_addr")]
+        socket: SocketAddr,
+    }
+
+    fn round_trip(peer: Peer) -> Vec<u8> {
+        let bytes = crate::to_vec(&peer).unwrap();
+        assert_eq!(crate::from_slice::<Peer>(&bytes).unwrap(), peer);
+        bytes
+    }
+
+    #[test]
+    fn test_v4() {
+        let bytes = round_trip(Peer {
+            v4: Ipv4Addr::LOCALHOST,
+            v6: Ipv6Addr::UNSPECIFIED,
+            ip: Ipv4Addr::new(192, 168, 0, 1).into(),
+            socket: "10.0.0.2:443".parse().unwrap(),
+        });
+        let mut expected = vec![127, 0, 0, 1];
+        expected.extend_from_slice(&[0; 16]);
+        expected.extend_from_slice(&[4, 192, 168, 0, 1]);
+        expected.extend_from_slice(&[4, 10, 0, 0, 2, 0xbb, 0x01]);
+        assert_eq!(bytes, expected);
+    }
+
+    #[test]
+    fn test_v6() {
+        let bytes = round_trip(Peer {
+            v4: Ipv4Addr::UNSPECIFIED,
+            v6: Ipv6Addr::LOCALHOST,
+            ip: "2001:db8::1".parse().unwrap(),
+            socket: "[::1]:8080".parse().unwrap(),
+        });
+        assert_eq!(bytes.len(), 4 + 16 + 17 + 19);
+        assert_eq!(&bytes[4..20], &Ipv6Addr::LOCALHOST.octets());
+        assert_eq!(bytes[20], 6);
+        assert_eq!(&bytes[21..25], &[0x20, 0x01, 0x0d, 0xb8]);
+        assert_eq!(bytes[37], 6);
+        assert_eq!(&bytes[54..], &[0x90, 0x1f]);
+
+        // Neither 4 nor 6
+        let mut bad_tag = bytes.clone();
+        bad_tag[20] = 5;
+        assert!(crate::from_slice::<Peer>(&bad_tag).is_err());
+    }
+}
@@ -0,0 +1,82 @@
+//! `proptest` strategies for [`Uint`] and [`Int`], and a round trip assertion for property tests.
+//!
+//! The strategies mix uniformly random values with the values on either side of each varint
+//! length boundary, where an encoding goes from `n` to `n + 1` bytes.
+use crate::{from_slice, to_vec, Int, Uint};
+use ::proptest::{prelude::*, sample::select};
+use core::fmt::Debug;
+use serde::{de::DeserializeOwned, Serialize};
+
+/// 0, then `128^n - 1` and `128^n` for each `n` from 1 to 9, then `u64::MAX`.
+fn boundaries() -> Vec<u64> {
+    let mut values = vec![0];
+    for n in 1..10 {
+        let boundary = 1u64 << (7 * n);
+        values.push(boundary - 1);
+        values.push(boundary);
+    }
+    values.push(u64::MAX);
+    values
+}
+
+/// Any `Uint`, with extra weight on 0, 127, 128, 16383, 16384 and so on up to `u64::MAX`.
+pub fn uint() -> impl Strategy<Value = Uint> {
+    prop_oneof![select(boundaries()), any::<u64>()].prop_map(Uint)
+}
+
+/// Any `Int`, with extra weight on the values whose zigzag encodings are on a length boundary,
+/// from -1, 0 and 63 up to `i64::MIN` and `i64::MAX`.
+pub fn int() -> impl Strategy<Value = Int> {
+    let boundaries = boundaries()
+        .into_iter()
+        .map(|ux| ((ux >> 1) as i64) ^ -((ux & 1) as i64))
+        .collect::<Vec<_>>();
+    prop_oneof![select(boundaries), any::<i64>()].prop_map(Int)
+}
+
+/// Panic unless `value` serializes, deserializes from exactly those bytes, and compares equal
+/// to the result.
+pub fn assert_roundtrips<T>(value: T)
+where
+    T: Serialize + DeserializeOwned + PartialEq + Debug,
+{
+    let bytes = to_vec(&value).expect("failed to serialize");
+    let got = from_slice::<T>(&bytes).expect("failed to deserialize");
+    assert_eq!(got, value, "round trip through {:02x?}", bytes);
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_boundaries() {
+        let uints = boundaries();
+        for expected in [0, 127, 128, 16383, 16384, u64::MAX] {
+            assert!(uints.contains(&expected));
+        }
+        for pair in uints[1..19].chunks(2) {
+            assert_eq!(Uint(pair[0]).encoded_len() + 1, Uint(pair[1]).encoded_len());
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn uint_roundtrips(x in uint()) {
+            prop_assert_eq!(to_vec(&x).unwrap().len(), x.encoded_len());
+            assert_roundtrips(x);
+        }
+
+        #[test]
+        fn int_roundtrips(x in int()) {
+            prop_assert_eq!(to_vec(&x).unwrap().len(), x.encoded_len());
+            assert_roundtrips(x);
+        }
+
+        #[test]
+        fn truncated_uint_fails(x in uint()) {
+            let bytes = to_vec(&x).unwrap();
+            prop_assert!(from_slice::<Uint>(&bytes[..bytes.len() - 1]).is_err());
+        }
+    }
+}
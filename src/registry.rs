@@ -0,0 +1,71 @@
+//! Decoding values whose type is only known at runtime by its `TypeId`.
+use crate::{de::from_slice, error::Error};
+use alloc::{boxed::Box, collections::BTreeMap};
+use core::any::{Any, TypeId};
+use serde::de::DeserializeOwned;
+
+type Decoder = fn(&[u8]) -> Result<Box<dyn Any>, Error>;
+
+/// Maps `TypeId`s to type-erased `from_slice` functions.
+#[derive(Default)]
+pub struct TypeRegistry {
+    decoders: BTreeMap<TypeId, Decoder>,
+}
+
+impl TypeRegistry {
+    pub fn new() -> Self {
+        TypeRegistry::default()
+    }
+
+    /// Register a decoder for `T`, replacing any existing one.
+    pub fn register<T>(&mut self)
+    where
+        T: DeserializeOwned + Any,
+    {
+        self.decoders.insert(TypeId::of::<T>(), |bytes| {
+            from_slice::<T>(bytes).map(|value| Box::new(value) as Box<dyn Any>)
+        });
+    }
+
+    pub fn contains(&self, type_id: TypeId) -> bool {
+        self.decoders.contains_key(&type_id)
+    }
+}
+
+/// Decode `bytes` as the type identified by `type_id`.
+/// Error::UnregisteredType if `registry` has no decoder for it.
+pub fn decode_as(
+    type_id: TypeId,
+    bytes: &[u8],
+    registry: &TypeRegistry,
+) -> Result<Box<dyn Any>, Error> {
+    let decode = registry
+        .decoders
+        .get(&type_id)
+        .ok_or(Error::UnregisteredType)?;
+    decode(bytes)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_decode_as() {
+        let mut registry = TypeRegistry::new();
+        registry.register::<u32>();
+        registry.register::<String>();
+
+        let value = decode_as(TypeId::of::<u32>(), &[1, 2, 3, 4], &registry).unwrap();
+        assert_eq!(value.downcast_ref::<u32>(), Some(&67305985));
+
+        let value = decode_as(TypeId::of::<String>(), &[2, b'h', b'i'], &registry).unwrap();
+        assert_eq!(value.downcast_ref::<String>().map(|s| &**s), Some("hi"));
+
+        assert!(matches!(
+            decode_as(TypeId::of::<u8>(), &[1], &registry),
+            Err(Error::UnregisteredType)
+        ));
+        assert!(decode_as(TypeId::of::<u32>(), &[1], &registry).is_err());
+    }
+}
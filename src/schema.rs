@@ -0,0 +1,497 @@
+//! Describe Rust types as a `.bare` schema.
+//!
+//! [`BareSchema`] gives the BARE type a Rust type serializes as. It's implemented for the types
+//! this crate maps from the serde data model, and can be derived for structs and enums with the
+//! `derive` feature. [`bare_schema`] collects the declarations of a type and every named type it
+//! refers to into a schema other BARE implementations can generate code from.
+//!
+//! Derived schemas follow serde's derive: structs keep their field order, enums whose variants
+//! are all units become BARE enums, and other enums become unions tagged with the variant index.
+//! Enums whose variants have `#[bare(tag = N)]` attributes are numbered with the tags
+//! [`BareUnion`](crate::tags::BareUnion) gives them instead. Fields marked `#[serde(skip)]` are
+//! left out, fields with `#[serde(with = "serde_bytes")]` are `data`, and `#[bare(schema = "...")]`
+//! overrides the type of a field. Other serde attributes that change the encoding aren't taken into
+//! account.
+use crate::{ByteBuf, Data, FixedSeq, Int, Uint, F32, F64};
+use alloc::{
+    borrow::ToOwned,
+    boxed::Box,
+    collections::{BTreeMap, BTreeSet},
+    format,
+    rc::Rc,
+    sync::Arc,
+    vec::Vec,
+};
+
+#[doc(hidden)]
+pub use alloc::string::String;
+
+/// A Rust type with a BARE schema.
+pub trait BareSchema {
+    /// Returns the BARE type of `Self`, declaring any named types it refers to in `schema`.
+    fn bare_type(schema: &mut SchemaBuilder) -> String;
+}
+
+/// Returns the schema for `T` and the named types it refers to.
+/// Types are declared before the types that refer to them.
+pub fn bare_schema<T: ?Sized + BareSchema>() -> String {
+    let mut schema = SchemaBuilder::default();
+    T::bare_type(&mut schema);
+    schema.declarations.join("\n")
+}
+
+/// The field names and types of a struct, for `SchemaBuilder::struct_type`.
+pub type Fields<'a> = &'a [(&'a str, fn(&mut SchemaBuilder) -> String)];
+
+/// Collects the declarations of named types.
+#[derive(Default)]
+pub struct SchemaBuilder {
+    declarations: Vec<String>,
+    names: BTreeSet<String>,
+}
+
+impl SchemaBuilder {
+    /// Declare `type name body()` unless `name` is declared already, and return `name`.
+    /// A type referring to itself sees its own name without being declared twice.
+    pub fn declare<F>(&mut self, name: &str, body: F) -> String
+    where
+        F: FnOnce(&mut SchemaBuilder) -> String,
+    {
+        if self.names.insert(name.to_owned()) {
+            let body = body(self);
+            self.declarations.push(format!("type {} {}\n", name, body));
+        }
+        name.to_owned()
+    }
+
+    /// Declare a BARE enum whose values are numbered from 0 in order, and return `name`.
+    pub fn declare_enum(&mut self, name: &str, values: &[&str]) -> String {
+        self.enum_declaration(name, None, values)
+    }
+
+    /// Declare a BARE enum whose values are numbered with `tags`, as
+    /// [`Union`](crate::tags::Union) writes them, and return `name`.
+    pub fn declare_tagged_enum(&mut self, name: &str, tags: &[u32], values: &[&str]) -> String {
+        self.enum_declaration(name, Some(tags), values)
+    }
+
+    fn enum_declaration(&mut self, name: &str, tags: Option<&[u32]>, values: &[&str]) -> String {
+        if self.names.insert(name.to_owned()) {
+            let mut declaration = format!("enum {} {{\n", name);
+            for (i, value) in values.iter().enumerate() {
+                declaration.push_str(&format!("  {}{}\n", value, tag_suffix(tags, i)));
+            }
+            declaration.push_str("}\n");
+            self.declarations.push(declaration);
+        }
+        name.to_owned()
+    }
+
+    /// Declare a union whose members are tagged from 0 in order, and return `name`.
+    /// Members that aren't a named type already are declared under the given name, and so are
+    /// members whose named type is shared with another member, since the types in a union have
+    /// to be distinct.
+    pub fn declare_union(&mut self, name: &str, members: Fields) -> String {
+        self.union_declaration(name, None, members)
+    }
+
+    /// Like `declare_union`, but with members tagged with `tags`, as
+    /// [`Union`](crate::tags::Union) writes them.
+    pub fn declare_tagged_union(&mut self, name: &str, tags: &[u32], members: Fields) -> String {
+        self.union_declaration(name, Some(tags), members)
+    }
+
+    fn union_declaration(&mut self, name: &str, tags: Option<&[u32]>, members: Fields) -> String {
+        self.declare(name, |schema| {
+            let types: Vec<String> = members
+                .iter()
+                .map(|(member, ty)| {
+                    let ty = ty(schema);
+                    if is_named(&ty) {
+                        ty
+                    } else {
+                        schema.declare(member, |_| ty)
+                    }
+                })
+                .collect();
+            let members: Vec<String> = members
+                .iter()
+                .zip(&types)
+                .enumerate()
+                .map(|(i, ((member, _), ty))| {
+                    let ty = if types.iter().filter(|other| *other == ty).count() > 1 {
+                        schema.declare(member, |_| ty.clone())
+                    } else {
+                        ty.clone()
+                    };
+                    format!("{}{}", ty, tag_suffix(tags, i))
+                })
+                .collect();
+            format!("({})", members.join(" | "))
+        })
+    }
+
+    /// Returns a struct type with the given fields.
+    pub fn struct_type(&mut self, fields: Fields) -> String {
+        let mut body = String::from("{\n");
+        for (field, ty) in fields {
+            body.push_str(&format!("  {}: {}\n", field, ty(self)));
+        }
+        body.push('}');
+        body
+    }
+}
+
+/// ` = N` for the value or member at `i`, if it has an explicit tag.
+fn tag_suffix(tags: Option<&[u32]>, i: usize) -> String {
+    match tags.and_then(|tags| tags.get(i)) {
+        Some(tag) => format!(" = {}", tag),
+        None => String::new(),
+    }
+}
+
+fn is_named(ty: &str) -> bool {
+    ty.starts_with(|c: char| c.is_ascii_uppercase())
+}
+
+macro_rules! impl_primitive {
+    ($($ty:ty => $bare:expr,)*) => {
+        $(
+            impl BareSchema for $ty {
+                fn bare_type(_: &mut SchemaBuilder) -> String {
+                    String::from($bare)
+                }
+            }
+        )*
+    };
+}
+
+impl_primitive! {
+    bool => "bool",
+    u8 => "u8",
+    u16 => "u16",
+    u32 => "u32",
+    u64 => "u64",
+    i8 => "i8",
+    i16 => "i16",
+    i32 => "i32",
+    i64 => "i64",
+    u128 => "data<16>",
+    i128 => "data<16>",
+    f32 => "f32",
+    f64 => "f64",
+    F32 => "f32",
+    F64 => "f64",
+    char => "u32",
+    str => "string",
+    String => "string",
+    () => "void",
+    Uint => "uint",
+    Int => "int",
+    ByteBuf => "data",
+}
+
+impl<const N: usize> BareSchema for Data<N> {
+    fn bare_type(_: &mut SchemaBuilder) -> String {
+        format!("data<{}>", N)
+    }
+}
+
+impl<T: BareSchema, const N: usize> BareSchema for FixedSeq<T, N> {
+    fn bare_type(schema: &mut SchemaBuilder) -> String {
+        format!("[{}]{}", N, T::bare_type(schema))
+    }
+}
+
+impl<T: BareSchema, const N: usize> BareSchema for [T; N] {
+    fn bare_type(schema: &mut SchemaBuilder) -> String {
+        format!("[{}]{}", N, T::bare_type(schema))
+    }
+}
+
+impl<T: BareSchema> BareSchema for [T] {
+    fn bare_type(schema: &mut SchemaBuilder) -> String {
+        format!("[]{}", T::bare_type(schema))
+    }
+}
+
+impl<T: BareSchema> BareSchema for Vec<T> {
+    fn bare_type(schema: &mut SchemaBuilder) -> String {
+        format!("[]{}", T::bare_type(schema))
+    }
+}
+
+#[cfg(feature = "smallvec")]
+impl<A> BareSchema for smallvec::SmallVec<A>
+where
+    A: smallvec::Array,
+    A::Item: BareSchema,
+{
+    fn bare_type(schema: &mut SchemaBuilder) -> String {
+        format!("[]{}", A::Item::bare_type(schema))
+    }
+}
+
+impl<T: BareSchema> BareSchema for BTreeSet<T> {
+    fn bare_type(schema: &mut SchemaBuilder) -> String {
+        format!("[]{}", T::bare_type(schema))
+    }
+}
+
+impl<T: BareSchema> BareSchema for Option<T> {
+    fn bare_type(schema: &mut SchemaBuilder) -> String {
+        format!("optional<{}>", T::bare_type(schema))
+    }
+}
+
+impl<K: BareSchema, V: BareSchema> BareSchema for BTreeMap<K, V> {
+    fn bare_type(schema: &mut SchemaBuilder) -> String {
+        format!("map[{}]{}", K::bare_type(schema), V::bare_type(schema))
+    }
+}
+
+#[cfg(feature = "std")]
+impl<K: BareSchema, V: BareSchema, S> BareSchema for std::collections::HashMap<K, V, S> {
+    fn bare_type(schema: &mut SchemaBuilder) -> String {
+        format!("map[{}]{}", K::bare_type(schema), V::bare_type(schema))
+    }
+}
+
+#[cfg(feature = "indexmap")]
+impl<K: BareSchema, V: BareSchema, S> BareSchema for indexmap::IndexMap<K, V, S> {
+    fn bare_type(schema: &mut SchemaBuilder) -> String {
+        format!("map[{}]{}", K::bare_type(schema), V::bare_type(schema))
+    }
+}
+
+impl<T: ?Sized + BareSchema> BareSchema for Box<T> {
+    fn bare_type(schema: &mut SchemaBuilder) -> String {
+        T::bare_type(schema)
+    }
+}
+
+impl<T: ?Sized + BareSchema> BareSchema for Rc<T> {
+    fn bare_type(schema: &mut SchemaBuilder) -> String {
+        T::bare_type(schema)
+    }
+}
+
+impl<T: ?Sized + BareSchema> BareSchema for Arc<T> {
+    fn bare_type(schema: &mut SchemaBuilder) -> String {
+        T::bare_type(schema)
+    }
+}
+
+impl<T: ?Sized + BareSchema> BareSchema for &T {
+    fn bare_type(schema: &mut SchemaBuilder) -> String {
+        T::bare_type(schema)
+    }
+}
+
+macro_rules! impl_tuple {
+    ($($name:ident $field:literal)+) => {
+        impl<$($name: BareSchema),+> BareSchema for ($($name,)+) {
+            fn bare_type(schema: &mut SchemaBuilder) -> String {
+                let fields = [$(format!("{}: {}", $field, $name::bare_type(schema))),+];
+                format!("{{ {} }}", fields.join(" "))
+            }
+        }
+    };
+}
+
+impl_tuple!(A "_0");
+impl_tuple!(A "_0" B "_1");
+impl_tuple!(A "_0" B "_1" C "_2");
+impl_tuple!(A "_0" B "_1" C "_2" D "_3");
+impl_tuple!(A "_0" B "_1" C "_2" D "_3" E "_4");
+impl_tuple!(A "_0" B "_1" C "_2" D "_3" E "_4" F "_5");
+impl_tuple!(A "_0" B "_1" C "_2" D "_3" E "_4" F "_5" G "_6");
+impl_tuple!(A "_0" B "_1" C "_2" D "_3" E "_4" F "_5" G "_6" H "_7");
+
+#[cfg(test)]
+#[allow(dead_code)]
+mod test {
+    use super::*;
+    use serde_bare_derive::BareSchema;
+    use serde_derive::Serialize;
+
+    // The benchmark types
+    #[derive(BareSchema, Serialize)]
+    enum UserRole {
+        Admin,
+        User,
+        Guest,
+    }
+
+    #[derive(BareSchema, Serialize)]
+    struct Session {
+        #[serde(with = "serde_bytes")]
+        token: Vec<u8>,
+        expires: u64,
+    }
+
+    #[derive(BareSchema, Serialize)]
+    struct User {
+        id: u32,
+        name: String,
+        email: String,
+        role: UserRole,
+        session: Option<Session>,
+    }
+
+    #[derive(BareSchema, Serialize)]
+    enum Shape {
+        Circle(f64),
+        Square { side: f32 },
+        Session(Session),
+        Empty,
+    }
+
+    #[derive(BareSchema, Serialize)]
+    struct Tree {
+        children: Vec<Tree>,
+        #[serde(skip)]
+        cache: u32,
+        #[bare(schema = "uint")]
+        weight: u64,
+        shapes: BTreeMap<String, (Shape, Data<4>)>,
+    }
+
+    #[test]
+    fn test_user() {
+        assert_eq!(
+            bare_schema::<User>(),
+            "\
+enum UserRole {
+  ADMIN
+  USER
+  GUEST
+}
+
+type Session {
+  token: data
+  expires: u64
+}
+
+type User {
+  id: u32
+  name: string
+  email: string
+  role: UserRole
+  session: optional<Session>
+}
+"
+        );
+    }
+
+    #[test]
+    fn test_union() {
+        assert_eq!(
+            bare_schema::<Tree>(),
+            "\
+type ShapeCircle f64
+
+type ShapeSquare {
+  side: f32
+}
+
+type Session {
+  token: data
+  expires: u64
+}
+
+type ShapeEmpty void
+
+type Shape (ShapeCircle | ShapeSquare | Session | ShapeEmpty)
+
+type Tree {
+  children: []Tree
+  weight: uint
+  shapes: map[string]{ _0: Shape _1: data<4> }
+}
+"
+        );
+    }
+
+    #[test]
+    fn test_union_shared_type() {
+        #[derive(BareSchema, Serialize)]
+        enum Event {
+            Login(Session),
+            Refresh(Session),
+            Logout(u64),
+            Expire(u64),
+        }
+
+        assert_eq!(
+            bare_schema::<Event>(),
+            "\
+type Session {
+  token: data
+  expires: u64
+}
+
+type EventLogout u64
+
+type EventExpire u64
+
+type EventLogin Session
+
+type EventRefresh Session
+
+type Event (EventLogin | EventRefresh | EventLogout | EventExpire)
+"
+        );
+    }
+
+    #[test]
+    fn test_tagged_union() {
+        use crate::tags::{BareUnion, Union};
+
+        #[derive(BareSchema, Serialize, serde_bare_derive::BareUnion)]
+        enum Packet {
+            #[bare(tag = 2)]
+            Ping(u64),
+            Pong(u64),
+            #[bare(tag = 10)]
+            Data(String),
+        }
+
+        #[derive(BareSchema, Serialize, serde_bare_derive::BareUnion)]
+        enum Level {
+            #[bare(tag = 1)]
+            Low,
+            #[bare(tag = 5)]
+            High,
+        }
+
+        assert_eq!(
+            bare_schema::<Packet>(),
+            "\
+type PacketPing u64
+
+type PacketPong u64
+
+type PacketData string
+
+type Packet (PacketPing = 2 | PacketPong = 3 | PacketData = 10)
+"
+        );
+        assert_eq!(Packet::TAGS, &[2, 3, 10]);
+        let bytes = crate::to_vec(&Union(Packet::Data(String::from("hi")))).unwrap();
+        assert_eq!(bytes[0], 10);
+        let bytes = crate::to_vec(&Union(Packet::Pong(0))).unwrap();
+        assert_eq!(bytes[0], 3);
+
+        assert_eq!(
+            bare_schema::<Level>(),
+            "\
+enum Level {
+  LOW = 1
+  HIGH = 5
+}
+"
+        );
+        assert_eq!(crate::to_vec(&Union(Level::High)).unwrap(), [5]);
+    }
+}
@@ -1,30 +1,272 @@
-use crate::{error::Error, Uint};
-use serde::{ser, Serialize};
-use std::io::Write;
+use crate::{
+    compat::io::{self, Write},
+    de::{Deserializer, DeserializerConfig},
+    error::Error,
+    size::BareSize,
+    Uint, CANONICAL_NAN_F32, CANONICAL_NAN_F64, MAX_VARINT_LEN,
+};
+use alloc::{format, vec::Vec};
+use core::fmt::{self, Display};
+use serde::{de::DeserializeOwned, ser, Serialize};
+
+/// The wire order of struct fields as given by a schema. Each entry is a struct name and the
+/// names of its fields, in the order the schema lists them.
+pub type FieldOrder = &'static [(&'static str, &'static [&'static str])];
+
+/// The options of a `Serializer`, for setting them all at once with `Serializer::with_config` or
+/// `to_vec_with_config`. Each field is documented on the `Serializer` method that sets it. The
+/// default matches `Serializer::new`.
+///
+/// Most options cost nothing, but `buffer_unknown_lengths` and `sort_map_keys` hold each affected
+/// sequence or map in memory until it ends, and `length_prefixed_variants` does the same for each
+/// enum variant's data. Checking the order of map keys with `assume_sorted_map_keys` holds one
+/// key at a time.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct SerializerConfig {
+    pub max_depth: Option<usize>,
+    pub length_prefixed_variants: bool,
+    pub buffer_unknown_lengths: bool,
+    pub sort_map_keys: bool,
+    pub assume_sorted_map_keys: bool,
+    pub check_sorted_map_keys: bool,
+    pub human_readable: bool,
+    pub max_variant_tag: u32,
+    pub canonical_nan: bool,
+}
+
+impl Default for SerializerConfig {
+    fn default() -> Self {
+        SerializerConfig {
+            max_depth: None,
+            length_prefixed_variants: false,
+            buffer_unknown_lengths: false,
+            sort_map_keys: false,
+            assume_sorted_map_keys: false,
+            check_sorted_map_keys: false,
+            human_readable: false,
+            max_variant_tag: u32::MAX,
+            canonical_nan: false,
+        }
+    }
+}
 
 pub struct Serializer<W> {
     writer: W,
+    field_order: Option<FieldOrder>,
+    depth: usize,
+    config: SerializerConfig,
+    /// `u8` elements of sequences that haven't been written yet. serde hands a plain `Vec<u8>`
+    /// over a byte at a time, and writing each one on its own is very slow with unbuffered
+    /// writers. Every other write goes after these bytes, so they're written first.
+    byte_run: Vec<u8>,
+    /// Whether a `u8` serialized now is an element of a sequence, to be added to `byte_run`.
+    byte_element: bool,
 }
 
 impl<W> Serializer<W> {
     pub fn new(writer: W) -> Self {
-        Serializer { writer }
+        Serializer {
+            writer,
+            field_order: None,
+            depth: 0,
+            config: SerializerConfig::default(),
+            byte_run: Vec::new(),
+            byte_element: false,
+        }
+    }
+
+    pub fn with_config(writer: W, config: SerializerConfig) -> Self {
+        Serializer {
+            config,
+            ..Serializer::new(writer)
+        }
+    }
+
+    pub fn config(&self) -> SerializerConfig {
+        self.config
+    }
+
+    /// Like `new`, but serializing values nested more than `max_depth` levels deep fails with
+    /// Error::DepthLimitExceeded instead of recursing further.
+    /// Options, newtypes, sequences, maps, structs and enum variants each count as a level.
+    pub fn with_max_depth(writer: W, max_depth: usize) -> Self {
+        let mut serializer = Serializer::new(writer);
+        serializer.config.max_depth = Some(max_depth);
+        serializer
+    }
+
+    /// Write a `uint` holding the length of each enum variant's data between its tag and the data,
+    /// so that a reader which doesn't know the variant can skip it.
+    /// This isn't standard BARE: it can only be read by a `Deserializer` with
+    /// `length_prefixed_variants` enabled.
+    pub fn length_prefixed_variants(&mut self) {
+        self.config.length_prefixed_variants = true;
+    }
+
+    /// Serialize sequences of unknown length, such as those from `collect_seq` on a filtered
+    /// iterator, by buffering their elements until the length is known instead of failing with
+    /// Error::SequenceLengthRequired.
+    pub fn buffer_unknown_lengths(&mut self) {
+        self.config.buffer_unknown_lengths = true;
+    }
+
+    /// Write map entries in ascending order of their serialized keys, so that maps with the same
+    /// contents serialize to the same bytes whatever their iteration order. Each map's entries
+    /// are buffered until it ends.
+    pub fn sort_map_keys(&mut self) {
+        self.config.sort_map_keys = true;
+    }
+
+    /// Write map entries as they come, trusting the caller that they're already in ascending
+    /// order of their serialized keys, so that the output is canonical without buffering. With
+    /// debug assertions on, a key that isn't above the one before it fails with
+    /// Error::UnsortedMapKeys. Ignored if `sort_map_keys` is enabled.
+    ///
+    /// Encoded order isn't always the keys' `Ord` order, so a `BTreeMap` qualifies only for keys
+    /// like `u8` or strings of equal length. See also `serialize_map_sorted_iter`.
+    pub fn assume_sorted_map_keys(&mut self) {
+        self.config.assume_sorted_map_keys = true;
+    }
+
+    /// Like `assume_sorted_map_keys`, but check the order of keys in release builds too.
+    pub fn check_sorted_map_keys(&mut self) {
+        self.config.assume_sorted_map_keys = true;
+        self.config.check_sorted_map_keys = true;
+    }
+
+    /// Make `is_human_readable` return true, for types that only choose the representation a
+    /// peer expects in that mode. This only changes which serde methods such types call: BARE
+    /// encodes whatever they produce the same way. Pair it with `human_readable` on the
+    /// `Deserializer`.
+    pub fn human_readable(&mut self) {
+        self.config.human_readable = true;
+    }
+
+    /// Fail with Error::VariantTagOutOfRange when serializing an enum variant whose tag is above
+    /// `max`, for schemas whose unions have a small tag space. No limit is set by default.
+    pub fn max_variant_tag(&mut self, max: u32) {
+        self.config.max_variant_tag = max;
+    }
+
+    /// Write every NaN `f32` and `f64` as the canonical NaN, `CANONICAL_NAN_F32` or
+    /// `CANONICAL_NAN_F64`, for peers that compare or hash encodings. By default floats are
+    /// written bit for bit, payload and all.
+    pub fn canonical_nan(&mut self) {
+        self.config.canonical_nan = true;
+    }
+
+    /// Returns the writer, for example to write more data after the serialized values.
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+
+    pub fn get_ref(&self) -> &W {
+        &self.writer
+    }
+
+    pub fn get_mut(&mut self) -> &mut W {
+        &mut self.writer
+    }
+
+    /// Forget a serialization that failed partway, so that the serializer can go on to the next
+    /// message. Options and the writer are kept. Sorted maps and sequences of unknown length are
+    /// buffered only until they end, so there's nothing else to clear; to reuse a `Vec` writer's
+    /// allocation, clear it through `get_mut`.
+    pub fn reset(&mut self) {
+        self.depth = 0;
+        self.byte_run.clear();
+        self.byte_element = false;
+    }
+
+    /// A serializer with the same options writing to `buf`.
+    fn nested<'b>(&self, buf: &'b mut Vec<u8>) -> Serializer<&'b mut Vec<u8>> {
+        Serializer {
+            writer: buf,
+            field_order: self.field_order,
+            depth: self.depth,
+            config: self.config,
+            byte_run: Vec::new(),
+            byte_element: false,
+        }
+    }
+
+    fn enter(&mut self) -> Result<(), Error> {
+        if self.config.max_depth.is_some_and(|max| self.depth >= max) {
+            return Err(Error::DepthLimitExceeded);
+        }
+        self.depth += 1;
+        Ok(())
+    }
+
+    fn leave(&mut self) {
+        self.depth -= 1;
+    }
+}
+
+/// The most `u8` elements of sequences held back before they're written.
+const BYTE_RUN_LEN: usize = 8192;
+
+impl<W> Serializer<W>
+where
+    W: Write,
+{
+    /// Write `buf` after any `u8` elements still held back.
+    fn write_all(&mut self, buf: &[u8]) -> Result<(), Error> {
+        self.write_byte_run()?;
+        self.writer.write_all(buf).map_err(Error::Io)
+    }
+
+    fn write_byte_run(&mut self) -> Result<(), Error> {
+        if !self.byte_run.is_empty() {
+            self.writer.write_all(&self.byte_run)?;
+            self.byte_run.clear();
+        }
+        Ok(())
+    }
+
+    /// Write `x` as a `uint` in one go. This crate's own lengths and tags are written this way
+    /// rather than through `Uint`'s serde impl, which writes a byte at a time.
+    fn write_uint(&mut self, x: u64) -> Result<(), Error> {
+        let mut buf = [0u8; MAX_VARINT_LEN];
+        let len = Uint(x).encode(&mut buf);
+        self.write_all(&buf[..len])
+    }
+
+    /// Write `n` as a BARE `uint`, for the lengths and tags of framing built on top of BARE.
+    /// It's written in one go, unlike a `Uint` passed to `serialize`.
+    pub fn write_len(&mut self, n: u64) -> Result<(), Error> {
+        self.write_uint(n)
+    }
+
+    /// Write the tag of an enum variant, checking it against `max_variant_tag`.
+    pub(crate) fn write_variant_tag(&mut self, variant_index: u32) -> Result<(), Error> {
+        if variant_index > self.config.max_variant_tag {
+            return Err(Error::VariantTagOutOfRange(variant_index));
+        }
+        self.write_uint(variant_index.into())
+    }
+
+    /// Write `buf` behind a `uint` holding its length.
+    fn write_prefixed(&mut self, buf: &[u8]) -> Result<(), Error> {
+        self.write_uint(buf.len() as u64)?;
+        self.write_all(buf)
     }
 }
 
+#[allow(deprecated)]
 impl<'a, W> ser::Serializer for &'a mut Serializer<W>
 where
     W: Write,
 {
     type Ok = ();
     type Error = Error;
-    type SerializeSeq = Self;
+    type SerializeSeq = SeqSerializer<'a, W>;
     type SerializeTuple = Self;
     type SerializeTupleStruct = Self;
-    type SerializeTupleVariant = Self;
-    type SerializeMap = Self;
-    type SerializeStruct = Self;
-    type SerializeStructVariant = Self;
+    type SerializeTupleVariant = VariantSerializer<'a, W>;
+    type SerializeMap = MapSerializer<'a, W>;
+    type SerializeStruct = StructSerializer<'a, W>;
+    type SerializeStructVariant = VariantSerializer<'a, W>;
 
     /// BARE type: bool
     fn serialize_bool(self, v: bool) -> Result<Self::Ok, Self::Error> {
@@ -33,66 +275,90 @@ where
 
     /// BARE type: i8
     fn serialize_i8(self, v: i8) -> Result<Self::Ok, Self::Error> {
-        self.writer.write_all(&v.to_le_bytes()).map_err(Error::Io)
+        self.write_all(&v.to_le_bytes())
     }
 
     /// BARE type: i16
     fn serialize_i16(self, v: i16) -> Result<Self::Ok, Self::Error> {
-        self.writer.write_all(&v.to_le_bytes()).map_err(Error::Io)
+        self.write_all(&v.to_le_bytes())
     }
 
     /// BARE type: i32
     fn serialize_i32(self, v: i32) -> Result<Self::Ok, Self::Error> {
-        self.writer.write_all(&v.to_le_bytes()).map_err(Error::Io)
+        self.write_all(&v.to_le_bytes())
     }
 
     /// BARE type: i64
     fn serialize_i64(self, v: i64) -> Result<Self::Ok, Self::Error> {
-        self.writer.write_all(&v.to_le_bytes()).map_err(Error::Io)
+        self.write_all(&v.to_le_bytes())
     }
 
     serde::serde_if_integer128! {
         /// BARE type: data\<16\>
         fn serialize_i128(self, v: i128) -> Result<Self::Ok, Self::Error> {
-            self.writer.write_all(&v.to_le_bytes()).map_err(Error::Io)
+            if !cfg!(feature = "i128") {
+                return Err(crate::Error::I128Disabled);
+            }
+            self.write_all(&v.to_le_bytes())
         }
     }
 
     /// BARE type: u8
     fn serialize_u8(self, v: u8) -> Result<Self::Ok, Self::Error> {
-        self.writer.write_all(&v.to_le_bytes()).map_err(Error::Io)
+        if !self.byte_element {
+            return self.write_all(&[v]);
+        }
+        self.byte_element = false;
+        self.byte_run.push(v);
+        if self.byte_run.len() >= BYTE_RUN_LEN {
+            self.write_byte_run()?;
+        }
+        Ok(())
     }
 
     /// BARE type: u16
     fn serialize_u16(self, v: u16) -> Result<Self::Ok, Self::Error> {
-        self.writer.write_all(&v.to_le_bytes()).map_err(Error::Io)
+        self.write_all(&v.to_le_bytes())
     }
 
     /// BARE type: u32
     fn serialize_u32(self, v: u32) -> Result<Self::Ok, Self::Error> {
-        self.writer.write_all(&v.to_le_bytes()).map_err(Error::Io)
+        self.write_all(&v.to_le_bytes())
     }
 
     /// BARE type: u64
     fn serialize_u64(self, v: u64) -> Result<Self::Ok, Self::Error> {
-        self.writer.write_all(&v.to_le_bytes()).map_err(Error::Io)
+        self.write_all(&v.to_le_bytes())
     }
 
     serde::serde_if_integer128! {
         /// BARE type: data\<16\>
         fn serialize_u128(self, v: u128) -> Result<Self::Ok, Self::Error> {
-            self.writer.write_all(&v.to_le_bytes()).map_err(Error::Io)
+            if !cfg!(feature = "i128") {
+                return Err(crate::Error::I128Disabled);
+            }
+            self.write_all(&v.to_le_bytes())
         }
     }
 
     /// BARE type: f32
     fn serialize_f32(self, v: f32) -> Result<Self::Ok, Self::Error> {
-        self.writer.write_all(&v.to_le_bytes()).map_err(Error::Io)
+        let v = if self.config.canonical_nan && v.is_nan() {
+            f32::from_bits(CANONICAL_NAN_F32)
+        } else {
+            v
+        };
+        self.write_all(&v.to_le_bytes())
     }
 
     /// BARE type: f64
     fn serialize_f64(self, v: f64) -> Result<Self::Ok, Self::Error> {
-        self.writer.write_all(&v.to_le_bytes()).map_err(Error::Io)
+        let v = if self.config.canonical_nan && v.is_nan() {
+            f64::from_bits(CANONICAL_NAN_F64)
+        } else {
+            v
+        };
+        self.write_all(&v.to_le_bytes())
     }
 
     /// BARE type: u32
@@ -102,14 +368,14 @@ where
 
     /// BARE type: string
     fn serialize_str(self, v: &str) -> Result<Self::Ok, Self::Error> {
-        Uint(v.len() as u64).serialize(&mut *self)?;
-        self.writer.write_all(v.as_bytes()).map_err(Error::Io)
+        self.write_uint(v.len() as u64)?;
+        self.write_all(v.as_bytes())
     }
 
     /// BARE type: data
     fn serialize_bytes(self, v: &[u8]) -> Result<Self::Ok, Self::Error> {
-        Uint(v.len() as u64).serialize(&mut *self)?;
-        self.writer.write_all(v).map_err(Error::Io)
+        self.write_uint(v.len() as u64)?;
+        self.write_all(v)
     }
 
     /// BARE type: optional\<type\>
@@ -118,12 +384,16 @@ where
     }
 
     /// BARE type: optional\<type\>
+    #[allow(clippy::multiple_bound_locations)]
     fn serialize_some<T: ?Sized>(self, value: &T) -> Result<Self::Ok, Self::Error>
     where
         T: Serialize,
     {
         self.serialize_u8(1)?;
-        value.serialize(self)
+        self.enter()?;
+        value.serialize(&mut *self)?;
+        self.leave();
+        Ok(())
     }
 
     /// BARE type: void
@@ -144,11 +414,16 @@ where
         variant_index: u32,
         _variant: &'static str,
     ) -> Result<Self::Ok, Self::Error> {
-        Uint(variant_index.into()).serialize(self)
+        self.write_variant_tag(variant_index)?;
+        if self.config.length_prefixed_variants {
+            self.write_uint(0)?;
+        }
+        Ok(())
     }
 
     /// BARE type: T
     /// `name` is ignored.
+    #[allow(clippy::multiple_bound_locations)]
     fn serialize_newtype_struct<T: ?Sized>(
         self,
         _name: &'static str,
@@ -157,11 +432,15 @@ where
     where
         T: Serialize,
     {
-        value.serialize(self)
+        self.enter()?;
+        value.serialize(&mut *self)?;
+        self.leave();
+        Ok(())
     }
 
     /// BARE type: { uint, T } or (... | T | ...)
     /// `name` and `variant` are ignored.
+    #[allow(clippy::multiple_bound_locations)]
     fn serialize_newtype_variant<T: ?Sized>(
         self,
         _name: &'static str,
@@ -172,21 +451,39 @@ where
     where
         T: Serialize,
     {
-        Uint(variant_index.into()).serialize(&mut *self)?;
-        value.serialize(self)
+        self.write_variant_tag(variant_index)?;
+        self.enter()?;
+        if self.config.length_prefixed_variants {
+            let mut buf = Vec::new();
+            value.serialize(&mut self.nested(&mut buf))?;
+            self.write_prefixed(&buf)?;
+        } else {
+            value.serialize(&mut *self)?;
+        }
+        self.leave();
+        Ok(())
     }
 
     /// BARE type if len is Some: []type
     /// BARE type \[len\]type is never used for variable-length sequences
-    /// Error::SequenceLengthRequired if len is None
+    /// Error::SequenceLengthRequired if len is None, unless `buffer_unknown_lengths` is enabled
     fn serialize_seq(self, len: Option<usize>) -> Result<Self::SerializeSeq, Self::Error> {
-        Uint(len.ok_or(Error::SequenceLengthRequired)? as u64).serialize(&mut *self)?;
-        Ok(self)
+        let buffer = match len {
+            Some(len) => {
+                self.write_uint(len as u64)?;
+                None
+            }
+            None if self.config.buffer_unknown_lengths => Some((Vec::new(), 0)),
+            None => return Err(Error::SequenceLengthRequired),
+        };
+        self.enter()?;
+        Ok(SeqSerializer { ser: self, buffer })
     }
 
     /// BARE type: \[len\]type
     /// `len` is ignored.
     fn serialize_tuple(self, _len: usize) -> Result<Self::SerializeTuple, Self::Error> {
+        self.enter()?;
         Ok(self)
     }
 
@@ -197,6 +494,7 @@ where
         _name: &'static str,
         _len: usize,
     ) -> Result<Self::SerializeTupleStruct, Self::Error> {
+        self.enter()?;
         Ok(self)
     }
 
@@ -209,24 +507,47 @@ where
         _variant: &'static str,
         _len: usize,
     ) -> Result<Self::SerializeTupleVariant, Self::Error> {
-        Uint(variant_index.into()).serialize(&mut *self)?;
-        Ok(self)
+        self.write_variant_tag(variant_index)?;
+        self.enter()?;
+        let buffer = self.config.length_prefixed_variants.then(Vec::new);
+        Ok(VariantSerializer { ser: self, buffer })
     }
 
     /// BARE type: map\[T\]U
     /// Error::MapLengthRequired if len is None
     fn serialize_map(self, len: Option<usize>) -> Result<Self::SerializeMap, Self::Error> {
-        Uint(len.ok_or(Error::MapLengthRequired)? as u64).serialize(&mut *self)?;
-        Ok(self)
+        self.write_uint(len.ok_or(Error::MapLengthRequired)? as u64)?;
+        self.enter()?;
+        let config = self.config;
+        let entries = config.sort_map_keys.then(Vec::new);
+        let check = !config.sort_map_keys
+            && config.assume_sorted_map_keys
+            && (config.check_sorted_map_keys || cfg!(debug_assertions));
+        let keys = check.then(SortedKeys::default);
+        Ok(MapSerializer {
+            ser: self,
+            entries,
+            keys,
+        })
     }
 
     /// BARE type: struct
+    /// Fields are reordered if the serializer has a field order for `name`.
     fn serialize_struct(
         self,
-        _name: &'static str,
+        name: &'static str,
         _len: usize,
     ) -> Result<Self::SerializeStruct, Self::Error> {
-        Ok(self)
+        let fields = self
+            .field_order
+            .and_then(|order| order.iter().find(|(n, _)| *n == name))
+            .map(|&(_, fields)| fields);
+        self.enter()?;
+        Ok(StructSerializer {
+            ser: self,
+            fields,
+            buffered: Vec::new(),
+        })
     }
 
     /// BARE type: { uint, T } or (... | T | ...)
@@ -237,35 +558,127 @@ where
         _variant: &'static str,
         _len: usize,
     ) -> Result<Self::SerializeStructVariant, Self::Error> {
-        Uint(variant_index.into()).serialize(&mut *self)?;
-        Ok(self)
+        self.write_variant_tag(variant_index)?;
+        self.enter()?;
+        let buffer = self.config.length_prefixed_variants.then(Vec::new);
+        Ok(VariantSerializer { ser: self, buffer })
     }
 
-    /// Returns false.
+    /// BARE type: string
+    /// `value` is formatted twice, once to measure it for the length prefix and once straight
+    /// into the writer, rather than into a `String` first. Error::Message if the two disagree.
+    fn collect_str<T>(self, value: &T) -> Result<Self::Ok, Self::Error>
+    where
+        T: ?Sized + Display,
+    {
+        let mut len = StrLen(0);
+        fmt::write(&mut len, format_args!("{}", value))
+            .map_err(|_| Error::Message("Display impl returned an error".into()))?;
+        self.write_uint(len.0 as u64)?;
+        self.write_byte_run()?;
+
+        let mut out = StrWriter {
+            writer: &mut self.writer,
+            left: len.0,
+            error: None,
+        };
+        let result = fmt::write(&mut out, format_args!("{}", value));
+        if let Some(e) = out.error {
+            return Err(Error::Io(e));
+        }
+        if result.is_err() || out.left != 0 {
+            return Err(Error::Message(
+                "Display impl wrote a different string the second time".into(),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Returns false unless `human_readable` is enabled.
     fn is_human_readable(&self) -> bool {
-        false
+        self.config.human_readable
+    }
+}
+
+/// Adds up the length of formatted output.
+struct StrLen(usize);
+
+impl fmt::Write for StrLen {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.0 += s.len();
+        Ok(())
     }
 }
 
-impl<'a, W> ser::SerializeSeq for &'a mut Serializer<W>
+/// Writes formatted output to a writer, failing rather than writing more than `left` bytes.
+struct StrWriter<'a, W> {
+    writer: &'a mut W,
+    left: usize,
+    error: Option<io::Error>,
+}
+
+impl<'a, W> fmt::Write for StrWriter<'a, W>
+where
+    W: Write,
+{
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        if s.len() > self.left {
+            return Err(fmt::Error);
+        }
+        self.left -= s.len();
+        self.writer.write_all(s.as_bytes()).map_err(|e| {
+            self.error = Some(e);
+            fmt::Error
+        })
+    }
+}
+
+/// Serializes the elements of a sequence, buffering them and counting them when the length
+/// wasn't given up front.
+pub struct SeqSerializer<'a, W> {
+    ser: &'a mut Serializer<W>,
+    buffer: Option<(Vec<u8>, u64)>,
+}
+
+impl<W> ser::SerializeSeq for SeqSerializer<'_, W>
 where
     W: Write,
 {
     type Ok = ();
     type Error = Error;
 
-    fn serialize_element<T: ?Sized>(&mut self, value: &T) -> Result<(), Self::Error>
+    fn serialize_element<T>(&mut self, value: &T) -> Result<(), Self::Error>
     where
-        T: Serialize,
+        T: ?Sized + Serialize,
     {
-        value.serialize(&mut **self)
+        match &mut self.buffer {
+            Some((buf, count)) => {
+                *count += 1;
+                value.serialize(&mut self.ser.nested(buf))
+            }
+            None => {
+                self.ser.byte_element = true;
+                let result = value.serialize(&mut *self.ser);
+                self.ser.byte_element = false;
+                result
+            }
+        }
     }
 
     fn end(self) -> Result<Self::Ok, Self::Error> {
+        match &self.buffer {
+            Some((buf, count)) => {
+                self.ser.write_uint(*count)?;
+                self.ser.write_all(buf)?;
+            }
+            None => self.ser.write_byte_run()?,
+        }
+        self.ser.leave();
         Ok(())
     }
 }
 
+#[allow(clippy::needless_lifetimes)]
 impl<'a, W> ser::SerializeTuple for &'a mut Serializer<W>
 where
     W: Write,
@@ -273,6 +686,7 @@ where
     type Ok = ();
     type Error = Error;
 
+    #[allow(clippy::multiple_bound_locations)]
     fn serialize_element<T: ?Sized>(&mut self, value: &T) -> Result<(), Self::Error>
     where
         T: Serialize,
@@ -281,10 +695,12 @@ where
     }
 
     fn end(self) -> Result<Self::Ok, Self::Error> {
+        self.leave();
         Ok(())
     }
 }
 
+#[allow(clippy::needless_lifetimes)]
 impl<'a, W> ser::SerializeTupleStruct for &'a mut Serializer<W>
 where
     W: Write,
@@ -292,6 +708,7 @@ where
     type Ok = ();
     type Error = Error;
 
+    #[allow(clippy::multiple_bound_locations)]
     fn serialize_field<T: ?Sized>(&mut self, value: &T) -> Result<(), Self::Error>
     where
         T: Serialize,
@@ -300,124 +717,587 @@ where
     }
 
     fn end(self) -> Result<Self::Ok, Self::Error> {
+        self.leave();
         Ok(())
     }
 }
 
-impl<'a, W> ser::SerializeTupleVariant for &'a mut Serializer<W>
+/// Serializes the fields of tuple and struct variants, buffering them when the variant needs a
+/// length prefix.
+pub struct VariantSerializer<'a, W> {
+    ser: &'a mut Serializer<W>,
+    buffer: Option<Vec<u8>>,
+}
+
+impl<W> VariantSerializer<'_, W>
+where
+    W: Write,
+{
+    fn field<T>(&mut self, value: &T) -> Result<(), Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        match &mut self.buffer {
+            Some(buf) => value.serialize(&mut self.ser.nested(buf)),
+            None => value.serialize(&mut *self.ser),
+        }
+    }
+
+    fn finish(self) -> Result<(), Error> {
+        if let Some(buf) = &self.buffer {
+            self.ser.write_prefixed(buf)?;
+        }
+        self.ser.leave();
+        Ok(())
+    }
+}
+
+impl<W> ser::SerializeTupleVariant for VariantSerializer<'_, W>
 where
     W: Write,
 {
     type Ok = ();
     type Error = Error;
 
-    fn serialize_field<T: ?Sized>(&mut self, value: &T) -> Result<(), Self::Error>
+    fn serialize_field<T>(&mut self, value: &T) -> Result<(), Self::Error>
     where
-        T: Serialize,
+        T: ?Sized + Serialize,
     {
-        value.serialize(&mut **self)
+        self.field(value)
     }
 
     fn end(self) -> Result<Self::Ok, Self::Error> {
-        Ok(())
+        self.finish()
     }
 }
 
-impl<'a, W> ser::SerializeMap for &'a mut Serializer<W>
+/// Serializes map entries, buffering them when they need to be sorted by key.
+pub struct MapSerializer<'a, W> {
+    ser: &'a mut Serializer<W>,
+    /// The serialized key and value of each entry so far, when sorting.
+    entries: Option<Vec<(Vec<u8>, Vec<u8>)>>,
+    /// The last key written, when checking that keys come in order.
+    keys: Option<SortedKeys>,
+}
+
+#[derive(Default)]
+struct SortedKeys {
+    previous: Option<Vec<u8>>,
+    /// A buffer for the next key, so that checking doesn't allocate for every one.
+    spare: Vec<u8>,
+}
+
+impl<W> ser::SerializeMap for MapSerializer<'_, W>
 where
     W: Write,
 {
     type Ok = ();
     type Error = Error;
 
-    fn serialize_key<T: ?Sized>(&mut self, value: &T) -> Result<(), Self::Error>
+    fn serialize_key<T>(&mut self, value: &T) -> Result<(), Self::Error>
     where
-        T: Serialize,
+        T: ?Sized + Serialize,
     {
-        value.serialize(&mut **self)
+        match (&mut self.entries, &mut self.keys) {
+            (Some(entries), _) => {
+                let mut key = Vec::new();
+                value.serialize(&mut self.ser.nested(&mut key))?;
+                entries.push((key, Vec::new()));
+                Ok(())
+            }
+            (None, Some(keys)) => {
+                let mut key = core::mem::take(&mut keys.spare);
+                key.clear();
+                value.serialize(&mut self.ser.nested(&mut key))?;
+                if keys
+                    .previous
+                    .as_ref()
+                    .is_some_and(|previous| *previous >= key)
+                {
+                    return Err(Error::UnsortedMapKeys);
+                }
+                self.ser.write_all(&key)?;
+                keys.spare = keys.previous.replace(key).unwrap_or_default();
+                Ok(())
+            }
+            (None, None) => value.serialize(&mut *self.ser),
+        }
     }
 
-    fn serialize_value<T: ?Sized>(&mut self, value: &T) -> Result<(), Self::Error>
+    fn serialize_value<T>(&mut self, value: &T) -> Result<(), Self::Error>
     where
-        T: Serialize,
+        T: ?Sized + Serialize,
     {
-        value.serialize(&mut **self)
+        match self.entries.as_mut().and_then(|entries| entries.last_mut()) {
+            Some((_, buf)) => value.serialize(&mut self.ser.nested(buf)),
+            None => value.serialize(&mut *self.ser),
+        }
     }
 
     fn end(self) -> Result<Self::Ok, Self::Error> {
+        if let Some(mut entries) = self.entries {
+            entries.sort_unstable_by(|a, b| a.0.cmp(&b.0));
+            for (key, value) in entries {
+                self.ser.write_all(&key)?;
+                self.ser.write_all(&value)?;
+            }
+        }
+        self.ser.leave();
         Ok(())
     }
 }
 
-impl<'a, W> ser::SerializeStruct for &'a mut Serializer<W>
+/// Serializes struct fields, buffering them when they need to be written in a different order.
+pub struct StructSerializer<'a, W> {
+    ser: &'a mut Serializer<W>,
+    /// The schema's field names, when reordering.
+    fields: Option<&'static [&'static str]>,
+    /// Fields serialized so far, when reordering.
+    buffered: Vec<(&'static str, Vec<u8>)>,
+}
+
+impl<'a, W> ser::SerializeStruct for StructSerializer<'a, W>
 where
     W: Write,
 {
     type Ok = ();
     type Error = Error;
 
-    fn serialize_field<T: ?Sized>(
-        &mut self,
-        _key: &'static str,
-        value: &T,
-    ) -> Result<(), Self::Error>
+    fn serialize_field<T>(&mut self, key: &'static str, value: &T) -> Result<(), Self::Error>
     where
-        T: Serialize,
+        T: ?Sized + Serialize,
     {
-        value.serialize(&mut **self)
+        if self.fields.is_none() {
+            return value.serialize(&mut *self.ser);
+        }
+        let mut buf = Vec::new();
+        value.serialize(&mut self.ser.nested(&mut buf))?;
+        self.buffered.push((key, buf));
+        Ok(())
     }
 
     fn end(self) -> Result<Self::Ok, Self::Error> {
+        self.ser.leave();
+        let fields = match self.fields {
+            Some(fields) => fields,
+            None => return Ok(()),
+        };
+        if let Some((key, _)) = self.buffered.iter().find(|(key, _)| !fields.contains(key)) {
+            return Err(Error::Message(format!(
+                "field `{}` is not in the schema",
+                key
+            )));
+        }
+        for field in fields {
+            let (_, buf) = self
+                .buffered
+                .iter()
+                .find(|(key, _)| key == field)
+                .ok_or_else(|| {
+                    Error::Message(format!("schema field `{}` was not serialized", field))
+                })?;
+            self.ser.write_all(buf)?;
+        }
         Ok(())
     }
 }
 
-impl<'a, W> ser::SerializeStructVariant for &'a mut Serializer<W>
+impl<W> ser::SerializeStructVariant for VariantSerializer<'_, W>
 where
     W: Write,
 {
     type Ok = ();
     type Error = Error;
 
-    fn serialize_field<T: ?Sized>(
-        &mut self,
-        _key: &'static str,
-        value: &T,
-    ) -> Result<(), Self::Error>
+    fn serialize_field<T>(&mut self, _key: &'static str, value: &T) -> Result<(), Self::Error>
     where
-        T: Serialize,
+        T: ?Sized + Serialize,
     {
-        value.serialize(&mut **self)
+        self.field(value)
     }
 
     fn end(self) -> Result<Self::Ok, Self::Error> {
-        Ok(())
+        self.finish()
     }
 }
 
+/// Returns the number of bytes `value` serializes to, without writing them anywhere.
+pub fn serialized_size<T>(value: &T) -> Result<u64, Error>
+where
+    T: ?Sized + Serialize,
+{
+    let mut serializer = Serializer::new(SizeCounter(0));
+    value.serialize(&mut serializer)?;
+    Ok(serializer.writer.0)
+}
+
+#[allow(clippy::multiple_bound_locations)]
 pub fn to_vec<T: ?Sized>(value: &T) -> Result<Vec<u8>, Error>
 where
     T: Serialize,
 {
     let mut vec = Vec::new();
-    let mut serializer = Serializer { writer: &mut vec };
+    let mut serializer = Serializer::new(&mut vec);
+    value.serialize(&mut serializer)?;
+    Ok(vec)
+}
+
+/// Like `to_vec`, but sizes the `Vec` up front with the size `value` reports through
+/// [`BareSize`], so that it's rarely reallocated. `value` is still serialized only once.
+pub fn to_vec_presized<T>(value: &T) -> Result<Vec<u8>, Error>
+where
+    T: ?Sized + Serialize + BareSize,
+{
+    let mut vec = Vec::with_capacity(value.approx_bare_size());
+    let mut serializer = Serializer::new(&mut vec);
     value.serialize(&mut serializer)?;
     Ok(vec)
 }
 
+/// Serialize `value` onto the end of `buf`, so that one allocation can be reused across
+/// messages. `buf` only grows if it lacks the capacity. On error it's left as it was.
+pub fn to_vec_in<T>(buf: &mut Vec<u8>, value: &T) -> Result<(), Error>
+where
+    T: ?Sized + Serialize,
+{
+    let len = buf.len();
+    let result = value.serialize(&mut Serializer::new(&mut *buf));
+    if result.is_err() {
+        buf.truncate(len);
+    }
+    result
+}
+
+/// Like `to_vec`, with the options in `config`. The `Vec` isn't sized up front.
+pub fn to_vec_with_config<T>(value: &T, config: SerializerConfig) -> Result<Vec<u8>, Error>
+where
+    T: ?Sized + Serialize,
+{
+    let mut vec = Vec::new();
+    to_writer_with_config(&mut vec, value, config)?;
+    Ok(vec)
+}
+
+#[allow(clippy::multiple_bound_locations)]
 pub fn to_writer<W, T: ?Sized>(writer: W, value: &T) -> Result<(), Error>
 where
     W: Write,
     T: Serialize,
 {
-    let mut serializer = Serializer { writer };
+    to_writer_with_config(writer, value, SerializerConfig::default())
+}
+
+/// Like `to_writer`, with the options in `config`.
+pub fn to_writer_with_config<W, T>(
+    writer: W,
+    value: &T,
+    config: SerializerConfig,
+) -> Result<(), Error>
+where
+    W: Write,
+    T: ?Sized + Serialize,
+{
+    let mut serializer = Serializer::with_config(writer, config);
+    value.serialize(&mut serializer)?;
+    Ok(())
+}
+
+/// Like `to_vec`, but the fields of each struct named in `field_order` are written in the order
+/// given there rather than declaration order.
+/// Errors if a struct's fields don't match the names in `field_order`.
+pub fn to_vec_schema_ordered<T>(value: &T, field_order: FieldOrder) -> Result<Vec<u8>, Error>
+where
+    T: ?Sized + Serialize,
+{
+    let mut vec = Vec::new();
+    let mut serializer = Serializer {
+        field_order: Some(field_order),
+        ..Serializer::new(&mut vec)
+    };
+    value.serialize(&mut serializer)?;
+    Ok(vec)
+}
+
+/// Write `value` as a frame: a BARE `uint` holding the length of the serialized value in bytes,
+/// followed by the value itself. `from_reader_framed` reads frames back.
+///
+/// Nothing is buffered: the length is computed with `serialized_size`, so `value` is serialized
+/// twice. Its `Serialize` impl has to produce the same data both times.
+pub fn to_writer_framed<W, T>(writer: W, value: &T) -> Result<(), Error>
+where
+    W: Write,
+    T: ?Sized + Serialize,
+{
+    let mut serializer = Serializer::new(writer);
+    serializer.write_uint(serialized_size(value)?)?;
+    value.serialize(&mut serializer)
+}
+
+/// Serialize `value` behind a BARE `uint` schema version, for `from_slice_versioned` to read.
+pub fn to_vec_versioned<T>(version: u64, value: &T) -> Result<Vec<u8>, Error>
+where
+    T: ?Sized + Serialize,
+{
+    let mut vec = Vec::new();
+    let mut serializer = Serializer::new(&mut vec);
+    serializer.write_uint(version)?;
     value.serialize(&mut serializer)?;
+    Ok(vec)
+}
+
+/// Write a BARE map of `len` entries straight from `entries`, which must already be in the
+/// canonical order: ascending by the bytes of each encoded key. Nothing is buffered.
+///
+/// Encoded order isn't always the keys' `Ord` order, so a `BTreeMap` qualifies only for keys
+/// like `u8` or strings of equal length. With debug assertions on, entries out of order fail
+/// with `Error::UnsortedMapKeys`. No more than `len` entries are taken from `entries`, and
+/// Error::MapLengthMismatch if it runs out before then or its `size_hint` says there are more.
+pub fn serialize_map_sorted_iter<W, I, K, V>(writer: W, entries: I, len: usize) -> Result<(), Error>
+where
+    W: Write,
+    I: IntoIterator<Item = (K, V)>,
+    K: Serialize,
+    V: Serialize,
+{
+    let mut serializer = Serializer::new(writer);
+    serializer.write_uint(len as u64)?;
+    // The previous key, followed by the one being checked against it from `start` on.
+    let mut keys = Vec::new();
+    let mut entries = entries.into_iter();
+    let mut count = 0;
+    for (key, value) in entries.by_ref().take(len) {
+        if cfg!(debug_assertions) {
+            let start = keys.len();
+            key.serialize(&mut serializer.nested(&mut keys))?;
+            if count > 0 && keys[..start] >= keys[start..] {
+                return Err(Error::UnsortedMapKeys);
+            }
+            serializer.write_all(&keys[start..])?;
+            keys.drain(..start);
+        } else {
+            key.serialize(&mut serializer)?;
+        }
+        value.serialize(&mut serializer)?;
+        count += 1;
+    }
+    if count < len || entries.size_hint().0 > 0 {
+        return Err(Error::MapLengthMismatch { expected: len });
+    }
     Ok(())
 }
 
+/// The most bytes of a primitive slice converted to little-endian before they're written.
+const SLICE_CHUNK_LEN: usize = 4096;
+
+impl<W> Serializer<W>
+where
+    W: Write,
+{
+    /// Write `slice` as a `[]type` of fixed-size elements, converting it to little-endian a chunk
+    /// at a time so that it takes one write per chunk rather than one per element.
+    fn write_le_slice<T, const N: usize>(
+        &mut self,
+        slice: &[T],
+        to_le_bytes: impl Fn(T) -> [u8; N],
+    ) -> Result<(), Error>
+    where
+        T: Copy,
+    {
+        self.write_uint(slice.len() as u64)?;
+        self.enter()?;
+        let mut buf = [0u8; SLICE_CHUNK_LEN];
+        for chunk in slice.chunks(SLICE_CHUNK_LEN / N) {
+            let bytes = &mut buf[..chunk.len() * N];
+            for (out, &x) in bytes.chunks_exact_mut(N).zip(chunk) {
+                out.copy_from_slice(&to_le_bytes(x));
+            }
+            self.write_all(bytes)?;
+        }
+        self.leave();
+        Ok(())
+    }
+}
+
+macro_rules! primitive_slices {
+    ($($name:ident: $ty:ty => $to_le_bytes:expr,)*) => {
+        $(
+            #[doc = concat!("Serialize a `&[", stringify!($ty), "]` the way serde would, with")]
+            /// one write per few thousand bytes instead of one per element. serde can't tell a
+            /// `Serializer` the element type of a slice, so hot paths have to call this directly.
+            pub fn $name<W>(ser: &mut Serializer<W>, slice: &[$ty]) -> Result<(), Error>
+            where
+                W: Write,
+            {
+                let to_le_bytes = $to_le_bytes(ser.config);
+                ser.write_le_slice(slice, to_le_bytes)
+            }
+        )*
+    };
+}
+
+primitive_slices! {
+    serialize_u16_slice: u16 => |_| u16::to_le_bytes,
+    serialize_u32_slice: u32 => |_| u32::to_le_bytes,
+    serialize_u64_slice: u64 => |_| u64::to_le_bytes,
+    serialize_i16_slice: i16 => |_| i16::to_le_bytes,
+    serialize_i32_slice: i32 => |_| i32::to_le_bytes,
+    serialize_i64_slice: i64 => |_| i64::to_le_bytes,
+    serialize_f32_slice: f32 => |config: SerializerConfig| move |v: f32| {
+        if config.canonical_nan && v.is_nan() {
+            CANONICAL_NAN_F32.to_le_bytes()
+        } else {
+            v.to_le_bytes()
+        }
+    },
+    serialize_f64_slice: f64 => |config: SerializerConfig| move |v: f64| {
+        if config.canonical_nan && v.is_nan() {
+            CANONICAL_NAN_F64.to_le_bytes()
+        } else {
+            v.to_le_bytes()
+        }
+    },
+}
+
+/// Decode a `T` from `bytes` with the options in `input` and serialize it again with those in
+/// `output`. For example, reading with the default options and writing with `sort_map_keys`
+/// normalizes a message into canonical form. Error::TrailingData if `T` doesn't use all of
+/// `bytes`.
+pub fn transcode<T>(
+    bytes: &[u8],
+    input: &DeserializerConfig,
+    output: &SerializerConfig,
+) -> Result<Vec<u8>, Error>
+where
+    T: DeserializeOwned + Serialize,
+{
+    let mut deserializer = Deserializer::with_config(bytes, *input);
+    let value = T::deserialize(&mut deserializer)?;
+    if !deserializer.into_inner().is_empty() {
+        return Err(Error::TrailingData);
+    }
+    let mut vec = Vec::with_capacity(bytes.len());
+    let mut serializer = Serializer::with_config(&mut vec, *output);
+    value.serialize(&mut serializer)?;
+    Ok(vec)
+}
+
+/// Serializes `value` into the start of `buf` without allocating, returning the part of `buf`
+/// that was written. Error::BufferFull if `value` doesn't fit.
+pub fn to_slice<'a, T>(buf: &'a mut [u8], value: &T) -> Result<&'a [u8], Error>
+where
+    T: ?Sized + Serialize,
+{
+    let mut serializer = Serializer::new(SliceWriter {
+        buf,
+        len: 0,
+        needed: None,
+    });
+    if let Err(e) = value.serialize(&mut serializer) {
+        return Err(match serializer.writer.needed {
+            Some(needed_at_least) => Error::BufferFull { needed_at_least },
+            None => e,
+        });
+    }
+    let SliceWriter { buf, len, .. } = serializer.writer;
+    Ok(&buf[..len])
+}
+
+/// Writes into a slice, and fails writes that don't fit entirely rather than truncating them.
+struct SliceWriter<'a> {
+    buf: &'a mut [u8],
+    len: usize,
+    /// The length the slice would have needed for the first write that didn't fit.
+    needed: Option<usize>,
+}
+
+impl Write for SliceWriter<'_> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let end = self.len.saturating_add(buf.len());
+        if end > self.buf.len() {
+            self.needed = Some(end);
+            return Err(io::Error::from(io::ErrorKind::WriteZero));
+        }
+        self.buf[self.len..end].copy_from_slice(buf);
+        self.len = end;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Like `to_writer`, but returns the number of bytes written, including length prefixes and
+/// enum tags.
+pub fn to_writer_counted<W, T>(writer: W, value: &T) -> Result<usize, Error>
+where
+    W: Write,
+    T: ?Sized + Serialize,
+{
+    let mut serializer = Serializer::new(CountWriter::new(writer));
+    value.serialize(&mut serializer)?;
+    Ok(serializer.writer.count())
+}
+
+/// Wraps a writer and tracks how many bytes have been written through it, so the running
+/// total can be read back between calls to `to_writer`.
+pub struct CountWriter<W> {
+    inner: W,
+    count: usize,
+}
+
+impl<W> CountWriter<W> {
+    pub fn new(inner: W) -> Self {
+        CountWriter { inner, count: 0 }
+    }
+
+    /// The number of bytes written so far.
+    pub fn count(&self) -> usize {
+        self.count
+    }
+
+    pub fn get_ref(&self) -> &W {
+        &self.inner
+    }
+
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+}
+
+impl<W> Write for CountWriter<W>
+where
+    W: Write,
+{
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        self.count += written;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// A writer that discards its input and counts the bytes.
+struct SizeCounter(u64);
+
+impl Write for SizeCounter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0 += buf.len() as u64;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
 #[cfg(test)]
 mod test {
     #[test]
+    #[allow(clippy::unnecessary_filter_map)]
     fn test_unbounded_sequence() {
         use serde::Serializer;
         let seq = [1, 2, 3];
@@ -433,4 +1313,867 @@ mod test {
             }))
             .is_err());
     }
+
+    #[test]
+    fn test_buffer_unknown_lengths() {
+        use serde::Serializer;
+        let seq = [1u32, 2, 3, 4, 5];
+        let mut serializer = super::Serializer::new(Vec::new());
+        serializer.buffer_unknown_lengths();
+        serializer
+            .collect_seq(seq.iter().filter(|x| *x % 2 == 1))
+            .unwrap();
+        let bytes = serializer.into_inner();
+        assert_eq!(bytes, super::to_vec(&[1u32, 3, 5][..]).unwrap());
+        assert_eq!(crate::from_slice::<Vec<u32>>(&bytes).unwrap(), [1, 3, 5]);
+
+        // Nested unknown lengths
+        let mut serializer = super::Serializer::new(Vec::new());
+        serializer.buffer_unknown_lengths();
+        serializer
+            .collect_seq((0..3).map(|n| Unknown(0..n)))
+            .unwrap();
+        let bytes = serializer.into_inner();
+        assert_eq!(
+            crate::from_slice::<Vec<Vec<u32>>>(&bytes).unwrap(),
+            [vec![], vec![0], vec![0, 1]]
+        );
+    }
+
+    /// Serializes as a sequence without a length.
+    struct Unknown(core::ops::Range<u32>);
+
+    impl serde::Serialize for Unknown {
+        fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            serializer.collect_seq(self.0.clone().filter(|_| true))
+        }
+    }
+
+    #[test]
+    fn test_into_inner() {
+        use serde::Serialize;
+        let mut serializer = super::Serializer::new(Vec::new());
+        1u16.serialize(&mut serializer).unwrap();
+        "hi".serialize(&mut serializer).unwrap();
+        assert_eq!(serializer.get_ref().len(), 5);
+        serializer.get_mut().push(0xff);
+        assert_eq!(serializer.into_inner(), [1, 0, 2, b'h', b'i', 0xff]);
+    }
+
+    #[test]
+    fn test_to_writer_counted() {
+        let s = "hello world".to_string();
+        let mut vec = Vec::new();
+        let count = super::to_writer_counted(&mut vec, &s).unwrap();
+        assert_eq!(count, super::to_vec(&s).unwrap().len());
+        assert_eq!(count, vec.len());
+    }
+
+    #[test]
+    fn test_count_writer() {
+        let mut writer = super::CountWriter::new(Vec::new());
+        super::to_writer(&mut writer, &1000u32).unwrap();
+        assert_eq!(writer.count(), 4);
+        super::to_writer(&mut writer, "hello").unwrap();
+        assert_eq!(writer.count(), 10);
+        assert_eq!(writer.get_ref().len(), 10);
+        assert_eq!(
+            writer.into_inner(),
+            super::to_vec(&(1000u32, "hello")).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_byte_runs() {
+        use crate::test_util::Writes;
+
+        let bytes: Vec<u8> = (0..20000u32).map(|i| i as u8).collect();
+        let mut writes = Writes(Vec::new());
+        super::to_writer(&mut writes, &bytes).unwrap();
+        assert_eq!(writes.0, [3, 8192, 8192, 3616]);
+        let vec = super::to_vec(&bytes).unwrap();
+        assert_eq!(vec, super::to_vec(serde_bytes::Bytes::new(&bytes)).unwrap());
+
+        // Runs of bytes between other elements, which are written as usual. A `bool` is a byte
+        // too, so it joins the run.
+        #[derive(serde_derive::Serialize)]
+        #[serde(untagged)]
+        enum Element {
+            Byte(u8),
+            Flag(bool),
+            Word(u16),
+        }
+        let elements = vec![
+            Element::Byte(1),
+            Element::Byte(2),
+            Element::Flag(true),
+            Element::Word(3),
+            Element::Byte(4),
+        ];
+        let mut writes = Writes(Vec::new());
+        super::to_writer(&mut writes, &elements).unwrap();
+        assert_eq!(writes.0, [1, 3, 2, 1]);
+        assert_eq!(super::to_vec(&elements).unwrap(), [5, 1, 2, 1, 3, 0, 4]);
+
+        // Each element is serialized once, and bytes nested in elements keep their place.
+        struct Counted<'a>(&'a core::cell::Cell<usize>, (u8, u16));
+        impl serde::Serialize for Counted<'_> {
+            fn serialize<S: serde::Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+                self.0.set(self.0.get() + 1);
+                self.1.serialize(s)
+            }
+        }
+        let calls = core::cell::Cell::new(0);
+        let counted = [Counted(&calls, (1, 2)), Counted(&calls, (3, 4))];
+        assert_eq!(super::to_vec(&counted[..]).unwrap(), [2, 1, 2, 0, 3, 4, 0]);
+        assert_eq!(calls.get(), 2);
+    }
+
+    #[test]
+    fn test_pointers() {
+        use alloc::{boxed::Box, rc::Rc, sync::Arc};
+        use serde_derive::{Deserialize, Serialize};
+
+        #[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+        struct Point {
+            x: i16,
+            y: i16,
+        }
+
+        #[derive(Serialize, Deserialize, Debug, PartialEq)]
+        struct Shared {
+            boxed: Box<Point>,
+            rc: Rc<Point>,
+            arc: Arc<Point>,
+            nested: Option<Arc<Vec<u32>>>,
+        }
+
+        let point = Point { x: -1, y: 300 };
+        let bytes = super::to_vec(&point).unwrap();
+        assert_eq!(bytes, [0xff, 0xff, 0x2c, 0x01]);
+        assert_eq!(super::to_vec(&&point).unwrap(), bytes);
+        assert_eq!(super::to_vec(&&&point).unwrap(), bytes);
+        assert_eq!(super::to_vec(&Box::new(point.clone())).unwrap(), bytes);
+        assert_eq!(super::to_vec(&Rc::new(point.clone())).unwrap(), bytes);
+        assert_eq!(super::to_vec(&Arc::new(point.clone())).unwrap(), bytes);
+        assert_eq!(*crate::from_slice::<Box<Point>>(&bytes).unwrap(), point);
+        assert_eq!(*crate::from_slice::<Rc<Point>>(&bytes).unwrap(), point);
+        assert_eq!(*crate::from_slice::<Arc<Point>>(&bytes).unwrap(), point);
+
+        let shared = Shared {
+            boxed: Box::new(point.clone()),
+            rc: Rc::new(point.clone()),
+            arc: Arc::new(point.clone()),
+            nested: Some(Arc::new(vec![7])),
+        };
+        let encoded = super::to_vec(&shared).unwrap();
+        let plain = super::to_vec(&(&point, &point, &point, Some(&[7u32][..]))).unwrap();
+        assert_eq!(encoded, plain);
+        assert_eq!(crate::from_slice::<Shared>(&encoded).unwrap(), shared);
+    }
+
+    #[test]
+    fn test_serialized_size() {
+        let value = (vec!["hello".to_string(); 200], Some(1u64), crate::Uint(300));
+        let size = super::serialized_size(&value).unwrap();
+        let vec = super::to_vec(&value).unwrap();
+        assert_eq!(size, vec.len() as u64);
+        let presized = super::to_vec_presized(&value).unwrap();
+        assert_eq!(presized, vec);
+        assert_eq!(presized.capacity(), presized.len());
+
+        // to_vec serializes once, so values that drain themselves come out whole.
+        struct Drain(core::cell::RefCell<Option<std::vec::IntoIter<u8>>>);
+        impl serde::Serialize for Drain {
+            fn serialize<S: serde::Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+                s.collect_seq(self.0.borrow_mut().take().unwrap_or_default())
+            }
+        }
+        let drain = Drain(core::cell::RefCell::new(Some(vec![1, 2, 3].into_iter())));
+        assert_eq!(super::to_vec(&drain).unwrap(), [3, 1, 2, 3]);
+    }
+
+    #[test]
+    fn test_max_depth() {
+        use serde::Serialize;
+
+        #[derive(serde_derive::Serialize)]
+        struct Chain(Option<Box<Chain>>);
+
+        fn chain(len: usize) -> Chain {
+            (0..len).fold(Chain(None), |c, _| Chain(Some(Box::new(c))))
+        }
+
+        let mut serializer = super::Serializer::with_max_depth(Vec::new(), 64);
+        chain(10).serialize(&mut serializer).unwrap();
+        assert_eq!(serializer.writer, [[1u8; 10].as_ref(), &[0]].concat());
+
+        let mut serializer = super::Serializer::with_max_depth(Vec::new(), 64);
+        assert!(matches!(
+            chain(100).serialize(&mut serializer),
+            Err(crate::Error::DepthLimitExceeded)
+        ));
+
+        assert_eq!(super::to_vec(&chain(100)).unwrap().len(), 101);
+
+        // Tuples nested in tuples, with nothing else in between
+        struct Tuples(usize);
+
+        impl Serialize for Tuples {
+            fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                use serde::ser::SerializeTuple;
+
+                if self.0 == 0 {
+                    return serializer.serialize_u8(0);
+                }
+                let mut tuple = serializer.serialize_tuple(1)?;
+                tuple.serialize_element(&Tuples(self.0 - 1))?;
+                tuple.end()
+            }
+        }
+
+        let mut serializer = super::Serializer::with_max_depth(Vec::new(), 64);
+        Tuples(64).serialize(&mut serializer).unwrap();
+        assert_eq!(serializer.writer, [0]);
+        let mut serializer = super::Serializer::with_max_depth(Vec::new(), 64);
+        assert!(matches!(
+            Tuples(65).serialize(&mut serializer),
+            Err(crate::Error::DepthLimitExceeded)
+        ));
+    }
+
+    #[test]
+    fn test_schema_ordered() {
+        #[derive(serde_derive::Serialize)]
+        struct Inner {
+            b: u8,
+            a: u8,
+        }
+
+        #[derive(serde_derive::Serialize)]
+        struct Outer {
+            inner: Vec<Inner>,
+            id: u16,
+        }
+
+        const ORDER: super::FieldOrder = &[("Inner", &["a", "b"]), ("Outer", &["id", "inner"])];
+
+        let value = Outer {
+            inner: vec![Inner { b: 2, a: 1 }],
+            id: 3,
+        };
+        assert_eq!(super::to_vec(&value).unwrap(), &[1, 2, 1, 3, 0]);
+        assert_eq!(
+            super::to_vec_schema_ordered(&value, ORDER).unwrap(),
+            &[3, 0, 1, 1, 2]
+        );
+
+        const MISSING: super::FieldOrder = &[("Inner", &["a", "b", "c"])];
+        assert!(super::to_vec_schema_ordered(&Inner { b: 2, a: 1 }, MISSING).is_err());
+        const EXTRA: super::FieldOrder = &[("Inner", &["a"])];
+        assert!(super::to_vec_schema_ordered(&Inner { b: 2, a: 1 }, EXTRA).is_err());
+    }
+
+    #[test]
+    fn test_collect_str() {
+        use core::{cell::Cell, fmt};
+        use serde::Serializer;
+
+        struct Point(i32, i32);
+
+        impl fmt::Display for Point {
+            fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                write!(f, "({}, {})", self.0, self.1)
+            }
+        }
+
+        let point = Point(-3, 40);
+        let mut serializer = super::Serializer::new(Vec::new());
+        serializer.collect_str(&point).unwrap();
+        let bytes = serializer.into_inner();
+        assert_eq!(bytes, super::to_vec("(-3, 40)").unwrap());
+
+        let long = "é".repeat(100);
+        let mut serializer = super::Serializer::new(Vec::new());
+        serializer.collect_str(&long).unwrap();
+        assert_eq!(serializer.into_inner(), super::to_vec(&long).unwrap());
+
+        /// Gets longer every time it's formatted.
+        struct Growing(Cell<usize>);
+
+        impl fmt::Display for Growing {
+            fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                self.0.set(self.0.get() + 1);
+                write!(f, "{}", "x".repeat(self.0.get()))
+            }
+        }
+
+        let mut serializer = super::Serializer::new(Vec::new());
+        assert!(matches!(
+            serializer.collect_str(&Growing(Cell::new(0))),
+            Err(crate::Error::Message(_))
+        ));
+        // Nothing past the measured length is written.
+        assert_eq!(serializer.into_inner(), [1]);
+    }
+
+    #[test]
+    fn test_tagged_enums() {
+        use serde_derive::{Deserialize, Serialize};
+
+        #[derive(Debug, Serialize, Deserialize)]
+        #[serde(tag = "type")]
+        enum Internal {
+            Move { x: u8 },
+        }
+
+        #[derive(Debug, Serialize, Deserialize)]
+        #[serde(untagged)]
+        enum Untagged {
+            Move { x: u8 },
+        }
+
+        // The tag is written as a string field, not as a union tag...
+        let bytes = super::to_vec(&Internal::Move { x: 5 }).unwrap();
+        assert_eq!(bytes, [4, b'M', b'o', b'v', b'e', 5]);
+        let bytes = super::to_vec(&Untagged::Move { x: 5 }).unwrap();
+        assert_eq!(bytes, [5]);
+
+        // ...and can't be read back.
+        let error = crate::from_slice::<Internal>(&[4, b'M', b'o', b'v', b'e', 5]).unwrap_err();
+        assert!(matches!(error, crate::Error::AnyUnsupported(_)));
+        assert!(error
+            .to_string()
+            .contains("internally or adjacently tagged enum"));
+        assert!(matches!(
+            crate::from_slice::<Untagged>(&bytes),
+            Err(crate::Error::AnyUnsupported(_))
+        ));
+    }
+
+    #[test]
+    fn test_primitive_slices() {
+        let lens = [0, 1, 3, 1023, 1024, 1025, 5000];
+        for len in lens {
+            let u32s: Vec<u32> = (0..len as u32)
+                .map(|i| i.wrapping_mul(0x0101_0103))
+                .collect();
+            let mut serializer = super::Serializer::new(Vec::new());
+            super::serialize_u32_slice(&mut serializer, &u32s).unwrap();
+            assert_eq!(serializer.into_inner(), super::to_vec(&u32s).unwrap());
+
+            let i16s: Vec<i16> = (0..len as i16).map(|i| -i).collect();
+            let mut serializer = super::Serializer::new(Vec::new());
+            super::serialize_i16_slice(&mut serializer, &i16s).unwrap();
+            assert_eq!(serializer.into_inner(), super::to_vec(&i16s).unwrap());
+
+            let f64s: Vec<f64> = (0..len).map(|i| i as f64 / 3.0).collect();
+            let mut serializer = super::Serializer::new(Vec::new());
+            super::serialize_f64_slice(&mut serializer, &f64s).unwrap();
+            assert_eq!(serializer.into_inner(), super::to_vec(&f64s).unwrap());
+        }
+
+        let mut serializer = super::Serializer::new(Vec::new());
+        super::serialize_u64_slice(&mut serializer, &[1, 1 << 63]).unwrap();
+        assert_eq!(
+            serializer.into_inner(),
+            [2, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0x80]
+        );
+
+        // Floats follow the serializer's config.
+        let nans = [f32::from_bits(0x7fa0_0001), 1.0];
+        let mut serializer = super::Serializer::new(Vec::new());
+        serializer.canonical_nan();
+        super::serialize_f32_slice(&mut serializer, &nans).unwrap();
+        let mut expected = super::Serializer::new(Vec::new());
+        expected.canonical_nan();
+        serde::Serialize::serialize(&nans[..], &mut expected).unwrap();
+        assert_eq!(serializer.into_inner(), expected.into_inner());
+    }
+
+    #[test]
+    fn test_reset() {
+        use serde::Serialize;
+
+        let mut serializer = super::Serializer::with_max_depth(Vec::new(), 2);
+        let deep = Some(Some(Some(1u8)));
+        assert!(matches!(
+            deep.serialize(&mut serializer),
+            Err(crate::Error::DepthLimitExceeded)
+        ));
+        // The failed value's levels would count against the next one.
+        serializer.get_mut().clear();
+        serializer.reset();
+        Some(Some(2u8)).serialize(&mut serializer).unwrap();
+        assert_eq!(serializer.get_ref(), &[1, 1, 2]);
+    }
+
+    #[test]
+    fn test_to_vec_in() {
+        let mut buf = Vec::with_capacity(128);
+        let ptr = buf.as_ptr();
+        super::to_vec_in(&mut buf, &(1u8, "ab")).unwrap();
+        super::to_vec_in(&mut buf, &7u16).unwrap();
+        assert_eq!(buf, [1, 2, b'a', b'b', 7, 0]);
+        assert_eq!(buf.as_ptr(), ptr);
+
+        /// Fails after the `u8` before it has been written.
+        struct Unknown;
+
+        impl serde::Serialize for Unknown {
+            fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                serializer.collect_seq([1u8, 2].iter().filter(|x| **x > 1))
+            }
+        }
+
+        let unknown = super::to_vec_in(&mut buf, &(3u8, Unknown));
+        assert!(matches!(unknown, Err(crate::Error::SequenceLengthRequired)));
+        assert_eq!(buf, [1, 2, b'a', b'b', 7, 0]);
+
+        buf.clear();
+        super::to_vec_in(&mut buf, "x".repeat(100).as_str()).unwrap();
+        assert_eq!(buf, super::to_vec(&"x".repeat(100)).unwrap());
+        assert_eq!(buf.as_ptr(), ptr);
+    }
+
+    #[test]
+    fn test_little_endian() {
+        // Spelled out byte for byte, so that a native-endian encoding fails on big-endian hosts.
+        assert_eq!(super::to_vec(&0x7fu8).unwrap(), [0x7f]);
+        assert_eq!(super::to_vec(&-2i8).unwrap(), [0xfe]);
+        assert_eq!(super::to_vec(&513u16).unwrap(), [1, 2]);
+        assert_eq!(super::to_vec(&-2i16).unwrap(), [0xfe, 0xff]);
+        assert_eq!(super::to_vec(&0x0102_0304u32).unwrap(), [4, 3, 2, 1]);
+        assert_eq!(super::to_vec(&-2i32).unwrap(), [0xfe, 0xff, 0xff, 0xff]);
+        assert_eq!(
+            super::to_vec(&0x0102_0304_0506_0708u64).unwrap(),
+            [8, 7, 6, 5, 4, 3, 2, 1]
+        );
+        assert_eq!(
+            super::to_vec(&-2i64).unwrap(),
+            [0xfe, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff]
+        );
+        assert_eq!(super::to_vec(&1.0f32).unwrap(), [0, 0, 0x80, 0x3f]);
+        assert_eq!(super::to_vec(&-2.5f32).unwrap(), [0, 0, 0x20, 0xc0]);
+        assert_eq!(
+            super::to_vec(&1.0f64).unwrap(),
+            [0, 0, 0, 0, 0, 0, 0xf0, 0x3f]
+        );
+        assert_eq!(
+            super::to_vec(&-2.5f64).unwrap(),
+            [0, 0, 0, 0, 0, 0, 0x04, 0xc0]
+        );
+        // Characters are u32 codepoints.
+        assert_eq!(super::to_vec(&'\u{1f600}').unwrap(), [0, 0xf6, 1, 0]);
+    }
+
+    #[test]
+    fn test_128_bit() {
+        let vectors: &[(i128, [u8; 16])] = &[
+            (0, [0; 16]),
+            (
+                -2,
+                [
+                    0xfe, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff,
+                    0xff, 0xff, 0xff,
+                ],
+            ),
+            (1 << 64, [0, 0, 0, 0, 0, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0, 0]),
+            (
+                i128::MIN,
+                [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0x80],
+            ),
+        ];
+        for (value, bytes) in vectors {
+            if cfg!(feature = "i128") {
+                assert_eq!(super::to_vec(value).unwrap(), bytes);
+                assert_eq!(super::to_vec(&(*value as u128)).unwrap(), bytes);
+                assert_eq!(crate::from_slice::<i128>(bytes).unwrap(), *value);
+                assert_eq!(crate::from_slice::<u128>(bytes).unwrap(), *value as u128);
+            } else {
+                assert!(matches!(
+                    super::to_vec(value),
+                    Err(crate::Error::I128Disabled)
+                ));
+                assert!(matches!(
+                    crate::from_slice::<u128>(bytes),
+                    Err(crate::Error::I128Disabled)
+                ));
+            }
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn test_sort_map_keys() {
+        use serde::Serialize;
+        use std::collections::HashMap;
+        let to_vec = |map: &HashMap<String, u32>| {
+            let mut serializer = super::Serializer::new(Vec::new());
+            serializer.sort_map_keys();
+            map.serialize(&mut serializer).unwrap();
+            serializer.into_inner()
+        };
+        let forward: HashMap<String, u32> = (0..100).map(|n| (n.to_string(), n)).collect();
+        let backward: HashMap<String, u32> = (0..100).rev().map(|n| (n.to_string(), n)).collect();
+        let bytes = to_vec(&forward);
+        assert_eq!(bytes, to_vec(&backward));
+        assert_eq!(
+            crate::from_slice::<HashMap<String, u32>>(&bytes).unwrap(),
+            forward
+        );
+
+        // Sorted by encoded key, so the length prefix of the strings comes first
+        let map: HashMap<String, u32> = vec![("aa".into(), 1), ("b".into(), 2)]
+            .into_iter()
+            .collect();
+        assert_eq!(
+            to_vec(&map),
+            [2, 1, b'b', 2, 0, 0, 0, 2, b'a', b'a', 1, 0, 0, 0]
+        );
+    }
+
+    #[test]
+    fn test_assume_sorted_map_keys() {
+        use alloc::{collections::BTreeMap, rc::Rc, string::String};
+        use core::cell::RefCell;
+        use serde::{Serialize, Serializer};
+
+        /// A writer whose output can be looked at while serializing.
+        struct Shared(Rc<RefCell<Vec<u8>>>);
+
+        impl super::Write for Shared {
+            fn write(&mut self, buf: &[u8]) -> super::io::Result<usize> {
+                self.0.borrow_mut().extend_from_slice(buf);
+                Ok(buf.len())
+            }
+
+            fn flush(&mut self) -> super::io::Result<()> {
+                Ok(())
+            }
+        }
+
+        /// Serializes as a `u8`, noting how much had been written by then.
+        struct Probe(Rc<RefCell<Vec<u8>>>, Rc<RefCell<Vec<usize>>>);
+
+        impl Serialize for Probe {
+            fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                self.1.borrow_mut().push(self.0.borrow().len());
+                serializer.serialize_u8(0)
+            }
+        }
+
+        let serialize = |configure: fn(&mut super::Serializer<Shared>)| {
+            let out = Rc::new(RefCell::new(Vec::new()));
+            let seen = Rc::new(RefCell::new(Vec::new()));
+            let map: BTreeMap<u8, Probe> = (1..=3)
+                .map(|k| (k, Probe(out.clone(), seen.clone())))
+                .collect();
+            let mut serializer = super::Serializer::new(Shared(out.clone()));
+            configure(&mut serializer);
+            map.serialize(&mut serializer).unwrap();
+            let bytes = out.borrow().clone();
+            let seen = seen.borrow().clone();
+            (bytes, seen)
+        };
+
+        // Each entry is written before the next is serialized...
+        let (bytes, seen) = serialize(|s| s.check_sorted_map_keys());
+        assert_eq!(bytes, [3, 1, 0, 2, 0, 3, 0]);
+        assert_eq!(seen, [2, 4, 6]);
+        let (_, seen) = serialize(|s| s.assume_sorted_map_keys());
+        assert_eq!(seen, [2, 4, 6]);
+        // ...rather than held until the map ends.
+        let (sorted, seen) = serialize(|s| s.sort_map_keys());
+        assert_eq!(sorted, bytes);
+        assert_eq!(seen, [1, 1, 1]);
+
+        let check = |entries: &[(&str, u8)]| {
+            let mut serializer = super::Serializer::new(Vec::new());
+            serializer.check_sorted_map_keys();
+            serializer.collect_map(entries.iter().copied())
+        };
+        assert!(check(&[("a", 1), ("b", 2)]).is_ok());
+        assert!(matches!(
+            check(&[("b", 1), ("a", 2)]),
+            Err(crate::Error::UnsortedMapKeys)
+        ));
+        assert!(matches!(
+            check(&[("a", 1), ("a", 2)]),
+            Err(crate::Error::UnsortedMapKeys)
+        ));
+        // `Ord` order, but the longer string's length prefix sorts it last.
+        let map: BTreeMap<String, u8> = [("aa".into(), 1), ("b".into(), 2)].into();
+        let mut serializer = super::Serializer::new(Vec::new());
+        serializer.check_sorted_map_keys();
+        assert!(matches!(
+            map.serialize(&mut serializer),
+            Err(crate::Error::UnsortedMapKeys)
+        ));
+    }
+
+    #[test]
+    fn test_transcode() {
+        use super::SerializerConfig;
+        use crate::DeserializerConfig;
+        use alloc::{collections::BTreeMap, string::String};
+        use serde_derive::{Deserialize, Serialize};
+
+        #[derive(Serialize, Deserialize)]
+        struct Message {
+            flag: bool,
+            name: String,
+            scores: BTreeMap<String, u8>,
+        }
+
+        let lenient = [
+            2, // bool
+            0x82, 0x00, b'h', b'i', // non-minimal length
+            2, 2, b'a', b'a', 1, 1, b'b', 2, // map in `Ord` order
+        ];
+        let canonical = [1, 2, b'h', b'i', 2, 1, b'b', 2, 2, b'a', b'a', 1];
+        let lenient_in = DeserializerConfig::default();
+        let strict_in = DeserializerConfig::strict();
+        let canonical_out = SerializerConfig {
+            sort_map_keys: true,
+            ..SerializerConfig::default()
+        };
+        assert!(crate::from_slice_with_config::<Message>(&lenient, strict_in).is_err());
+        let transcoded =
+            super::transcode::<Message>(&lenient, &lenient_in, &canonical_out).unwrap();
+        assert_eq!(transcoded, canonical);
+        assert!(crate::from_slice_with_config::<Message>(&transcoded, strict_in).is_ok());
+
+        assert!(super::transcode::<Message>(&lenient, &strict_in, &canonical_out).is_err());
+        let again = super::transcode::<Message>(&canonical, &strict_in, &canonical_out).unwrap();
+        assert_eq!(again, canonical);
+
+        let mut trailing = canonical.to_vec();
+        trailing.push(0);
+        assert!(matches!(
+            super::transcode::<Message>(&trailing, &lenient_in, &SerializerConfig::default()),
+            Err(crate::Error::TrailingData)
+        ));
+    }
+
+    #[test]
+    fn test_serialize_map_sorted_iter() {
+        use alloc::collections::BTreeMap;
+        let map: BTreeMap<u8, &str> = vec![(1, "one"), (2, "two"), (200, "two hundred")]
+            .into_iter()
+            .collect();
+        let mut vec = Vec::new();
+        super::serialize_map_sorted_iter(&mut vec, &map, map.len()).unwrap();
+        assert_eq!(vec, super::to_vec(&map).unwrap());
+
+        let mut vec = Vec::new();
+        assert!(matches!(
+            super::serialize_map_sorted_iter(&mut vec, &map, 2),
+            Err(crate::Error::MapLengthMismatch { expected: 2 })
+        ));
+        let mut vec = Vec::new();
+        assert!(matches!(
+            super::serialize_map_sorted_iter(&mut vec, &map, 4),
+            Err(crate::Error::MapLengthMismatch { expected: 4 })
+        ));
+
+        // The entry past `len` is left in the iterator.
+        let mut entries = map.iter();
+        let mut vec = Vec::new();
+        assert!(matches!(
+            super::serialize_map_sorted_iter(&mut vec, entries.by_ref(), 2),
+            Err(crate::Error::MapLengthMismatch { expected: 2 })
+        ));
+        assert_eq!(entries.next(), Some((&200, &"two hundred")));
+
+        // A unit key encodes as nothing, which still counts as in order the first time.
+        let mut vec = Vec::new();
+        super::serialize_map_sorted_iter(&mut vec, [((), 1u8)], 1).unwrap();
+        assert_eq!(vec, [1, 1]);
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    fn test_serialize_map_sorted_iter_unsorted() {
+        use alloc::collections::BTreeMap;
+        // 256 encodes as [0, 1], which sorts before 1 as [1, 0]
+        let map: BTreeMap<u16, ()> = vec![(1, ()), (256, ())].into_iter().collect();
+        let mut vec = Vec::new();
+        assert!(matches!(
+            super::serialize_map_sorted_iter(&mut vec, &map, map.len()),
+            Err(crate::Error::UnsortedMapKeys)
+        ));
+        // Equal keys aren't canonical either
+        let mut vec = Vec::new();
+        assert!(matches!(
+            super::serialize_map_sorted_iter(&mut vec, [(1u8, 1u8), (1, 2)], 2),
+            Err(crate::Error::UnsortedMapKeys)
+        ));
+    }
+
+    #[test]
+    fn test_to_slice() {
+        let value = ("hi".to_string(), 258u16);
+        let expected = super::to_vec(&value).unwrap();
+
+        let mut buf = [0xaa; 16];
+        let written = super::to_slice(&mut buf, &value).unwrap();
+        assert_eq!(written, &expected[..]);
+        assert_eq!(written.len(), 5);
+        assert_eq!(buf[5..], [0xaa; 11]);
+
+        let mut exact = [0; 5];
+        assert_eq!(super::to_slice(&mut exact, &value).unwrap(), &expected[..]);
+
+        // The string fits, but the u16 after it doesn't.
+        let mut short = [0; 4];
+        assert!(matches!(
+            super::to_slice(&mut short, &value),
+            Err(crate::Error::BufferFull { needed_at_least: 5 })
+        ));
+        assert!(matches!(
+            super::to_slice(&mut [], &()),
+            Ok(written) if written.is_empty()
+        ));
+    }
+
+    #[test]
+    fn test_human_readable() {
+        use crate::{de::Deserializer, ser::Serializer};
+        use alloc::string::String;
+        use serde::{ser, Deserialize, Serialize};
+
+        /// Degrees Celsius, written out as text in human readable mode.
+        #[derive(Debug, PartialEq)]
+        struct Celsius(i16);
+
+        impl Serialize for Celsius {
+            fn serialize<S: ser::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                if serializer.is_human_readable() {
+                    serializer.collect_str(&format_args!("{}C", self.0))
+                } else {
+                    serializer.serialize_i16(self.0)
+                }
+            }
+        }
+
+        impl<'de> Deserialize<'de> for Celsius {
+            fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+                use serde::de::Error as _;
+                if deserializer.is_human_readable() {
+                    let text = String::deserialize(deserializer)?;
+                    let degrees = text.strip_suffix('C').and_then(|d| d.parse().ok());
+                    degrees
+                        .map(Celsius)
+                        .ok_or_else(|| D::Error::custom("bad temperature"))
+                } else {
+                    i16::deserialize(deserializer).map(Celsius)
+                }
+            }
+        }
+
+        let value = vec![Celsius(-5), Celsius(21)];
+        for human_readable in [false, true] {
+            let mut vec = Vec::new();
+            let mut serializer = Serializer::new(&mut vec);
+            if human_readable {
+                serializer.human_readable();
+            }
+            value.serialize(&mut serializer).unwrap();
+            if human_readable {
+                assert_eq!(vec, b"\x02\x03-5C\x0321C");
+            } else {
+                assert_eq!(vec, [2, 0xfb, 0xff, 21, 0]);
+            }
+
+            let mut deserializer = Deserializer::new(&vec[..]);
+            if human_readable {
+                deserializer.human_readable();
+            }
+            assert_eq!(
+                Vec::<Celsius>::deserialize(&mut deserializer).unwrap(),
+                value
+            );
+        }
+    }
+
+    #[test]
+    fn test_max_variant_tag() {
+        use crate::ser::Serializer;
+        use serde::Serialize;
+        use serde_derive::Serialize;
+
+        #[derive(Serialize)]
+        enum Op {
+            Nop,
+            Push(u8),
+            Pop,
+        }
+
+        let mut vec = Vec::new();
+        let mut serializer = Serializer::new(&mut vec);
+        serializer.max_variant_tag(1);
+        Op::Nop.serialize(&mut serializer).unwrap();
+        Op::Push(7).serialize(&mut serializer).unwrap();
+        assert!(matches!(
+            Op::Pop.serialize(&mut serializer),
+            Err(crate::Error::VariantTagOutOfRange(2))
+        ));
+        assert_eq!(vec, [0, 1, 7]);
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn test_config() {
+        use crate::ser::{to_vec_with_config, to_writer_with_config, Serializer, SerializerConfig};
+        use alloc::string::String;
+        use serde::Serialize;
+        use std::collections::HashMap;
+
+        /// Serializes as its name in human readable mode, and as its index otherwise.
+        #[derive(Clone, Copy, PartialEq, Eq, Hash)]
+        struct Color(u8);
+
+        impl Serialize for Color {
+            fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                if serializer.is_human_readable() {
+                    serializer.serialize_str(["red", "green"][self.0 as usize])
+                } else {
+                    serializer.serialize_u8(self.0)
+                }
+            }
+        }
+
+        let map: HashMap<Color, String> = vec![
+            (Color(1), "grass".to_string()),
+            (Color(0), "brick".to_string()),
+        ]
+        .into_iter()
+        .collect();
+        let config = SerializerConfig {
+            sort_map_keys: true,
+            human_readable: true,
+            ..SerializerConfig::default()
+        };
+        // Keys sort by their encoded bytes, so the shorter "red" comes first.
+        let expected = b"\x02\x03red\x05brick\x05green\x05grass";
+        assert_eq!(to_vec_with_config(&map, config).unwrap(), expected);
+        let mut vec = Vec::new();
+        to_writer_with_config(&mut vec, &map, config).unwrap();
+        assert_eq!(vec, expected);
+        assert_eq!(Serializer::with_config(&mut vec, config).config(), config);
+
+        let sorted = SerializerConfig {
+            sort_map_keys: true,
+            ..SerializerConfig::default()
+        };
+        assert_eq!(
+            to_vec_with_config(&map, sorted).unwrap(),
+            b"\x02\x00\x05brick\x01\x05grass"
+        );
+        assert_eq!(
+            super::to_vec(&Color(1)).unwrap(),
+            to_vec_with_config(&Color(1), SerializerConfig::default()).unwrap()
+        );
+    }
 }
@@ -0,0 +1,282 @@
+//! Estimate serialized sizes without serializing.
+//!
+//! [`BareSize`] adds up the sizes of a value's parts: fixed-size types count their exact size,
+//! and strings, sequences and maps count their length prefix plus their contents. It's meant
+//! for sizing buffers; `serialized_size` gives the exact size at the cost of a serialization
+//! pass. With the `derive` feature it can be derived for structs and enums, leaving out fields
+//! marked `#[serde(skip)]`. Serde attributes that change the encoding, and schemas given with
+//! `#[bare(schema = "...")]`, aren't taken into account.
+use crate::{varint_len, ByteBuf, Data, FixedSeq, Int, Uint, F32, F64};
+use alloc::{
+    boxed::Box,
+    collections::{BTreeMap, BTreeSet},
+    rc::Rc,
+    string::String,
+    sync::Arc,
+    vec::Vec,
+};
+
+/// A value whose serialized size can be estimated.
+pub trait BareSize {
+    /// Returns the number of bytes `self` is expected to serialize to.
+    fn approx_bare_size(&self) -> usize;
+}
+
+/// The size of the `uint` length prefix for `len`.
+fn prefix_len(len: usize) -> usize {
+    varint_len(len as u64)
+}
+
+macro_rules! impl_fixed {
+    ($($ty:ty => $size:expr,)*) => {
+        $(
+            impl BareSize for $ty {
+                fn approx_bare_size(&self) -> usize {
+                    $size
+                }
+            }
+        )*
+    };
+}
+
+impl_fixed! {
+    bool => 1,
+    u8 => 1,
+    u16 => 2,
+    u32 => 4,
+    u64 => 8,
+    u128 => 16,
+    i8 => 1,
+    i16 => 2,
+    i32 => 4,
+    i64 => 8,
+    i128 => 16,
+    f32 => 4,
+    f64 => 8,
+    F32 => 4,
+    F64 => 8,
+    char => 4,
+    () => 0,
+}
+
+impl BareSize for Uint {
+    fn approx_bare_size(&self) -> usize {
+        self.encoded_len()
+    }
+}
+
+impl BareSize for Int {
+    fn approx_bare_size(&self) -> usize {
+        self.encoded_len()
+    }
+}
+
+impl BareSize for str {
+    fn approx_bare_size(&self) -> usize {
+        prefix_len(self.len()) + self.len()
+    }
+}
+
+impl BareSize for String {
+    fn approx_bare_size(&self) -> usize {
+        self.as_str().approx_bare_size()
+    }
+}
+
+impl BareSize for ByteBuf {
+    fn approx_bare_size(&self) -> usize {
+        self.as_slice().approx_bare_size()
+    }
+}
+
+impl<const N: usize> BareSize for Data<N> {
+    fn approx_bare_size(&self) -> usize {
+        N
+    }
+}
+
+impl<T: BareSize, const N: usize> BareSize for FixedSeq<T, N> {
+    fn approx_bare_size(&self) -> usize {
+        self.0.approx_bare_size()
+    }
+}
+
+impl<T: BareSize, const N: usize> BareSize for [T; N] {
+    fn approx_bare_size(&self) -> usize {
+        self.iter().map(T::approx_bare_size).sum()
+    }
+}
+
+impl<T: BareSize> BareSize for [T] {
+    fn approx_bare_size(&self) -> usize {
+        prefix_len(self.len()) + self.iter().map(T::approx_bare_size).sum::<usize>()
+    }
+}
+
+impl<T: BareSize> BareSize for Vec<T> {
+    fn approx_bare_size(&self) -> usize {
+        self.as_slice().approx_bare_size()
+    }
+}
+
+#[cfg(feature = "smallvec")]
+impl<A> BareSize for smallvec::SmallVec<A>
+where
+    A: smallvec::Array,
+    A::Item: BareSize,
+{
+    fn approx_bare_size(&self) -> usize {
+        self.as_slice().approx_bare_size()
+    }
+}
+
+impl<T: BareSize> BareSize for BTreeSet<T> {
+    fn approx_bare_size(&self) -> usize {
+        prefix_len(self.len()) + self.iter().map(T::approx_bare_size).sum::<usize>()
+    }
+}
+
+impl<T: BareSize> BareSize for Option<T> {
+    fn approx_bare_size(&self) -> usize {
+        1 + self.as_ref().map_or(0, T::approx_bare_size)
+    }
+}
+
+impl<K: BareSize, V: BareSize> BareSize for BTreeMap<K, V> {
+    fn approx_bare_size(&self) -> usize {
+        let entries: usize = self
+            .iter()
+            .map(|(k, v)| k.approx_bare_size() + v.approx_bare_size())
+            .sum();
+        prefix_len(self.len()) + entries
+    }
+}
+
+#[cfg(feature = "std")]
+impl<K: BareSize, V: BareSize, S> BareSize for std::collections::HashMap<K, V, S> {
+    fn approx_bare_size(&self) -> usize {
+        let entries: usize = self
+            .iter()
+            .map(|(k, v)| k.approx_bare_size() + v.approx_bare_size())
+            .sum();
+        prefix_len(self.len()) + entries
+    }
+}
+
+#[cfg(feature = "indexmap")]
+impl<K: BareSize, V: BareSize, S> BareSize for indexmap::IndexMap<K, V, S> {
+    fn approx_bare_size(&self) -> usize {
+        let entries: usize = self
+            .iter()
+            .map(|(k, v)| k.approx_bare_size() + v.approx_bare_size())
+            .sum();
+        prefix_len(self.len()) + entries
+    }
+}
+
+impl<T: ?Sized + BareSize> BareSize for Box<T> {
+    fn approx_bare_size(&self) -> usize {
+        (**self).approx_bare_size()
+    }
+}
+
+impl<T: ?Sized + BareSize> BareSize for Rc<T> {
+    fn approx_bare_size(&self) -> usize {
+        (**self).approx_bare_size()
+    }
+}
+
+impl<T: ?Sized + BareSize> BareSize for Arc<T> {
+    fn approx_bare_size(&self) -> usize {
+        (**self).approx_bare_size()
+    }
+}
+
+impl<T: ?Sized + BareSize> BareSize for &T {
+    fn approx_bare_size(&self) -> usize {
+        (**self).approx_bare_size()
+    }
+}
+
+macro_rules! impl_tuple {
+    ($($name:ident $index:tt)+) => {
+        impl<$($name: BareSize),+> BareSize for ($($name,)+) {
+            fn approx_bare_size(&self) -> usize {
+                0 $(+ self.$index.approx_bare_size())+
+            }
+        }
+    };
+}
+
+impl_tuple!(A 0);
+impl_tuple!(A 0 B 1);
+impl_tuple!(A 0 B 1 C 2);
+impl_tuple!(A 0 B 1 C 2 D 3);
+impl_tuple!(A 0 B 1 C 2 D 3 E 4);
+impl_tuple!(A 0 B 1 C 2 D 3 E 4 F 5);
+impl_tuple!(A 0 B 1 C 2 D 3 E 4 F 5 G 6);
+impl_tuple!(A 0 B 1 C 2 D 3 E 4 F 5 G 6 H 7);
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use serde_bare_derive::BareSize;
+    use serde_derive::Serialize;
+
+    #[derive(BareSize, Serialize)]
+    enum Shape {
+        Circle(f64),
+        Square { side: f32 },
+        Empty,
+    }
+
+    #[derive(BareSize, Serialize)]
+    struct Mixed {
+        id: u32,
+        name: String,
+        #[serde(with = "serde_bytes")]
+        token: Vec<u8>,
+        #[serde(skip)]
+        cache: u64,
+        tags: Vec<String>,
+        parent: Option<Box<Mixed>>,
+        shapes: BTreeMap<u8, Shape>,
+        count: Uint,
+        pair: (i16, Data<3>),
+    }
+
+    #[test]
+    fn test_mixed() {
+        let parent = Mixed {
+            id: 1,
+            name: String::new(),
+            token: Vec::new(),
+            cache: 0,
+            tags: Vec::new(),
+            parent: None,
+            shapes: BTreeMap::new(),
+            count: Uint(0),
+            pair: (0, Data([0; 3])),
+        };
+        let mut shapes = BTreeMap::new();
+        shapes.insert(1, Shape::Circle(1.0));
+        shapes.insert(2, Shape::Square { side: 2.0 });
+        shapes.insert(3, Shape::Empty);
+        let mixed = Mixed {
+            id: 2,
+            name: "x".repeat(200),
+            token: vec![7; 300],
+            cache: 9,
+            tags: vec!["a".into(), "bc".into()],
+            parent: Some(Box::new(parent)),
+            shapes,
+            count: Uint(1 << 20),
+            pair: (-1, Data([1, 2, 3])),
+        };
+        // Exact, as nothing here changes the encoding
+        assert_eq!(
+            mixed.approx_bare_size(),
+            crate::to_vec(&mixed).unwrap().len()
+        );
+    }
+}
@@ -0,0 +1,114 @@
+//! Encodings for fieldless enums from the standard library that serde has no impls for, for use
+//! with `#[serde(with = "...")]`.
+//!
+//! Like derived enums, each serializes as a `uint` holding the index of its variant in the order
+//! the standard library declares them.
+
+macro_rules! index_enum {
+    ($(#[$attr:meta])* $module:ident, $ty:path, [$($variant:ident),+]) => {
+        $(#[$attr])*
+        pub mod $module {
+            use crate::Uint;
+            use core::convert::TryFrom;
+            use serde::{de::Error as _, Deserialize, Deserializer, Serialize, Serializer};
+            use $ty as Enum;
+
+            const VARIANTS: &[Enum] = &[$(Enum::$variant),+];
+
+            pub fn serialize<S>(value: &Enum, serializer: S) -> Result<S::Ok, S::Error>
+            where
+                S: Serializer,
+            {
+                let index = VARIANTS
+                    .iter()
+                    .position(|v| v == value)
+                    .expect("VARIANTS lists every variant");
+                Uint(index as u64).serialize(serializer)
+            }
+
+            pub fn deserialize<'de, D>(deserializer: D) -> Result<Enum, D::Error>
+            where
+                D: Deserializer<'de>,
+            {
+                let Uint(index) = Uint::deserialize(deserializer)?;
+                usize::try_from(index)
+                    .ok()
+                    .and_then(|index| VARIANTS.get(index))
+                    .copied()
+                    .ok_or_else(|| D::Error::custom("invalid variant index"))
+            }
+        }
+    };
+}
+
+index_enum!(
+    /// `core::cmp::Ordering`: `Less` is 0, `Equal` is 1 and `Greater` is 2.
+    ordering,
+    core::cmp::Ordering,
+    [Less, Equal, Greater]
+);
+
+index_enum!(
+    /// `core::num::FpCategory`: `Nan` is 0, `Infinite` is 1, `Zero` is 2, `Subnormal` is 3 and
+    /// `Normal` is 4.
+    fp_category,
+    core::num::FpCategory,
+    [Nan, Infinite, Zero, Subnormal, Normal]
+);
+
+index_enum!(
+    /// `std::net::Shutdown`: `Read` is 0, `Write` is 1 and `Both` is 2.
+    #[cfg(feature = "std")]
+    shutdown,
+    std::net::Shutdown,
+    [Read, Write, Both]
+);
+
+#[cfg(test)]
+mod test {
+    use core::{cmp::Ordering, num::FpCategory};
+    use serde_derive::{Deserialize, Serialize};
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    struct Enums {
+        #[serde(with = "super::ordering")]
+        ordering: Ordering,
+        #[serde(with = "super::fp_category")]
+        category: FpCategory,
+    }
+
+    #[test]
+    fn test_index_enums() {
+        let cases = [
+            (Ordering::Less, FpCategory::Nan, [0, 0]),
+            (Ordering::Equal, FpCategory::Subnormal, [1, 3]),
+            (Ordering::Greater, FpCategory::Normal, [2, 4]),
+        ];
+        for (ordering, category, bytes) in cases {
+            let value = Enums { ordering, category };
+            assert_eq!(crate::to_vec(&value).unwrap(), bytes);
+            assert_eq!(crate::from_slice::<Enums>(&bytes).unwrap(), value);
+        }
+        assert!(crate::from_slice::<Enums>(&[3, 0]).is_err());
+        assert!(crate::from_slice::<Enums>(&[0, 5]).is_err());
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn test_shutdown() {
+        use std::net::Shutdown;
+
+        #[derive(Serialize, Deserialize, Debug, PartialEq)]
+        struct Wrapper(#[serde(with = "super::shutdown")] Shutdown);
+
+        let variants = [Shutdown::Read, Shutdown::Write, Shutdown::Both];
+        for (i, &shutdown) in variants.iter().enumerate() {
+            let bytes = crate::to_vec(&Wrapper(shutdown)).unwrap();
+            assert_eq!(bytes, [i as u8]);
+            assert_eq!(
+                crate::from_slice::<Wrapper>(&bytes).unwrap(),
+                Wrapper(shutdown)
+            );
+        }
+    }
+}
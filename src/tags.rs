@@ -0,0 +1,502 @@
+//! Explicit union tags for enums.
+//!
+//! serde passes each variant's declaration index as the union tag, which doesn't match schemas
+//! that assign tags explicitly. `serialize` and `deserialize` are meant to be called from
+//! `#[serde(serialize_with = "...")]` and `#[serde(deserialize_with = "...")]` functions, with
+//! `tags` listing the schema's tag for each variant in declaration order.
+//!
+//! Alternatively, an enum implementing [`BareUnion`] can be wrapped in [`Union`], which uses the
+//! enum's own tags. With the `derive` feature, `BareUnion` can be derived from `#[bare(tag = N)]`
+//! attributes on the variants.
+use core::fmt;
+use serde::{de, ser, Deserialize, Serialize};
+
+/// An enum with explicit union tags.
+pub trait BareUnion {
+    /// The tag of each variant, in declaration order.
+    const TAGS: &'static [u32];
+}
+
+/// Serializes the enum it wraps with the tags from its `BareUnion` impl instead of the variant
+/// indexes.
+#[derive(Copy, Clone, Debug, Default, Ord, PartialOrd, Eq, PartialEq, Hash)]
+pub struct Union<T>(pub T);
+
+impl<T> Serialize for Union<T>
+where
+    T: BareUnion + Serialize,
+{
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: ser::Serializer,
+    {
+        serialize(&self.0, T::TAGS, serializer)
+    }
+}
+
+impl<'de, T> Deserialize<'de> for Union<T>
+where
+    T: BareUnion + Deserialize<'de>,
+{
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: de::Deserializer<'de>,
+    {
+        deserialize(T::TAGS, deserializer).map(Union)
+    }
+}
+
+/// Serialize an enum, replacing the tag of the variant at index `i` with `tags[i]`.
+pub fn serialize<T, S>(value: &T, tags: &'static [u32], serializer: S) -> Result<S::Ok, S::Error>
+where
+    T: ?Sized + Serialize,
+    S: ser::Serializer,
+{
+    value.serialize(TagSerializer {
+        inner: serializer,
+        tags,
+    })
+}
+
+/// Deserialize an enum, mapping a tag found in `tags` at index `i` back to variant `i`.
+pub fn deserialize<'de, T, D>(tags: &'static [u32], deserializer: D) -> Result<T, D::Error>
+where
+    T: Deserialize<'de>,
+    D: de::Deserializer<'de>,
+{
+    T::deserialize(TagDeserializer {
+        inner: deserializer,
+        tags,
+    })
+}
+
+fn tag_for<E: ser::Error>(tags: &[u32], variant_index: u32) -> Result<u32, E> {
+    tags.get(variant_index as usize).copied().ok_or_else(|| {
+        E::custom(format_args!(
+            "no explicit tag given for variant {}",
+            variant_index
+        ))
+    })
+}
+
+struct TagSerializer<S> {
+    inner: S,
+    tags: &'static [u32],
+}
+
+impl<S> ser::Serializer for TagSerializer<S>
+where
+    S: ser::Serializer,
+{
+    type Ok = S::Ok;
+    type Error = S::Error;
+    type SerializeSeq = S::SerializeSeq;
+    type SerializeTuple = S::SerializeTuple;
+    type SerializeTupleStruct = S::SerializeTupleStruct;
+    type SerializeTupleVariant = S::SerializeTupleVariant;
+    type SerializeMap = S::SerializeMap;
+    type SerializeStruct = S::SerializeStruct;
+    type SerializeStructVariant = S::SerializeStructVariant;
+
+    fn serialize_bool(self, v: bool) -> Result<Self::Ok, Self::Error> {
+        self.inner.serialize_bool(v)
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<Self::Ok, Self::Error> {
+        self.inner.serialize_i8(v)
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<Self::Ok, Self::Error> {
+        self.inner.serialize_i16(v)
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<Self::Ok, Self::Error> {
+        self.inner.serialize_i32(v)
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<Self::Ok, Self::Error> {
+        self.inner.serialize_i64(v)
+    }
+
+    fn serialize_i128(self, v: i128) -> Result<Self::Ok, Self::Error> {
+        self.inner.serialize_i128(v)
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<Self::Ok, Self::Error> {
+        self.inner.serialize_u8(v)
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<Self::Ok, Self::Error> {
+        self.inner.serialize_u16(v)
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<Self::Ok, Self::Error> {
+        self.inner.serialize_u32(v)
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<Self::Ok, Self::Error> {
+        self.inner.serialize_u64(v)
+    }
+
+    fn serialize_u128(self, v: u128) -> Result<Self::Ok, Self::Error> {
+        self.inner.serialize_u128(v)
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<Self::Ok, Self::Error> {
+        self.inner.serialize_f32(v)
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<Self::Ok, Self::Error> {
+        self.inner.serialize_f64(v)
+    }
+
+    fn serialize_char(self, v: char) -> Result<Self::Ok, Self::Error> {
+        self.inner.serialize_char(v)
+    }
+
+    fn serialize_str(self, v: &str) -> Result<Self::Ok, Self::Error> {
+        self.inner.serialize_str(v)
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<Self::Ok, Self::Error> {
+        self.inner.serialize_bytes(v)
+    }
+
+    fn serialize_none(self) -> Result<Self::Ok, Self::Error> {
+        self.inner.serialize_none()
+    }
+
+    fn serialize_some<T>(self, value: &T) -> Result<Self::Ok, Self::Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        self.inner.serialize_some(value)
+    }
+
+    fn serialize_unit(self) -> Result<Self::Ok, Self::Error> {
+        self.inner.serialize_unit()
+    }
+
+    fn serialize_unit_struct(self, name: &'static str) -> Result<Self::Ok, Self::Error> {
+        self.inner.serialize_unit_struct(name)
+    }
+
+    fn serialize_unit_variant(
+        self,
+        name: &'static str,
+        variant_index: u32,
+        variant: &'static str,
+    ) -> Result<Self::Ok, Self::Error> {
+        let tag = tag_for(self.tags, variant_index)?;
+        self.inner.serialize_unit_variant(name, tag, variant)
+    }
+
+    fn serialize_newtype_struct<T>(
+        self,
+        name: &'static str,
+        value: &T,
+    ) -> Result<Self::Ok, Self::Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        self.inner.serialize_newtype_struct(name, value)
+    }
+
+    fn serialize_newtype_variant<T>(
+        self,
+        name: &'static str,
+        variant_index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<Self::Ok, Self::Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        let tag = tag_for(self.tags, variant_index)?;
+        self.inner
+            .serialize_newtype_variant(name, tag, variant, value)
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<Self::SerializeSeq, Self::Error> {
+        self.inner.serialize_seq(len)
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<Self::SerializeTuple, Self::Error> {
+        self.inner.serialize_tuple(len)
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        name: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeTupleStruct, Self::Error> {
+        self.inner.serialize_tuple_struct(name, len)
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        name: &'static str,
+        variant_index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeTupleVariant, Self::Error> {
+        let tag = tag_for(self.tags, variant_index)?;
+        self.inner.serialize_tuple_variant(name, tag, variant, len)
+    }
+
+    fn serialize_map(self, len: Option<usize>) -> Result<Self::SerializeMap, Self::Error> {
+        self.inner.serialize_map(len)
+    }
+
+    fn serialize_struct(
+        self,
+        name: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeStruct, Self::Error> {
+        self.inner.serialize_struct(name, len)
+    }
+
+    fn serialize_struct_variant(
+        self,
+        name: &'static str,
+        variant_index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeStructVariant, Self::Error> {
+        let tag = tag_for(self.tags, variant_index)?;
+        self.inner.serialize_struct_variant(name, tag, variant, len)
+    }
+
+    fn is_human_readable(&self) -> bool {
+        self.inner.is_human_readable()
+    }
+}
+
+/// Only `deserialize_enum` is intercepted; everything else goes through `deserialize_any`.
+struct TagDeserializer<D> {
+    inner: D,
+    tags: &'static [u32],
+}
+
+impl<'de, D> de::Deserializer<'de> for TagDeserializer<D>
+where
+    D: de::Deserializer<'de>,
+{
+    type Error = D::Error;
+
+    fn deserialize_any<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: de::Visitor<'de>,
+    {
+        self.inner.deserialize_any(visitor)
+    }
+
+    fn deserialize_enum<V>(
+        self,
+        name: &'static str,
+        variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Self::Error>
+    where
+        V: de::Visitor<'de>,
+    {
+        self.inner.deserialize_enum(
+            name,
+            variants,
+            TagVisitor {
+                inner: visitor,
+                tags: self.tags,
+            },
+        )
+    }
+
+    fn is_human_readable(&self) -> bool {
+        self.inner.is_human_readable()
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf option unit unit_struct newtype_struct seq tuple
+        tuple_struct map struct identifier ignored_any
+    }
+}
+
+/// Wraps the enum visitor so the variant identifier is remapped before it reaches `inner`.
+struct TagVisitor<V> {
+    inner: V,
+    tags: &'static [u32],
+}
+
+impl<'de, V> de::Visitor<'de> for TagVisitor<V>
+where
+    V: de::Visitor<'de>,
+{
+    type Value = V::Value;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        self.inner.expecting(formatter)
+    }
+
+    fn visit_enum<A>(self, data: A) -> Result<Self::Value, A::Error>
+    where
+        A: de::EnumAccess<'de>,
+    {
+        self.inner.visit_enum(TagEnumAccess {
+            inner: data,
+            tags: self.tags,
+        })
+    }
+}
+
+struct TagEnumAccess<A> {
+    inner: A,
+    tags: &'static [u32],
+}
+
+impl<'de, A> de::EnumAccess<'de> for TagEnumAccess<A>
+where
+    A: de::EnumAccess<'de>,
+{
+    type Error = A::Error;
+    type Variant = A::Variant;
+
+    fn variant_seed<V>(self, seed: V) -> Result<(V::Value, Self::Variant), Self::Error>
+    where
+        V: de::DeserializeSeed<'de>,
+    {
+        self.inner.variant_seed(TagSeed {
+            inner: seed,
+            tags: self.tags,
+        })
+    }
+}
+
+/// Reads the raw tag and hands the variant index to `inner`.
+struct TagSeed<S> {
+    inner: S,
+    tags: &'static [u32],
+}
+
+impl<'de, S> de::DeserializeSeed<'de> for TagSeed<S>
+where
+    S: de::DeserializeSeed<'de>,
+{
+    type Value = S::Value;
+
+    fn deserialize<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+    where
+        D: de::Deserializer<'de>,
+    {
+        use serde::de::IntoDeserializer;
+
+        let tag = <u32 as Deserialize>::deserialize(IdentifierDeserializer(deserializer))?;
+        let index = self
+            .tags
+            .iter()
+            .position(|&t| t == tag)
+            .ok_or_else(|| de::Error::custom(format_args!("unknown union tag {}", tag)))?;
+        self.inner.deserialize((index as u32).into_deserializer())
+    }
+}
+
+/// Makes `u32::deserialize` read an enum identifier rather than a fixed-width integer.
+struct IdentifierDeserializer<D>(D);
+
+impl<'de, D> de::Deserializer<'de> for IdentifierDeserializer<D>
+where
+    D: de::Deserializer<'de>,
+{
+    type Error = D::Error;
+
+    fn deserialize_any<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: de::Visitor<'de>,
+    {
+        self.0.deserialize_identifier(visitor)
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf option unit unit_struct newtype_struct seq tuple
+        tuple_struct map struct enum identifier ignored_any
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use serde_derive::{Deserialize, Serialize};
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    enum Kind {
+        A,
+        C(u8),
+    }
+
+    const KIND_TAGS: &[u32] = &[0, 5];
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Message {
+        #[serde(serialize_with = "ser_kind", deserialize_with = "de_kind")]
+        kind: Kind,
+    }
+
+    fn ser_kind<S: serde::Serializer>(kind: &Kind, serializer: S) -> Result<S::Ok, S::Error> {
+        super::serialize(kind, KIND_TAGS, serializer)
+    }
+
+    fn de_kind<'de, D: serde::Deserializer<'de>>(deserializer: D) -> Result<Kind, D::Error> {
+        super::deserialize(KIND_TAGS, deserializer)
+    }
+
+    #[test]
+    fn test_explicit_tags() {
+        let a = Message { kind: Kind::A };
+        let a_bytes = crate::to_vec(&a).unwrap();
+        assert_eq!(a_bytes, &[0]);
+        assert_eq!(crate::from_slice::<Message>(&a_bytes).unwrap(), a);
+
+        let c = Message { kind: Kind::C(7) };
+        let c_bytes = crate::to_vec(&c).unwrap();
+        assert_eq!(c_bytes, &[5, 7]);
+        assert_eq!(crate::from_slice::<Message>(&c_bytes).unwrap(), c);
+
+        assert!(crate::from_slice::<Message>(&[1, 7]).is_err());
+    }
+
+    /// `(u8 = 0 | string = 3 | Point = 4 | void = 9)`
+    #[derive(Debug, PartialEq, Serialize, Deserialize, serde_bare_derive::BareUnion)]
+    enum Value {
+        Byte(u8),
+        #[bare(tag = 3)]
+        Text(String),
+        Point {
+            x: i8,
+            y: i8,
+        },
+        #[bare(tag = 9)]
+        Nothing,
+    }
+
+    #[test]
+    fn test_derived_union() {
+        use super::{BareUnion, Union};
+
+        assert_eq!(Value::TAGS, &[0, 3, 4, 9]);
+        let values = vec![
+            Union(Value::Byte(1)),
+            Union(Value::Text("hi".to_string())),
+            Union(Value::Point { x: -1, y: 2 }),
+            Union(Value::Nothing),
+        ];
+        let bytes = crate::to_vec(&values).unwrap();
+        assert_eq!(bytes, [4, 0, 1, 3, 2, b'h', b'i', 4, 0xff, 2, 9]);
+        assert_eq!(
+            crate::from_slice::<Vec<Union<Value>>>(&bytes).unwrap(),
+            values
+        );
+
+        // The positional indexes 1 and 2 aren't tags of this union.
+        assert!(crate::from_slice::<Union<Value>>(&[1, 2, b'h', b'i']).is_err());
+        assert!(crate::from_slice::<Union<Value>>(&[2, 0, 0]).is_err());
+    }
+}
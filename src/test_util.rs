@@ -0,0 +1,17 @@
+//! Fixtures shared by the tests of several modules.
+use crate::compat::io::{self, Write};
+use alloc::vec::Vec;
+
+/// Records the size of each write.
+pub(crate) struct Writes(pub(crate) Vec<usize>);
+
+impl Write for Writes {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.push(buf.len());
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
@@ -0,0 +1,139 @@
+//! Encodings for `Duration` and `SystemTime`, for use with `#[serde(with = "...")]`.
+
+/// `Duration` as the BARE struct `{ secs: u64, nanos: u32 }`.
+///
+/// `nanos` is below 1,000,000,000; larger values fail to deserialize.
+pub mod duration {
+    use core::time::Duration;
+    use serde::{de::Error as _, Deserialize, Deserializer, Serialize, Serializer};
+
+    pub fn serialize<S>(value: &Duration, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        (value.as_secs(), value.subsec_nanos()).serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<Duration, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let (secs, nanos) = <(u64, u32)>::deserialize(deserializer)?;
+        if nanos >= 1_000_000_000 {
+            return Err(D::Error::custom("nanos out of range"));
+        }
+        Ok(Duration::new(secs, nanos))
+    }
+}
+
+/// `SystemTime` as the BARE struct `{ secs: i64, nanos: u32 }`, counted from the Unix epoch.
+///
+/// `secs` is rounded down, so times before the epoch have negative `secs` and `nanos` still
+/// counts forwards: half a second before the epoch is `{ secs: -1, nanos: 500_000_000 }`.
+#[cfg(feature = "std")]
+pub mod system_time {
+    use core::{convert::TryFrom, time::Duration};
+    use serde::{
+        de::Error as _, ser::Error as _, Deserialize, Deserializer, Serialize, Serializer,
+    };
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    pub fn serialize<S>(value: &SystemTime, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let (secs, nanos) = match value.duration_since(UNIX_EPOCH) {
+            Ok(after) => (i64::try_from(after.as_secs()).ok(), after.subsec_nanos()),
+            Err(e) => {
+                let before = e.duration();
+                let secs = i64::try_from(before.as_secs()).ok().map(|secs| -secs);
+                match before.subsec_nanos() {
+                    0 => (secs, 0),
+                    nanos => (
+                        secs.and_then(|secs| secs.checked_sub(1)),
+                        1_000_000_000 - nanos,
+                    ),
+                }
+            }
+        };
+        let secs = secs.ok_or_else(|| S::Error::custom("time out of range"))?;
+        (secs, nanos).serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<SystemTime, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let (secs, nanos) = <(i64, u32)>::deserialize(deserializer)?;
+        if nanos >= 1_000_000_000 {
+            return Err(D::Error::custom("nanos out of range"));
+        }
+        let whole = Duration::from_secs(secs.unsigned_abs());
+        let time = if secs >= 0 {
+            UNIX_EPOCH.checked_add(whole)
+        } else {
+            UNIX_EPOCH.checked_sub(whole)
+        };
+        time.and_then(|time| time.checked_add(Duration::from_nanos(nanos.into())))
+            .ok_or_else(|| D::Error::custom("time out of range"))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use core::time::Duration;
+    use serde_derive::{Deserialize, Serialize};
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    struct Timeout(#[serde(with = "super::duration")] Duration);
+
+    #[test]
+    fn test_duration() {
+        let timeout = Timeout(Duration::new(90, 500));
+        let bytes = crate::to_vec(&timeout).unwrap();
+        assert_eq!(bytes, [90, 0, 0, 0, 0, 0, 0, 0, 0xf4, 1, 0, 0]);
+        assert_eq!(crate::from_slice::<Timeout>(&bytes).unwrap(), timeout);
+
+        let max = Timeout(Duration::MAX);
+        let bytes = crate::to_vec(&max).unwrap();
+        assert_eq!(crate::from_slice::<Timeout>(&bytes).unwrap(), max);
+
+        let mut bad_nanos = [0; 12];
+        bad_nanos[8..].copy_from_slice(&1_000_000_000u32.to_le_bytes());
+        assert!(crate::from_slice::<Timeout>(&bad_nanos).is_err());
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn test_system_time() {
+        use std::time::{SystemTime, UNIX_EPOCH};
+
+        #[derive(Serialize, Deserialize, Debug, PartialEq)]
+        struct Event(#[serde(with = "super::system_time")] SystemTime);
+
+        let encode = |time: SystemTime| {
+            let bytes = crate::to_vec(&Event(time)).unwrap();
+            assert_eq!(crate::from_slice::<Event>(&bytes).unwrap(), Event(time));
+            crate::from_slice::<(i64, u32)>(&bytes).unwrap()
+        };
+        assert_eq!(encode(UNIX_EPOCH), (0, 0));
+        assert_eq!(
+            encode(UNIX_EPOCH + Duration::new(1_700_000_000, 5)),
+            (1_700_000_000, 5)
+        );
+        assert_eq!(
+            encode(UNIX_EPOCH - Duration::from_millis(500)),
+            (-1, 500_000_000)
+        );
+        assert_eq!(encode(UNIX_EPOCH - Duration::from_secs(86400)), (-86400, 0));
+        assert_eq!(
+            encode(UNIX_EPOCH - Duration::new(86400, 1)),
+            (-86401, 999_999_999)
+        );
+        encode(SystemTime::now());
+
+        let mut bad_nanos = [0; 12];
+        bad_nanos[8..].copy_from_slice(&1_000_000_000u32.to_le_bytes());
+        assert!(crate::from_slice::<Event>(&bad_nanos).is_err());
+    }
+}
@@ -0,0 +1,141 @@
+//! Encodings for types from the `time` crate, for use with `#[serde(with = "...")]`.
+//!
+//! These don't depend on the `time` crate's own serde support, so the encoding is the same
+//! whichever of its features are enabled.
+
+/// `OffsetDateTime` as the BARE struct
+/// `{ unix_timestamp: i64, nanosecond: i32, offset_seconds: i32 }`.
+///
+/// `unix_timestamp` is in UTC, so the offset only affects how the value is displayed.
+pub mod offset_datetime {
+    use core::convert::TryFrom;
+    use serde::{de::Error as _, Deserialize, Deserializer, Serialize, Serializer};
+    use time::{OffsetDateTime, UtcOffset};
+
+    pub fn serialize<S>(value: &OffsetDateTime, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        (
+            value.unix_timestamp(),
+            value.nanosecond() as i32,
+            value.offset().whole_seconds(),
+        )
+            .serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<OffsetDateTime, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let (unix_timestamp, nanosecond, offset_seconds) =
+            <(i64, i32, i32)>::deserialize(deserializer)?;
+        let nanosecond =
+            u32::try_from(nanosecond).map_err(|_| D::Error::custom("nanosecond out of range"))?;
+        let offset = UtcOffset::from_whole_seconds(offset_seconds).map_err(D::Error::custom)?;
+        OffsetDateTime::from_unix_timestamp(unix_timestamp)
+            .and_then(|value| value.replace_nanosecond(nanosecond))
+            .map_err(D::Error::custom)?
+            .checked_to_offset(offset)
+            .ok_or_else(|| D::Error::custom("date out of range"))
+    }
+}
+
+/// `time::Duration` as the BARE struct `{ seconds: i64, nanoseconds: i32 }`.
+///
+/// As in `time::Duration` itself, `nanoseconds` is below 1,000,000,000 in magnitude and has the
+/// same sign as `seconds` unless one of them is zero, so -1.5 seconds is `{ seconds: -1,
+/// nanoseconds: -500_000_000 }`. Other values fail to deserialize.
+pub mod duration {
+    use serde::{de::Error as _, Deserialize, Deserializer, Serialize, Serializer};
+    use time::Duration;
+
+    pub fn serialize<S>(value: &Duration, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        (value.whole_seconds(), value.subsec_nanoseconds()).serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<Duration, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let (seconds, nanoseconds) = <(i64, i32)>::deserialize(deserializer)?;
+        if nanoseconds.unsigned_abs() >= 1_000_000_000 {
+            return Err(D::Error::custom("nanoseconds out of range"));
+        }
+        if seconds.signum() * i64::from(nanoseconds.signum()) < 0 {
+            return Err(D::Error::custom(
+                "seconds and nanoseconds have different signs",
+            ));
+        }
+        Ok(Duration::new(seconds, nanoseconds))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use serde_derive::{Deserialize, Serialize};
+    use time::{Duration, OffsetDateTime, UtcOffset};
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    struct Event {
+        #[serde(with = "super::offset_datetime")]
+        at: OffsetDateTime,
+    }
+
+    #[test]
+    fn test_offset_datetime() {
+        let offset = UtcOffset::from_hms(-5, -30, 0).unwrap();
+        let at = OffsetDateTime::from_unix_timestamp_nanos(1_600_000_000_123_456_789)
+            .unwrap()
+            .to_offset(offset);
+        let event = Event { at };
+        let bytes = crate::to_vec(&event).unwrap();
+        let expected = crate::to_vec(&(1_600_000_000i64, 123_456_789i32, -19_800i32)).unwrap();
+        assert_eq!(bytes, expected);
+        let got: Event = crate::from_slice(&bytes).unwrap();
+        assert_eq!(got, event);
+        assert_eq!(got.at.offset(), offset);
+        assert_eq!(got.at.nanosecond(), 123_456_789);
+
+        let before_epoch = Event {
+            at: OffsetDateTime::from_unix_timestamp_nanos(-1_500_000_000).unwrap(),
+        };
+        let bytes = crate::to_vec(&before_epoch).unwrap();
+        assert_eq!(crate::from_slice::<Event>(&bytes).unwrap(), before_epoch);
+
+        let bad_nanos = crate::to_vec(&(0i64, 1_000_000_000i32, 0i32)).unwrap();
+        assert!(crate::from_slice::<Event>(&bad_nanos).is_err());
+        let bad_offset = crate::to_vec(&(0i64, 0i32, 100_000i32)).unwrap();
+        assert!(crate::from_slice::<Event>(&bad_offset).is_err());
+    }
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    struct Timeout(#[serde(with = "super::duration")] Duration);
+
+    #[test]
+    fn test_duration() {
+        let timeout = Timeout(Duration::new(90, 500));
+        let bytes = crate::to_vec(&timeout).unwrap();
+        assert_eq!(bytes, [90, 0, 0, 0, 0, 0, 0, 0, 0xf4, 1, 0, 0]);
+        assert_eq!(crate::from_slice::<Timeout>(&bytes).unwrap(), timeout);
+
+        let negative = Timeout(Duration::milliseconds(-1_500));
+        let bytes = crate::to_vec(&negative).unwrap();
+        let expected = crate::to_vec(&(-1i64, -500_000_000i32)).unwrap();
+        assert_eq!(bytes, expected);
+        assert_eq!(crate::from_slice::<Timeout>(&bytes).unwrap(), negative);
+
+        for extreme in [Duration::MIN, Duration::MAX, Duration::nanoseconds(-1)] {
+            let bytes = crate::to_vec(&Timeout(extreme)).unwrap();
+            assert_eq!(crate::from_slice::<Timeout>(&bytes).unwrap().0, extreme);
+        }
+
+        let bad_nanos = crate::to_vec(&(0i64, -1_000_000_000i32)).unwrap();
+        assert!(crate::from_slice::<Timeout>(&bad_nanos).is_err());
+        let mixed_signs = crate::to_vec(&(1i64, -1i32)).unwrap();
+        assert!(crate::from_slice::<Timeout>(&mixed_signs).is_err());
+    }
+}
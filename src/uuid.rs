@@ -0,0 +1,51 @@
+//! `uuid::Uuid` as BARE `data<16>`, for use with `#[serde(with = "serde_bare::uuid")]`.
+//!
+//! The 16 bytes are those of `Uuid::as_bytes`, in the order the UUID is written out, with no
+//! length prefix.
+use crate::Data;
+use ::uuid::Uuid;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+pub fn serialize<S>(value: &Uuid, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    Data(*value.as_bytes()).serialize(serializer)
+}
+
+pub fn deserialize<'de, D>(deserializer: D) -> Result<Uuid, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let Data(bytes) = Data::<16>::deserialize(deserializer)?;
+    Ok(Uuid::from_bytes(bytes))
+}
+
+#[cfg(test)]
+mod test {
+    use ::uuid::Uuid;
+    use serde_derive::{Deserialize, Serialize};
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    struct Record {
+        #[serde(with = "super")]
+        id: Uuid,
+        version: u8,
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let id = Uuid::from_u128(0x67e5_5044_10b1_426f_9247_bb68_0e5f_e0c8);
+        let record = Record { id, version: 1 };
+        let bytes = crate::to_vec(&record).unwrap();
+        assert_eq!(
+            bytes,
+            [
+                0x67, 0xe5, 0x50, 0x44, 0x10, 0xb1, 0x42, 0x6f, 0x92, 0x47, 0xbb, 0x68, 0x0e, 0x5f,
+                0xe0, 0xc8, 1,
+            ]
+        );
+        assert_eq!(crate::from_slice::<Record>(&bytes).unwrap(), record);
+        assert!(crate::from_slice::<Record>(&bytes[..16]).is_err());
+    }
+}
@@ -0,0 +1,412 @@
+//! Layout-driven decoding into a dynamic [`Value`].
+//!
+//! BARE isn't self-describing, so decoding a message without a matching Rust type needs a
+//! [`Layout`] describing it instead.
+use crate::{
+    compat::io::Read,
+    de::{checked_length, read_bytes, Deserializer},
+    error::Error,
+    ser::to_writer,
+    Int, Uint, CANONICAL_NAN_F32, CANONICAL_NAN_F64,
+};
+use alloc::{boxed::Box, string::String, vec::Vec};
+use serde::{de::DeserializeOwned, Serialize};
+
+/// The layout of a BARE type. Unlike [`BareSchema`](crate::BareSchema), which writes schema
+/// text, this drives decoding.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Layout {
+    Uint,
+    Int,
+    U8,
+    U16,
+    U32,
+    U64,
+    I8,
+    I16,
+    I32,
+    I64,
+    F32,
+    F64,
+    Bool,
+    Str,
+    /// `data`
+    Data,
+    /// `data<N>`
+    FixedData(usize),
+    Void,
+    Optional(Box<Layout>),
+    /// `[]type`
+    List(Box<Layout>),
+    /// `[N]type`
+    FixedList(Box<Layout>, usize),
+    Map(Box<Layout>, Box<Layout>),
+    /// The members of a union and their tags.
+    Union(Vec<(u64, Layout)>),
+    /// The types of a struct's fields, in order.
+    Struct(Vec<Layout>),
+}
+
+impl Layout {
+    /// Whether values of this layout encode to no bytes at all.
+    pub(crate) fn is_zero_width(&self) -> bool {
+        match self {
+            Layout::Void | Layout::FixedData(0) | Layout::FixedList(_, 0) => true,
+            Layout::FixedList(inner, _) => inner.is_zero_width(),
+            Layout::Struct(fields) => fields.iter().all(Layout::is_zero_width),
+            _ => false,
+        }
+    }
+}
+
+/// A decoded BARE value.
+///
+/// Fixed-width integers decode to `Uint` and `Int`, and fixed-length data and lists decode to
+/// `Bytes` and `Seq`.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+    Uint(u64),
+    Int(i64),
+    F32(f32),
+    F64(f64),
+    Bool(bool),
+    Str(String),
+    Bytes(Vec<u8>),
+    Void,
+    Optional(Option<Box<Value>>),
+    Seq(Vec<Value>),
+    Map(Vec<(Value, Value)>),
+    /// The tag of the union member, and its value.
+    Union(u64, Box<Value>),
+    Struct(Vec<Value>),
+}
+
+fn read<R: Read, T: DeserializeOwned>(reader: &mut R) -> Result<T, Error> {
+    T::deserialize(&mut Deserializer::new(reader))
+}
+
+fn read_len<R: Read>(reader: &mut R) -> Result<usize, Error> {
+    let Uint(len) = read(reader)?;
+    checked_length(len)
+}
+
+fn read_value<R: Read>(reader: &mut R, layout: &Layout) -> Result<Value, Error> {
+    Ok(match layout {
+        Layout::Uint => Value::Uint(read::<_, Uint>(reader)?.0),
+        Layout::Int => Value::Int(read::<_, Int>(reader)?.0),
+        Layout::U8 => Value::Uint(read::<_, u8>(reader)?.into()),
+        Layout::U16 => Value::Uint(read::<_, u16>(reader)?.into()),
+        Layout::U32 => Value::Uint(read::<_, u32>(reader)?.into()),
+        Layout::U64 => Value::Uint(read(reader)?),
+        Layout::I8 => Value::Int(read::<_, i8>(reader)?.into()),
+        Layout::I16 => Value::Int(read::<_, i16>(reader)?.into()),
+        Layout::I32 => Value::Int(read::<_, i32>(reader)?.into()),
+        Layout::I64 => Value::Int(read(reader)?),
+        Layout::F32 => Value::F32(read(reader)?),
+        Layout::F64 => Value::F64(read(reader)?),
+        Layout::Bool => Value::Bool(read(reader)?),
+        Layout::Str => Value::Str(read(reader)?),
+        Layout::Data => {
+            let len = read_len(reader)?;
+            Value::Bytes(read_bytes(&mut *reader, len)?)
+        }
+        Layout::FixedData(len) => Value::Bytes(read_bytes(&mut *reader, *len)?),
+        Layout::Void => Value::Void,
+        Layout::Optional(inner) => Value::Optional(if read(reader)? {
+            Some(Box::new(read_value(reader, inner)?))
+        } else {
+            None
+        }),
+        Layout::List(inner) => {
+            let len = read_len(reader)?;
+            if len > 0 && inner.is_zero_width() {
+                return Err(Error::ZeroWidthElements);
+            }
+            Value::Seq(read_values(reader, inner, len)?)
+        }
+        Layout::FixedList(inner, len) => Value::Seq(read_values(reader, inner, *len)?),
+        Layout::Map(key, value) => {
+            let len = read_len(reader)?;
+            if len > 0 && key.is_zero_width() && value.is_zero_width() {
+                return Err(Error::ZeroWidthElements);
+            }
+            let mut entries = Vec::with_capacity(len.min(256));
+            for _ in 0..len {
+                entries.push((read_value(reader, key)?, read_value(reader, value)?));
+            }
+            Value::Map(entries)
+        }
+        Layout::Union(members) => {
+            let Uint(tag) = read(reader)?;
+            let member = members
+                .iter()
+                .find(|(t, _)| *t == tag)
+                .ok_or(Error::UnknownUnionTag(tag))?;
+            Value::Union(tag, Box::new(read_value(reader, &member.1)?))
+        }
+        Layout::Struct(fields) => Value::Struct(
+            fields
+                .iter()
+                .map(|field| read_value(reader, field))
+                .collect::<Result<_, _>>()?,
+        ),
+    })
+}
+
+fn read_values<R: Read>(reader: &mut R, layout: &Layout, len: usize) -> Result<Vec<Value>, Error> {
+    // Don't trust the length for the initial allocation.
+    let mut values = Vec::with_capacity(len.min(256));
+    for _ in 0..len {
+        values.push(read_value(reader, layout)?);
+    }
+    Ok(values)
+}
+
+/// Decode a value laid out as `layout` from `slice`.
+pub fn from_slice_with_layout(mut slice: &[u8], layout: &Layout) -> Result<Value, Error> {
+    read_value(&mut slice, layout)
+}
+
+fn write<T: Serialize>(out: &mut Vec<u8>, value: &T) -> Result<(), Error> {
+    to_writer(out, value)
+}
+
+/// Encode `value`, read as `layout`, in canonical form: minimal varints and canonical NaNs.
+fn write_value(out: &mut Vec<u8>, layout: &Layout, value: &Value) -> Result<(), Error> {
+    match (layout, value) {
+        (Layout::Uint, Value::Uint(v)) => write(out, &Uint(*v)),
+        (Layout::Int, Value::Int(v)) => write(out, &Int(*v)),
+        (Layout::U8, Value::Uint(v)) => write(out, &(*v as u8)),
+        (Layout::U16, Value::Uint(v)) => write(out, &(*v as u16)),
+        (Layout::U32, Value::Uint(v)) => write(out, &(*v as u32)),
+        (Layout::U64, Value::Uint(v)) => write(out, v),
+        (Layout::I8, Value::Int(v)) => write(out, &(*v as i8)),
+        (Layout::I16, Value::Int(v)) => write(out, &(*v as i16)),
+        (Layout::I32, Value::Int(v)) => write(out, &(*v as i32)),
+        (Layout::I64, Value::Int(v)) => write(out, v),
+        (Layout::F32, Value::F32(v)) if v.is_nan() => write(out, &CANONICAL_NAN_F32),
+        (Layout::F32, Value::F32(v)) => write(out, v),
+        (Layout::F64, Value::F64(v)) if v.is_nan() => write(out, &CANONICAL_NAN_F64),
+        (Layout::F64, Value::F64(v)) => write(out, v),
+        (Layout::Bool, Value::Bool(v)) => write(out, v),
+        (Layout::Str, Value::Str(v)) => write(out, v),
+        (Layout::Data, Value::Bytes(v)) => {
+            write(out, &Uint(v.len() as u64))?;
+            out.extend_from_slice(v);
+            Ok(())
+        }
+        (Layout::FixedData(_), Value::Bytes(v)) => {
+            out.extend_from_slice(v);
+            Ok(())
+        }
+        (Layout::Void, Value::Void) => Ok(()),
+        (Layout::Optional(inner), Value::Optional(v)) => {
+            write(out, &v.is_some())?;
+            match v {
+                Some(v) => write_value(out, inner, v),
+                None => Ok(()),
+            }
+        }
+        (Layout::List(inner), Value::Seq(values)) => {
+            write(out, &Uint(values.len() as u64))?;
+            write_values(out, inner, values)
+        }
+        (Layout::FixedList(inner, _), Value::Seq(values)) => write_values(out, inner, values),
+        (Layout::Map(key, value), Value::Map(entries)) => {
+            write(out, &Uint(entries.len() as u64))?;
+            for (k, v) in entries {
+                write_value(out, key, k)?;
+                write_value(out, value, v)?;
+            }
+            Ok(())
+        }
+        (Layout::Union(members), Value::Union(tag, v)) => {
+            write(out, &Uint(*tag))?;
+            let (_, member) = members
+                .iter()
+                .find(|(t, _)| t == tag)
+                .ok_or(Error::UnknownUnionTag(*tag))?;
+            write_value(out, member, v)
+        }
+        (Layout::Struct(fields), Value::Struct(values)) => {
+            for (field, v) in fields.iter().zip(values) {
+                write_value(out, field, v)?;
+            }
+            Ok(())
+        }
+        _ => unreachable!("value wasn't decoded with this layout"),
+    }
+}
+
+fn write_values(out: &mut Vec<u8>, layout: &Layout, values: &[Value]) -> Result<(), Error> {
+    values.iter().try_for_each(|v| write_value(out, layout, v))
+}
+
+/// Whether `bytes` is the canonical encoding of a value laid out as `layout`, without a Rust type
+/// for it: it's decoded with `from_slice_with_layout`, encoded again with minimal varints and
+/// canonical NaNs, and compared with `bytes`, so trailing bytes make it non-canonical too.
+/// Bytes that don't decode as `layout` at all are an error.
+pub fn is_canonical_dynamic(layout: &Layout, bytes: &[u8]) -> Result<bool, Error> {
+    let value = from_slice_with_layout(bytes, layout)?;
+    let mut canonical = Vec::with_capacity(bytes.len());
+    write_value(&mut canonical, layout, &value)?;
+    Ok(canonical == bytes)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use alloc::{collections::BTreeMap, string::ToString, vec};
+    use serde_derive::Serialize;
+
+    #[derive(Serialize)]
+    enum Shape {
+        Circle(f64),
+        Square { side: f32 },
+    }
+
+    #[derive(Serialize)]
+    struct Inner {
+        id: Uint,
+        tags: Vec<String>,
+    }
+
+    #[derive(Serialize)]
+    struct Outer {
+        name: String,
+        delta: i16,
+        inner: Option<Inner>,
+        attrs: BTreeMap<u8, bool>,
+        shapes: Vec<Shape>,
+        raw: crate::Data<2>,
+    }
+
+    #[test]
+    fn test_struct() {
+        let mut attrs = BTreeMap::new();
+        attrs.insert(1, true);
+        attrs.insert(2, false);
+        let bytes = crate::to_vec(&Outer {
+            name: "outer".to_string(),
+            delta: -7,
+            inner: Some(Inner {
+                id: Uint(300),
+                tags: vec!["a".to_string(), "b".to_string()],
+            }),
+            attrs,
+            shapes: vec![Shape::Square { side: 1.5 }, Shape::Circle(2.0)],
+            raw: crate::Data([0xde, 0xad]),
+        })
+        .unwrap();
+
+        let layout = Layout::Struct(vec![
+            Layout::Str,
+            Layout::I16,
+            Layout::Optional(Box::new(Layout::Struct(vec![
+                Layout::Uint,
+                Layout::List(Box::new(Layout::Str)),
+            ]))),
+            Layout::Map(Box::new(Layout::U8), Box::new(Layout::Bool)),
+            Layout::List(Box::new(Layout::Union(vec![
+                (0, Layout::F64),
+                (1, Layout::Struct(vec![Layout::F32])),
+            ]))),
+            Layout::FixedData(2),
+        ]);
+        let value = from_slice_with_layout(&bytes, &layout).unwrap();
+        assert_eq!(
+            value,
+            Value::Struct(vec![
+                Value::Str("outer".to_string()),
+                Value::Int(-7),
+                Value::Optional(Some(Box::new(Value::Struct(vec![
+                    Value::Uint(300),
+                    Value::Seq(vec![
+                        Value::Str("a".to_string()),
+                        Value::Str("b".to_string())
+                    ]),
+                ])))),
+                Value::Map(vec![
+                    (Value::Uint(1), Value::Bool(true)),
+                    (Value::Uint(2), Value::Bool(false)),
+                ]),
+                Value::Seq(vec![
+                    Value::Union(1, Box::new(Value::Struct(vec![Value::F32(1.5)]))),
+                    Value::Union(0, Box::new(Value::F64(2.0))),
+                ]),
+                Value::Bytes(vec![0xde, 0xad]),
+            ])
+        );
+
+        // The layout doesn't match the payload
+        let layout = Layout::Union(vec![(0, Layout::Void)]);
+        assert!(matches!(
+            from_slice_with_layout(&[1], &layout),
+            Err(Error::UnknownUnionTag(1))
+        ));
+        assert!(matches!(
+            from_slice_with_layout(&bytes[..3], &Layout::Str),
+            Err(Error::Eof)
+        ));
+    }
+
+    #[test]
+    fn test_is_canonical_dynamic() {
+        let layout = Layout::Struct(vec![
+            Layout::Uint,
+            Layout::Optional(Box::new(Layout::Str)),
+            Layout::List(Box::new(Layout::F32)),
+            Layout::Union(vec![(0, Layout::Void), (3, Layout::I16)]),
+        ]);
+        let canonical = [
+            0xac, 0x02, 1, 2, b'h', b'i', 1, 0, 0, 0xc0, 0x7f, 3, 0xfe, 0xff,
+        ];
+        assert!(is_canonical_dynamic(&layout, &canonical).unwrap());
+
+        // 300 padded out to three bytes
+        let padded = [
+            0xac, 0x82, 0x00, 1, 2, b'h', b'i', 1, 0, 0, 0xc0, 0x7f, 3, 0xfe, 0xff,
+        ];
+        assert!(!is_canonical_dynamic(&layout, &padded).unwrap());
+        // A NaN with a payload
+        let mut nan = canonical;
+        nan[7] = 1;
+        assert!(!is_canonical_dynamic(&layout, &nan).unwrap());
+        // Trailing bytes
+        let mut trailing = canonical.to_vec();
+        trailing.push(0);
+        assert!(!is_canonical_dynamic(&layout, &trailing).unwrap());
+
+        assert!(matches!(
+            is_canonical_dynamic(&layout, &canonical[..5]),
+            Err(Error::Eof)
+        ));
+    }
+
+    #[test]
+    fn test_zero_width_elements() {
+        // A length of 2^28 - 1, which must not be looped over when the elements read nothing.
+        let bytes = [0xff, 0xff, 0xff, 0x7f];
+        let voids = Layout::List(Box::new(Layout::FixedList(Box::new(Layout::Void), 4)));
+        let map = Layout::Map(
+            Box::new(Layout::Struct(vec![])),
+            Box::new(Layout::FixedData(0)),
+        );
+        for layout in [&voids, &map] {
+            assert!(matches!(
+                from_slice_with_layout(&bytes, layout),
+                Err(Error::ZeroWidthElements)
+            ));
+        }
+
+        // Empty ones are still fine
+        assert_eq!(
+            from_slice_with_layout(&[0], &voids).unwrap(),
+            Value::Seq(vec![])
+        );
+        assert_eq!(
+            from_slice_with_layout(&[0], &map).unwrap(),
+            Value::Map(vec![])
+        );
+    }
+}